@@ -0,0 +1,324 @@
+//! `clipsnap --install-autostart` / `--uninstall-autostart`: start ClipSnap
+//! on login through an XDG autostart entry,
+//! `~/.config/autostart/clipsnap.desktop`.
+//!
+//! The entry points at the running executable. An entry written by an
+//! earlier install is updated freely; one the user has edited is never
+//! overwritten, and uninstalling disables it with `Hidden=true` instead of
+//! deleting it.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Group holding the keys the autostart spec reads.
+const GROUP: &str = "[Desktop Entry]";
+
+/// Path of the autostart entry.
+pub fn autostart_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("~/.config"))
+        .join("autostart")
+        .join("clipsnap.desktop")
+}
+
+/// The entry `install` writes for the executable at `exe`.
+pub fn desktop_entry(exe: &Path) -> String {
+    let exe = exe.to_string_lossy();
+    format!(
+        "{}\n\
+         Type=Application\n\
+         Name=ClipSnap\n\
+         Comment=Area Screenshot & Clipboard History Manager\n\
+         Exec={}\n\
+         TryExec={}\n\
+         Icon=accessories-clipboard\n\
+         Terminal=false\n\
+         Hidden=false\n\
+         X-GNOME-Autostart-enabled=true\n",
+        GROUP,
+        escape_value(&quote_exec(&exe)),
+        escape_value(&exe),
+    )
+}
+
+/// Quote a program path for an `Exec` key, per the desktop entry spec.
+fn quote_exec(path: &str) -> String {
+    let path = path.replace('%', "%%");
+    let reserved = |c: char| c.is_whitespace() || "\"'\\><~|&;$*?#()`".contains(c);
+    if !path.contains(reserved) {
+        return path;
+    }
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Escape a string value for a desktop file.
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Undo `escape_value`.
+fn unescape_value(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Value of `key` in the `[Desktop Entry]` group of `content`, unescaped.
+fn entry_value(content: &str, key: &str) -> Option<String> {
+    let mut in_group = false;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_group = line == GROUP;
+        } else if let Some((k, v)) = line.split_once('=').filter(|_| in_group) {
+            if k.trim() == key {
+                return Some(unescape_value(v.trim()));
+            }
+        }
+    }
+    None
+}
+
+/// Whether `content` is exactly what `install` writes, for whichever
+/// executable it names. Anything else has been edited by hand.
+fn is_generated(content: &str) -> bool {
+    entry_value(content, "TryExec").is_some_and(|exe| content == desktop_entry(Path::new(&exe)))
+}
+
+/// `content` with `Hidden=true` set in its `[Desktop Entry]` group, which
+/// tells the session not to start it. Other lines are kept as they are.
+fn hide(content: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_group = false;
+    let mut done = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_group = trimmed == GROUP;
+            lines.push(line.to_string());
+            if in_group && !done {
+                lines.push("Hidden=true".to_string());
+                done = true;
+            }
+            continue;
+        }
+        // Drop any earlier Hidden line in the group; the one above wins
+        let is_hidden_key = trimmed.split_once('=').is_some_and(|(k, _)| k.trim() == "Hidden");
+        if !(in_group && is_hidden_key) {
+            lines.push(line.to_string());
+        }
+    }
+    if !done {
+        lines.push(GROUP.to_string());
+        lines.push("Hidden=true".to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+/// What `install` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Installed {
+    Written,
+    /// An entry from an earlier install pointed elsewhere and was rewritten.
+    Updated,
+    Unchanged,
+    /// The existing entry was edited by hand and left alone.
+    KeptEdited,
+}
+
+/// Write the autostart entry at `path` for `exe`.
+pub fn install(path: &Path, exe: &Path) -> Result<Installed> {
+    let wanted = desktop_entry(exe);
+    let outcome = match fs::read_to_string(path) {
+        Ok(existing) if existing == wanted => return Ok(Installed::Unchanged),
+        Ok(existing) if is_generated(&existing) => Installed::Updated,
+        Ok(_) => return Ok(Installed::KeptEdited),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Installed::Written,
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+    fs::write(path, wanted).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(outcome)
+}
+
+/// What `uninstall` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Uninstalled {
+    Removed,
+    /// The entry was edited by hand, so it was kept with `Hidden=true`.
+    Disabled,
+    NotInstalled,
+}
+
+/// Remove the autostart entry at `path`, or disable it if it was edited.
+pub fn uninstall(path: &Path) -> Result<Uninstalled> {
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Uninstalled::NotInstalled),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+    if is_generated(&existing) {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+        return Ok(Uninstalled::Removed);
+    }
+    if entry_value(&existing, "Hidden").as_deref() != Some("true") {
+        fs::write(path, hide(&existing)).with_context(|| format!("Failed to write {:?}", path))?;
+    }
+    Ok(Uninstalled::Disabled)
+}
+
+/// Describe the autostart entry at `path` for `clipsnap doctor`. Fails only
+/// when the entry would start a program that is gone.
+pub fn status(path: &Path) -> Result<String, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok("not installed (clipsnap --install-autostart adds it)".to_string())
+        }
+        Err(e) => return Err(format!("Cannot read {:?}: {}", path, e)),
+    };
+    if entry_value(&content, "Hidden").as_deref() == Some("true") {
+        return Ok(format!("disabled with Hidden=true in {:?}", path));
+    }
+    match entry_value(&content, "TryExec") {
+        Some(exe) if !Path::new(&exe).exists() => {
+            Err(format!("{:?} starts {}, which no longer exists", path, exe))
+        }
+        _ => Ok(format!("starts on login ({:?})", path)),
+    }
+}
+
+/// Run `clipsnap --install-autostart`. Returns the process exit code.
+pub fn run_install() -> i32 {
+    let path = autostart_path();
+    let result = std::env::current_exe()
+        .context("Failed to find the running executable")
+        .and_then(|exe| install(&path, &exe));
+    match result {
+        Ok(Installed::Written) => println!("Wrote {:?}; ClipSnap will start on login.", path),
+        Ok(Installed::Updated) => println!("Updated {:?} to start this executable.", path),
+        Ok(Installed::Unchanged) => println!("{:?} is already installed.", path),
+        Ok(Installed::KeptEdited) => {
+            println!("{:?} exists and has been edited; left it as it is.", path);
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("Installing autostart failed: {:#}", e);
+            return 1;
+        }
+    }
+    0
+}
+
+/// Run `clipsnap --uninstall-autostart`. Returns the process exit code.
+pub fn run_uninstall() -> i32 {
+    let path = autostart_path();
+    match uninstall(&path) {
+        Ok(Uninstalled::Removed) => println!("Removed {:?}.", path),
+        Ok(Uninstalled::Disabled) => println!("{:?} has been edited; disabled it with Hidden=true instead of removing it.", path),
+        Ok(Uninstalled::NotInstalled) => println!("{:?} does not exist; nothing to do.", path),
+        Err(e) => {
+            eprintln!("Removing autostart failed: {:#}", e);
+            return 1;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desktop_entry_quoting() {
+        let plain = desktop_entry(Path::new("/usr/local/bin/clipsnap"));
+        assert!(plain.contains("\nExec=/usr/local/bin/clipsnap\n"));
+        assert!(plain.contains("\nTryExec=/usr/local/bin/clipsnap\n"));
+        assert!(plain.contains("\nHidden=false\n"));
+
+        let odd = desktop_entry(Path::new("/home/a b/100%/$x\\y"));
+        assert!(odd.contains("\nExec=\"/home/a b/100%%/\\\\$x\\\\\\\\y\"\n"), "{}", odd);
+        assert_eq!(entry_value(&odd, "TryExec").as_deref(), Some("/home/a b/100%/$x\\y"));
+        assert!(is_generated(&odd));
+    }
+
+    #[test]
+    fn test_hide_keeps_edits() {
+        let edited = "# mine\n[Desktop Entry]\nExec=clipsnap --verbose\nHidden = false\n\n[Desktop Action x]\nHidden=false\n";
+        let hidden = hide(edited);
+        assert_eq!(
+            hidden,
+            "# mine\n[Desktop Entry]\nHidden=true\nExec=clipsnap --verbose\n\n[Desktop Action x]\nHidden=false\n"
+        );
+        assert_eq!(entry_value(&hidden, "Hidden").as_deref(), Some("true"));
+        assert_eq!(entry_value("Exec=x\n", "Exec"), None);
+    }
+
+    #[test]
+    fn test_install_and_uninstall() {
+        let dir = std::env::temp_dir().join(format!("clipsnap_autostart_{}", std::process::id()));
+        let path = dir.join("autostart").join("clipsnap.desktop");
+        let exe = std::env::current_exe().unwrap();
+
+        assert!(status(&path).unwrap().starts_with("not installed"));
+        assert_eq!(install(&path, &exe).unwrap(), Installed::Written);
+        assert_eq!(install(&path, &exe).unwrap(), Installed::Unchanged);
+        assert!(status(&path).unwrap().starts_with("starts on login"));
+
+        // An entry for a moved executable is ours to update, but it is broken
+        fs::write(&path, desktop_entry(Path::new("/nonexistent/clipsnap"))).unwrap();
+        assert!(status(&path).is_err());
+        assert_eq!(install(&path, &exe).unwrap(), Installed::Updated);
+        assert_eq!(uninstall(&path).unwrap(), Uninstalled::Removed);
+        assert!(!path.exists());
+        assert_eq!(uninstall(&path).unwrap(), Uninstalled::NotInstalled);
+
+        // Hand edits are never overwritten or deleted
+        let edited = desktop_entry(&exe).replace("Terminal=false", "Terminal=false\nX-Delay=5");
+        fs::write(&path, &edited).unwrap();
+        assert_eq!(install(&path, &exe).unwrap(), Installed::KeptEdited);
+        assert_eq!(fs::read_to_string(&path).unwrap(), edited);
+        assert_eq!(uninstall(&path).unwrap(), Uninstalled::Disabled);
+        let disabled = fs::read_to_string(&path).unwrap();
+        assert!(disabled.contains("X-Delay=5"));
+        assert!(status(&path).unwrap().starts_with("disabled"));
+        assert_eq!(uninstall(&path).unwrap(), Uninstalled::Disabled);
+        assert_eq!(fs::read_to_string(&path).unwrap(), disabled);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,814 @@
+use crate::config::{CaptureConfig, CaptureOutput, Config};
+use crate::models::{CaptureMethod, LargeImageFallback, Rectangle, RedactStyle};
+use crate::{clipboard, database::Database, events, notifications, post_capture, screenshot, temp_files};
+use anyhow::{Context, Result};
+use arboard::Clipboard;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Action names used to look up per-action capture settings.
+pub const ACTION_REGION: &str = "region";
+pub const ACTION_REPEAT_REGION: &str = "repeat_region";
+pub const ACTION_CLIPBOARD_IMAGE: &str = "clipboard_image";
+pub const ACTION_WINDOW: &str = "window";
+pub const ACTION_FULLSCREEN: &str = "fullscreen";
+
+/// Capture sessions currently running; see [`CaptureSession`].
+static CAPTURES_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// A running capture: an overlay or window picker from the moment it opens
+/// until the capture it scheduled is stored, or a direct capture. Dropping it
+/// ends the session.
+pub struct CaptureSession {
+    counter: &'static AtomicUsize,
+}
+
+impl CaptureSession {
+    /// Start a session, or `None` while `limit` sessions are already running
+    /// (`capture.max_concurrent_captures`).
+    pub fn begin(limit: usize) -> Option<Self> {
+        Self::begin_on(&CAPTURES_IN_FLIGHT, limit)
+    }
+
+    fn begin_on(counter: &'static AtomicUsize, limit: usize) -> Option<Self> {
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < limit.max(1)).then_some(n + 1))
+            .ok()
+            .map(|_| CaptureSession { counter })
+    }
+}
+
+impl Drop for CaptureSession {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Start a capture session, telling the user when the hotkey is ignored
+/// because too many are running already.
+pub fn begin_session(config: &Config) -> Option<CaptureSession> {
+    let session = CaptureSession::begin(config.capture.max_concurrent_captures);
+    if session.is_none() {
+        log::info!("A capture is already in progress; ignoring the request");
+        notifications::notify_screenshot_error(config, "A capture is already in progress");
+    }
+    session
+}
+
+/// Outcome of checking an overlay selection against `capture.min_selection_px`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionCheck {
+    Capture,
+    TooSmall,
+    Discard,
+}
+
+/// Decide what to do with a `width`×`height` selection in device pixels.
+pub fn check_selection(width: u32, height: u32, config: &CaptureConfig) -> SelectionCheck {
+    if width == 0 || height == 0 {
+        return SelectionCheck::Discard;
+    }
+    let min = config.min_selection_px.max(1);
+    if width >= min && height >= min {
+        return SelectionCheck::Capture;
+    }
+    match config.small_selection.as_str() {
+        "capture" => SelectionCheck::Capture,
+        "ignore" => SelectionCheck::Discard,
+        _ => SelectionCheck::TooSmall,
+    }
+}
+
+/// Move an overlay point onto the edges of the topmost window under it,
+/// each axis independently, when an edge is within `distance`. `windows`
+/// are `(x, y, width, height)` in the point's coordinates, topmost first
+/// (see `screenshot::client_windows`).
+pub fn snap_to_window_edges(point: (f64, f64), windows: &[(f64, f64, f64, f64)], distance: f64) -> (f64, f64) {
+    let (x, y) = point;
+    let under = windows.iter().find(|&&(wx, wy, ww, wh)| {
+        x >= wx - distance && x <= wx + ww + distance && y >= wy - distance && y <= wy + wh + distance
+    });
+    let Some(&(wx, wy, ww, wh)) = under else {
+        return point;
+    };
+    let snap = |value: f64, edges: [f64; 2]| {
+        edges
+            .into_iter()
+            .map(|edge| (edge, (edge - value).abs()))
+            .filter(|&(_, gap)| gap <= distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(value, |(edge, _)| edge)
+    };
+    (snap(x, [wx, wx + ww]), snap(y, [wy, wy + wh]))
+}
+
+/// Areas marked in the overlay, as (x, y, width, height) in logical
+/// coordinates, mapped to pixels of an image whose top-left corner is at
+/// `origin` (also logical). Areas outside the image are clipped later, by
+/// [`screenshot::redact`].
+pub fn redaction_areas(marked: &[(f64, f64, f64, f64)], origin: (f64, f64), scale: f64) -> Vec<Rectangle> {
+    marked
+        .iter()
+        .map(|&(x, y, w, h)| Rectangle {
+            x: ((x - origin.0) * scale).round() as i32,
+            y: ((y - origin.1) * scale).round() as i32,
+            width: (w * scale).round() as u32,
+            height: (h * scale).round() as u32,
+        })
+        .collect()
+}
+
+/// Parse a region typed in the overlay as "x,y,w,h" in screen pixels and
+/// check that it lies within `bounds`. The error is meant to be shown to the
+/// user as is.
+pub fn parse_typed_region(text: &str, bounds: Rectangle) -> Result<Rectangle, String> {
+    let parts: Vec<&str> = text.split(',').map(str::trim).collect();
+    let [x, y, w, h] = parts[..] else {
+        return Err("Enter four numbers: x,y,w,h".to_string());
+    };
+    let number = |part: &str| part.parse::<i64>().map_err(|_| format!("{:?} is not a whole number", part));
+    let (x, y, w, h) = (number(x)?, number(y)?, number(w)?, number(h)?);
+    if w <= 0 || h <= 0 {
+        return Err("Width and height must be at least 1".to_string());
+    }
+    let (left, top) = (bounds.x as i64, bounds.y as i64);
+    let (right, bottom) = (left + bounds.width as i64, top + bounds.height as i64);
+    if x < left || y < top || x + w > right || y + h > bottom {
+        return Err(format!("Region must fit within {},{} – {},{}", left, top, right, bottom));
+    }
+    Ok(Rectangle {
+        x: x as i32,
+        y: y as i32,
+        width: w as u32,
+        height: h as u32,
+    })
+}
+
+/// How far, in logical pixels, the pointer may move between press and
+/// release for a drag to count as a plain click.
+const CLICK_SLOP: f64 = 2.0;
+
+/// Whether a drag that moved the pointer by `offset` was really a click.
+/// The overlay cancels on one instead of capturing a sliver.
+pub fn is_click((dx, dy): (f64, f64)) -> bool {
+    dx.abs() <= CLICK_SLOP && dy.abs() <= CLICK_SLOP
+}
+
+/// Selecting a region by clicking two opposite corners
+/// (`capture.selection_mode = "click-click"`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ClickSelection {
+    corner: Option<(f64, f64)>,
+}
+
+impl ClickSelection {
+    /// Handle a click at `point`: the first click sets a corner, the second
+    /// returns both corners and starts over.
+    pub fn click(&mut self, point: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+        match self.corner.take() {
+            Some(corner) => Some((corner, point)),
+            None => {
+                self.corner = Some(point);
+                None
+            }
+        }
+    }
+
+    /// The corner clicked first, while waiting for the second click.
+    pub fn corner(&self) -> Option<(f64, f64)> {
+        self.corner
+    }
+
+    /// Forget the first corner. Returns whether there was one.
+    pub fn cancel(&mut self) -> bool {
+        self.corner.take().is_some()
+    }
+}
+
+/// An RGBA image the overlay selects from instead of the live screen.
+#[derive(Clone)]
+pub struct BaseImage {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl BaseImage {
+    /// Copy out the pixels inside `rect` (image coordinates), clamped to the
+    /// image bounds. Returns `None` if nothing of the image is selected.
+    pub fn crop(&self, rect: Rectangle) -> Option<(Vec<u8>, u32, u32)> {
+        let x0 = rect.x.clamp(0, self.width as i32) as u32;
+        let y0 = rect.y.clamp(0, self.height as i32) as u32;
+        let x1 = (rect.x as i64 + rect.width as i64).clamp(0, self.width as i64) as u32;
+        let y1 = (rect.y as i64 + rect.height as i64).clamp(0, self.height as i64) as u32;
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        let (w, h) = (x1 - x0, y1 - y0);
+        let stride = self.width as usize * 4;
+        let mut out = Vec::with_capacity(w as usize * h as usize * 4);
+        for y in y0..y1 {
+            let start = y as usize * stride + x0 as usize * 4;
+            out.extend_from_slice(&self.rgba[start..start + w as usize * 4]);
+        }
+        Some((out, w, h))
+    }
+}
+
+/// The image currently on the clipboard, if there is one.
+pub fn clipboard_base_image(clipboard: &Arc<Mutex<Clipboard>>) -> Option<BaseImage> {
+    let mut cb = clipboard.lock().ok()?;
+    let img = cb.get_image().ok()?;
+    if !clipboard::is_valid_rgba_image(img.width, img.height, img.bytes.len()) {
+        return None;
+    }
+    Some(BaseImage {
+        rgba: img.bytes.into_owned(),
+        width: img.width as u32,
+        height: img.height as u32,
+    })
+}
+
+/// A capture that failed, kept so it can be retried with the same region.
+#[derive(Debug, Clone)]
+pub struct FailedCapture {
+    pub rect: Rectangle,
+    pub output: CaptureOutput,
+    pub error: String,
+}
+
+/// The most recent failed capture; cleared by the next successful one.
+static LAST_FAILURE: Mutex<Option<FailedCapture>> = Mutex::new(None);
+
+/// The last failed capture, if the latest capture attempt failed.
+pub fn last_failure() -> Option<FailedCapture> {
+    LAST_FAILURE.lock().ok()?.clone()
+}
+
+/// Capture a screen rectangle (X11 root coordinates), copy it to the clipboard,
+/// store it in history and notify the user.
+///
+/// Returns at once: the pixels are read on a worker thread, since the
+/// Wayland portal may sit on a permission prompt for up to a minute, and the
+/// capture is stored back on the main loop. `session` is held until then.
+///
+/// On failure the rectangle and error are kept (see [`last_failure`]) and the
+/// error notification offers to retry the exact same region. While the
+/// screen looks locked nothing is captured (`capture.refuse_when_locked`).
+pub fn capture_to_history(
+    rect: Rectangle,
+    output: &CaptureOutput,
+    db: &Arc<Mutex<Database>>,
+    clipboard: &Arc<Mutex<Clipboard>>,
+    config: &Arc<Config>,
+    session: Rc<CaptureSession>,
+) {
+    if config.capture.refuse_when_locked && screenshot::is_secure_context() {
+        notifications::notify_screenshot_error(
+            config,
+            "The screen looks locked or a password prompt is open, so the capture would be black. Not capturing.",
+        );
+        return;
+    }
+    let (output, db, clipboard, config) = (output.clone(), db.clone(), clipboard.clone(), config.clone());
+    glib::MainContext::default().spawn_local(async move {
+        let grabbed = gio::spawn_blocking(move || screenshot::capture_region(rect.x, rect.y, rect.width, rect.height))
+            .await
+            .map_err(|_| anyhow::anyhow!("The capture thread panicked"))
+            .and_then(|grabbed| grabbed.map_err(anyhow::Error::from));
+        let result = grabbed.and_then(|(raw_bgra, width, height)| {
+            let rgba = screenshot::bgra_to_rgba(&raw_bgra);
+            store_capture(&rgba, width, height, &output, &db, &clipboard, &config)
+        });
+        record_result(rect, &output, result, &db, &clipboard, &config);
+        drop(session);
+    });
+}
+
+/// Remember how a capture went, and on failure tell the user, offering a
+/// retry of the same region.
+fn record_result(
+    rect: Rectangle,
+    output: &CaptureOutput,
+    result: Result<()>,
+    db: &Arc<Mutex<Database>>,
+    clipboard: &Arc<Mutex<Clipboard>>,
+    config: &Arc<Config>,
+) {
+    let mut last_failure = LAST_FAILURE.lock().unwrap_or_else(|e| e.into_inner());
+    match result {
+        Ok(()) => *last_failure = None,
+        Err(e) => {
+            log::error!("Region capture failed: {:#}", e);
+            let error = format!("{:#}", e);
+            *last_failure = Some(FailedCapture {
+                rect,
+                output: output.clone(),
+                error: error.clone(),
+            });
+            drop(last_failure);
+
+            let (db, clipboard, retry_config) = (db.clone(), clipboard.clone(), config.clone());
+            notifications::notify_capture_failed(config, &format!("Capture failed: {}", error), move || {
+                retry_last_failure(&db, &clipboard, &retry_config)
+            });
+        }
+    }
+}
+
+/// Re-run the last failed capture with the same region and output settings.
+pub fn retry_last_failure(db: &Arc<Mutex<Database>>, clipboard: &Arc<Mutex<Clipboard>>, config: &Arc<Config>) {
+    match last_failure() {
+        Some(failed) => {
+            let Some(session) = begin_session(config) else {
+                return;
+            };
+            log::info!("Retrying capture of {:?}", failed.rect);
+            capture_to_history(failed.rect, &failed.output, db, clipboard, config, Rc::new(session));
+        }
+        None => log::info!("No failed capture to retry"),
+    }
+}
+
+/// Capture the window under the pointer, or the whole screen when the pointer
+/// is over the bare desktop. `session` is held as in [`capture_to_history`].
+pub fn capture_window_under_pointer(
+    output: &CaptureOutput,
+    db: &Arc<Mutex<Database>>,
+    clipboard: &Arc<Mutex<Clipboard>>,
+    config: &Arc<Config>,
+    session: Rc<CaptureSession>,
+) {
+    let mut output = output.clone();
+    let rect = match screenshot::window_under_pointer() {
+        Ok(Some(rect)) => rect,
+        Ok(None) => {
+            log::info!("No window under the pointer; capturing the full screen");
+            output.method = CaptureMethod::Fullscreen;
+            match screenshot::get_screen_info() {
+                Ok((x, y, width, height)) => Rectangle { x, y, width, height },
+                Err(e) => {
+                    notifications::notify_screenshot_error(config, &format!("Capture failed: {}", e));
+                    return;
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Window lookup failed: {:#}", e);
+            notifications::notify_screenshot_error(config, &format!("Could not find the window: {}", e));
+            return;
+        }
+    };
+    capture_to_history(rect, &output, db, clipboard, config, session);
+}
+
+/// Capture the focused window, frame included. Without one (e.g. the desktop
+/// has focus) the user is told so and nothing is captured. `session` is held
+/// as in [`capture_to_history`].
+pub fn capture_active_window(
+    output: &CaptureOutput,
+    db: &Arc<Mutex<Database>>,
+    clipboard: &Arc<Mutex<Clipboard>>,
+    config: &Arc<Config>,
+    session: Rc<CaptureSession>,
+) {
+    match screenshot::active_window() {
+        Ok(Some(rect)) => capture_to_history(rect, output, db, clipboard, config, session),
+        Ok(None) => notifications::notify_screenshot_error(config, "No window is focused; click a window first"),
+        Err(e) => {
+            log::error!("Active window lookup failed: {:#}", e);
+            notifications::notify_screenshot_error(config, &format!("Could not find the focused window: {}", e));
+        }
+    }
+}
+
+/// Copy RGBA pixels to the clipboard, store them in history and notify the
+/// user, exactly as for a fresh screen capture. Fails only if the image
+/// cannot be encoded; the caller reports that.
+pub fn store_capture(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+    output: &CaptureOutput,
+    db: &Arc<Mutex<Database>>,
+    clipboard: &Arc<Mutex<Clipboard>>,
+    config: &Arc<Config>,
+) -> Result<()> {
+    // Redacted pixels never reach the clipboard, history or a file
+    let redacted = redact(rgba, width, height, &output.redactions, config.redact_style())?;
+    let rgba: &[u8] = &redacted;
+
+    // The clipboard gets the full image; history may keep a smaller one
+    let (stored_rgba, stored_w, stored_h) = match config.history.max_image_dimension {
+        Some(max) => screenshot::downscale_to_fit(rgba, width, height, max).context("Downscaling failed")?,
+        None => (Cow::Borrowed(rgba), width, height),
+    };
+    let to_file = config.privacy.temp_file && !config.capture.post_capture_command.trim().is_empty();
+    let format = capture_format(&output.format);
+    let encoded = encode_capture(&stored_rgba, stored_w, stored_h, format, output.quality, to_file)?;
+    let thumb = screenshot::create_thumbnail_from_rgba(&stored_rgba, stored_w, stored_h, screenshot::thumbnail_px())
+        .unwrap_or_default();
+    drop(stored_rgba);
+
+    // Copy to shared clipboard
+    if let Ok(mut cb) = clipboard.lock() {
+        copy_capture(&mut cb, rgba, width, height, config);
+    }
+
+    // Store in database
+    if let Ok(db) = db.lock() {
+        let mime = screenshot::image_mime_type(format);
+        let stored = match &encoded {
+            EncodedImage::File(path) => db.insert_image_file(path, &thumb, stored_w, stored_h, output.method, mime),
+            EncodedImage::Memory(bytes) => {
+                db.insert_captured_image(bytes, &thumb, stored_w, stored_h, output.method, mime)
+            }
+        };
+        match stored {
+            Ok(id) => {
+                if output.pin {
+                    if let Err(e) = db.set_pinned(id, true) {
+                        log::error!("Failed to pin screenshot {}: {}", id, e);
+                    }
+                }
+                events::publish(&events::EntryEvent::image(id, stored_w, stored_h))
+            }
+            Err(e) => log::error!("Failed to save screenshot: {}", e),
+        }
+    }
+
+    let icon_file = notifications::notify_screenshot_success(config, &encoded, output.pin);
+    let capture_file = match encoded {
+        EncodedImage::File(path) => {
+            post_capture::spawn(&config.capture, &path, clipboard.clone());
+            Some(path)
+        }
+        EncodedImage::Memory(_) => {
+            if !config.capture.post_capture_command.trim().is_empty() {
+                log::warn!("post_capture_command needs privacy.temp_file = true; not running it");
+            }
+            icon_file
+        }
+    };
+    // The previous capture's file has served its notification
+    if let Some(path) = capture_file {
+        let mut last = LAST_CAPTURE_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(old) = last.replace(path) {
+            let _ = fs::remove_file(old);
+        }
+    }
+    Ok(())
+}
+
+/// Raw RGBA size above which some clipboard managers and compositors drop a
+/// copied image without an error (a little over 4096×4096).
+const SAFE_CLIPBOARD_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// The file most recently copied in place of a too-large image.
+static LAST_CLIPBOARD_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Copy a capture to the clipboard. An image too large to copy reliably, or
+/// one that fails to copy, is copied as a PNG file instead unless
+/// `capture.large_image_fallback` is "none".
+fn copy_capture(cb: &mut Clipboard, rgba: &[u8], width: u32, height: u32, config: &Config) {
+    let fallback = config.large_image_fallback();
+    let copied = if rgba.len() > SAFE_CLIPBOARD_IMAGE_BYTES && fallback == LargeImageFallback::Uri {
+        log::info!("Capture is {}×{}, too large to copy reliably; copying it as a file", width, height);
+        false
+    } else {
+        match clipboard::set_clipboard_image(cb, rgba, width as usize, height as usize) {
+            Ok(()) => true,
+            Err(e) => {
+                log::error!("Failed to copy to clipboard: {}", e);
+                false
+            }
+        }
+    };
+    if copied {
+        return;
+    }
+    if fallback == LargeImageFallback::None {
+        notifications::notify_screenshot_error(config, "Clipboard copy failed");
+        return;
+    }
+    match copy_as_file(cb, rgba, width, height) {
+        Ok(()) => notifications::notify_copied_as_file(config),
+        Err(e) => {
+            log::error!("Failed to copy the capture as a file: {:#}", e);
+            notifications::notify_screenshot_error(config, "Clipboard copy failed");
+        }
+    }
+}
+
+/// Save the image to a private temp file and put that on the clipboard.
+fn copy_as_file(cb: &mut Clipboard, rgba: &[u8], width: u32, height: u32) -> Result<()> {
+    let path = temp_files::create("clipboard", "png")?;
+    screenshot::encode_png_to_file(rgba, width, height, &path).context("PNG encoding failed")?;
+    clipboard::set_clipboard_file(cb, &path)?;
+    // Only the newest copy can still be pasted
+    let mut last = LAST_CLIPBOARD_FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(old) = last.replace(path) {
+        let _ = fs::remove_file(old);
+    }
+    Ok(())
+}
+
+/// `rgba` with every area in `areas` hidden, or borrowed if there are none.
+fn redact<'a>(rgba: &'a [u8], width: u32, height: u32, areas: &[Rectangle], style: RedactStyle) -> Result<Cow<'a, [u8]>> {
+    if areas.is_empty() {
+        return Ok(Cow::Borrowed(rgba));
+    }
+    let mut pixels = rgba.to_vec();
+    for &area in areas {
+        screenshot::redact(&mut pixels, width, height, area, style).context("Redaction failed")?;
+    }
+    Ok(Cow::Owned(pixels))
+}
+
+/// An encoded capture, on disk or in memory.
+pub enum EncodedImage {
+    File(PathBuf),
+    Memory(Vec<u8>),
+}
+
+/// The private temp file of the most recent capture.
+static LAST_CAPTURE_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// The `capture.format` a capture is encoded in, falling back to PNG for a
+/// format the encoder doesn't know.
+fn capture_format(format: &str) -> &str {
+    match format {
+        "png" | "jpeg" | "jpg" | "webp" => format,
+        other => {
+            log::warn!("Unknown capture format {:?}, saving PNG", other);
+            "png"
+        }
+    }
+}
+
+/// Encode a capture in `format` (see `screenshot::encode_image`). With
+/// `to_file`, write it to a private temp file rather than holding it in
+/// memory, as the post-capture command needs a path anyway; a PNG is
+/// streamed there directly.
+fn encode_capture(rgba: &[u8], width: u32, height: u32, format: &str, quality: u8, to_file: bool) -> Result<EncodedImage> {
+    if to_file {
+        match temp_files::create("capture", screenshot::image_extension(format)) {
+            Ok(path) => {
+                if format == "png" {
+                    screenshot::encode_png_to_file(rgba, width, height, &path).context("PNG encoding failed")?;
+                } else {
+                    let bytes = screenshot::encode_image(rgba, width, height, format, quality)
+                        .context("Image encoding failed")?;
+                    fs::write(&path, bytes).with_context(|| format!("Failed to write {:?}", path))?;
+                }
+                return Ok(EncodedImage::File(path));
+            }
+            Err(e) => log::warn!("Encoding capture in memory: {:#}", e),
+        }
+    }
+    let bytes = screenshot::encode_image(rgba, width, height, format, quality).context("Image encoding failed")?;
+    Ok(EncodedImage::Memory(bytes))
+}
+
+/// The last overlay selection, together with the screen size it was made on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct SavedRegion {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    screen_width: u32,
+    screen_height: u32,
+}
+
+impl SavedRegion {
+    /// The saved rectangle, unless the screen geometry has changed since.
+    fn valid_for(&self, screen_width: u32, screen_height: u32) -> Option<Rectangle> {
+        if self.screen_width != screen_width || self.screen_height != screen_height {
+            return None;
+        }
+        Some(Rectangle {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        })
+    }
+}
+
+/// Path of the file holding the last selected region.
+fn last_region_path() -> PathBuf {
+    Config::config_dir().join("last_region.toml")
+}
+
+/// Remember `rect` as the last selected region.
+pub fn save_last_region(rect: Rectangle) {
+    let result = screenshot::get_screen_info()
+        .map_err(anyhow::Error::from)
+        .and_then(|(_, _, screen_width, screen_height)| {
+            write_region(
+                &last_region_path(),
+                SavedRegion {
+                    x: rect.x,
+                    y: rect.y,
+                    width: rect.width,
+                    height: rect.height,
+                    screen_width,
+                    screen_height,
+                },
+            )
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to save last region: {:#}", e);
+    }
+}
+
+/// Load the last selected region if it is still valid for the current screen.
+pub fn load_last_region() -> Option<Rectangle> {
+    let saved = read_region(&last_region_path()).ok()?;
+    let (_, _, screen_width, screen_height) = screenshot::get_screen_info().ok()?;
+    let rect = saved.valid_for(screen_width, screen_height);
+    if rect.is_none() {
+        log::info!("Screen geometry changed since the last region was saved; ignoring it");
+    }
+    rect
+}
+
+fn write_region(path: &Path, region: SavedRegion) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create dir: {:?}", parent))?;
+    }
+    let content = toml::to_string(&region).context("Failed to serialize region")?;
+    fs::write(path, content).with_context(|| format!("Failed to write region file: {:?}", path))
+}
+
+fn read_region(path: &Path) -> Result<SavedRegion> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read region file: {:?}", path))?;
+    toml::from_str(&content).context("Failed to parse region file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_session_lifecycle() {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let first = CaptureSession::begin_on(&COUNTER, 1).expect("first session starts");
+        assert!(CaptureSession::begin_on(&COUNTER, 1).is_none());
+        drop(first);
+        assert_eq!(COUNTER.load(Ordering::Acquire), 0);
+
+        // A higher limit allows that many at once; 0 still allows one
+        let a = CaptureSession::begin_on(&COUNTER, 2).unwrap();
+        let b = CaptureSession::begin_on(&COUNTER, 2).unwrap();
+        assert!(CaptureSession::begin_on(&COUNTER, 2).is_none());
+        drop((a, b));
+        let only = CaptureSession::begin_on(&COUNTER, 0).unwrap();
+        assert!(CaptureSession::begin_on(&COUNTER, 0).is_none());
+        drop(only);
+        assert_eq!(COUNTER.load(Ordering::Acquire), 0);
+    }
+
+    fn region() -> SavedRegion {
+        SavedRegion {
+            x: 10,
+            y: 20,
+            width: 300,
+            height: 200,
+            screen_width: 1920,
+            screen_height: 1080,
+        }
+    }
+
+    fn capture_config(min_selection_px: u32, small_selection: &str) -> CaptureConfig {
+        let mut config = Config::default().capture;
+        config.min_selection_px = min_selection_px;
+        config.small_selection = small_selection.to_string();
+        config
+    }
+
+    #[test]
+    fn test_check_selection() {
+        let notify = capture_config(10, "notify");
+        assert_eq!(check_selection(10, 10, &notify), SelectionCheck::Capture);
+        assert_eq!(check_selection(9, 200, &notify), SelectionCheck::TooSmall);
+        assert_eq!(check_selection(9, 200, &capture_config(10, "capture")), SelectionCheck::Capture);
+        assert_eq!(check_selection(9, 200, &capture_config(10, "ignore")), SelectionCheck::Discard);
+    }
+
+    #[test]
+    fn test_check_selection_allows_single_pixel() {
+        let config = capture_config(0, "notify");
+        assert_eq!(check_selection(1, 1, &config), SelectionCheck::Capture);
+        assert_eq!(check_selection(0, 5, &config), SelectionCheck::Discard);
+    }
+
+    #[test]
+    fn test_snap_to_window_edges() {
+        // A window on top of a larger one
+        let windows = [(100.0, 100.0, 200.0, 100.0), (0.0, 0.0, 500.0, 400.0)];
+        assert_eq!(snap_to_window_edges((104.0, 150.0), &windows, 8.0), (100.0, 150.0));
+        assert_eq!(snap_to_window_edges((295.0, 206.0), &windows, 8.0), (300.0, 200.0));
+        // Under only the lower window, its edges apply
+        assert_eq!(snap_to_window_edges((495.0, 50.0), &windows, 8.0), (500.0, 50.0));
+        // Too far from any edge, or outside every window
+        assert_eq!(snap_to_window_edges((150.0, 150.0), &windows, 8.0), (150.0, 150.0));
+        assert_eq!(snap_to_window_edges((600.0, 600.0), &windows, 8.0), (600.0, 600.0));
+    }
+
+    #[test]
+    fn test_redaction_areas() {
+        let areas = redaction_areas(&[(110.0, 60.0, 20.0, 10.5), (90.0, 40.0, 5.0, 5.0)], (100.0, 50.0), 2.0);
+        assert_eq!(areas[0], Rectangle { x: 20, y: 20, width: 40, height: 21 });
+        // Left of and above the image; clipped away when redacting
+        assert_eq!(areas[1], Rectangle { x: -20, y: -20, width: 10, height: 10 });
+    }
+
+    #[test]
+    fn test_parse_typed_region() {
+        let screen = Rectangle { x: 0, y: 0, width: 1920, height: 1080 };
+        assert_eq!(
+            parse_typed_region(" 10, 20,300 ,200 ", screen),
+            Ok(Rectangle { x: 10, y: 20, width: 300, height: 200 })
+        );
+        assert!(parse_typed_region("0,0,1920,1080", screen).is_ok());
+
+        assert!(parse_typed_region("10,20,300", screen).is_err());
+        assert!(parse_typed_region("10,20,300,200,5", screen).is_err());
+        assert!(parse_typed_region("10,abc,300,200", screen).is_err());
+        assert!(parse_typed_region("10,20,0,200", screen).is_err());
+        assert!(parse_typed_region("10,20,-5,200", screen).is_err());
+        // Hanging off any edge
+        assert!(parse_typed_region("1800,0,200,100", screen).is_err());
+        assert!(parse_typed_region("-1,0,100,100", screen).is_err());
+
+        // Monitors left of the primary have negative coordinates
+        let wide = Rectangle { x: -1280, y: 0, width: 3200, height: 1080 };
+        assert!(parse_typed_region("-1280,0,100,100", wide).is_ok());
+    }
+
+    #[test]
+    fn test_click_selection() {
+        let mut clicks = ClickSelection::default();
+        assert_eq!(clicks.corner(), None);
+        assert_eq!(clicks.click((300.0, 200.0)), None);
+        assert_eq!(clicks.corner(), Some((300.0, 200.0)));
+        // The second click completes the selection, in either direction
+        assert_eq!(clicks.click((10.0, 20.0)), Some(((300.0, 200.0), (10.0, 20.0))));
+        assert_eq!(clicks.corner(), None);
+
+        // The next click starts a new selection
+        assert_eq!(clicks.click((5.0, 5.0)), None);
+        assert!(clicks.cancel());
+        assert!(!clicks.cancel());
+        assert_eq!(clicks.click((50.0, 60.0)), None);
+        assert_eq!(clicks.click((70.0, 80.0)), Some(((50.0, 60.0), (70.0, 80.0))));
+    }
+
+    #[test]
+    fn test_is_click() {
+        assert!(is_click((0.0, 0.0)));
+        assert!(is_click((-1.5, 2.0)));
+        assert!(!is_click((2.5, 0.0)));
+        assert!(!is_click((0.0, -40.0)));
+    }
+
+    #[test]
+    fn test_base_image_crop() {
+        // 3×2 image whose red channel is the pixel index
+        let rgba: Vec<u8> = (0..6u8).flat_map(|i| [i, 0, 0, 255]).collect();
+        let base = BaseImage { rgba, width: 3, height: 2 };
+
+        let (pixels, w, h) = base.crop(Rectangle { x: 1, y: 0, width: 5, height: 5 }).unwrap();
+        assert_eq!((w, h), (2, 2));
+        let reds: Vec<u8> = pixels.chunks(4).map(|p| p[0]).collect();
+        assert_eq!(reds, vec![1, 2, 4, 5]);
+
+        assert!(base.crop(Rectangle { x: -10, y: 0, width: 5, height: 2 }).is_none());
+    }
+
+    #[test]
+    fn test_region_round_trip() {
+        let path = std::env::temp_dir().join(format!("clipsnap_region_{}.toml", std::process::id()));
+        write_region(&path, region()).unwrap();
+        let loaded = read_region(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, region());
+    }
+
+    #[test]
+    fn test_region_invalidated_by_geometry_change() {
+        let rect = region().valid_for(1920, 1080).unwrap();
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (10, 20, 300, 200));
+        assert!(region().valid_for(2560, 1440).is_none());
+    }
+}
@@ -1,14 +1,40 @@
 use crate::database::Database;
-use crate::screenshot;
+use crate::events::{self, EntryEvent};
+use crate::models::{ContentType, DualPreference};
+use crate::{screenshot, sensitive, source_app};
+use crate::selection::{self, ImageTarget};
 use anyhow::Result;
 use arboard::{Clipboard, ImageData};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{self, ConnectionExt as _};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
 
 /// Set an image (RGBA pixels) to the system clipboard.
+///
+/// On X11 the data is served by arboard's background thread for as long as a
+/// `Clipboard` instance is alive, so always pass the long-lived shared
+/// clipboard from `main`. A short-lived instance would give up the selection
+/// when dropped, and the copied image would vanish unless a clipboard manager
+/// happened to take it over.
+///
+/// When targets besides PNG are configured, the image is served by
+/// `selection::offer_image` instead, falling back to arboard without X11.
 pub fn set_clipboard_image(clipboard: &mut Clipboard, rgba: &[u8], width: usize, height: usize) -> Result<()> {
+    let targets = selection::image_targets();
+    if targets != [ImageTarget::Png] {
+        match selection::offer_image(rgba, width as u32, height as u32, &targets) {
+            Ok(()) => return Ok(()),
+            Err(e) => log::warn!("Offering the image on {:?} failed, copying PNG only: {:#}", targets, e),
+        }
+    }
     let img_data = ImageData {
         width,
         height,
@@ -20,6 +46,16 @@ pub fn set_clipboard_image(clipboard: &mut Clipboard, rgba: &[u8], width: usize,
     Ok(())
 }
 
+/// Put a file on the system clipboard as `text/uri-list`, for apps that
+/// paste files. `path` must be absolute.
+pub fn set_clipboard_file(clipboard: &mut Clipboard, path: &Path) -> Result<()> {
+    clipboard
+        .set()
+        .file_list(&[path])
+        .map_err(|e| anyhow::anyhow!("Failed to set clipboard file: {}", e))?;
+    Ok(())
+}
+
 /// Set text to the system clipboard.
 pub fn set_clipboard_text(clipboard: &mut Clipboard, text: &str) -> Result<()> {
     clipboard
@@ -28,20 +64,185 @@ pub fn set_clipboard_text(clipboard: &mut Clipboard, text: &str) -> Result<()> {
     Ok(())
 }
 
-/// Calculate a hash of data for deduplication.
-fn calculate_hash(data: &[u8]) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    data.hash(&mut hasher);
+/// Hash of content for deduplication, as 16 hex digits. Unlike the std
+/// hasher it is the same in every run and build, so hashes can be stored and
+/// compared after a restart, or on another machine.
+///
+/// It is not collision-resistant: equal hashes only mean the content may be
+/// equal, so the database compares the content itself before treating a new
+/// entry as a duplicate.
+pub fn content_hash(data: &[u8]) -> String {
+    let mut hasher = ContentHasher::default();
+    hasher.update(data);
     hasher.finish()
 }
 
+/// `content_hash` of content fed in pieces: 64-bit FNV-1a.
+pub struct ContentHasher(u64);
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        ContentHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl ContentHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        self.0 = data
+            .iter()
+            .fold(self.0, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3));
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+/// Hash RGBA pixels for deduplication. With `flatten_alpha` the pixels are
+/// first composited over white, so copies that look the same on a white
+/// background hash alike even when their alpha differs, e.g. the same
+/// screenshot with and without a transparent backdrop. Any fully transparent
+/// area flattens to white, which is why this is optional
+/// (`capture.dedup_flatten_alpha`).
+pub fn image_hash(rgba: &[u8], flatten_alpha: bool) -> String {
+    if !flatten_alpha {
+        return content_hash(rgba);
+    }
+    content_hash(&screenshot::flatten_over_white(rgba))
+}
+
+/// Settings the clipboard monitor applies to every poll.
+#[derive(Debug, Clone)]
+pub struct MonitorOptions {
+    /// What to keep when text and an image are copied together.
+    pub prefer_on_dual: DualPreference,
+    /// Hash images as composited over white; see [`image_hash`].
+    pub flatten_alpha: bool,
+    /// Delete entries flagged sensitive this many seconds after they were
+    /// copied; 0 keeps them (`privacy.sensitive_ttl_seconds`).
+    pub sensitive_ttl_secs: u64,
+    /// Downscale stored images to fit this size (`history.max_image_dimension`).
+    pub max_image_dimension: Option<u32>,
+    /// Skip changes copied from these apps (`privacy.blocked_source_apps`).
+    pub blocked_source_apps: Vec<String>,
+    /// Skip text that is or looks like a password (`privacy.exclude_passwords`).
+    pub exclude_passwords: bool,
+}
+
+/// The last few content hashes of one type, newest last. Content whose hash
+/// is still in the window is not stored again (`capture.dedup_window`).
+#[derive(Debug, Clone)]
+pub struct RecentHashes {
+    hashes: VecDeque<String>,
+    capacity: usize,
+}
+
+impl RecentHashes {
+    /// An empty window remembering up to `capacity` hashes (at least one).
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        RecentHashes {
+            hashes: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.hashes.iter().any(|h| h == hash)
+    }
+
+    /// The most recently remembered hash.
+    pub fn latest(&self) -> Option<&str> {
+        self.hashes.back().map(String::as_str)
+    }
+
+    /// Record `hash` as the newest, evicting the oldest beyond capacity. A hash
+    /// already in the window moves to the front instead of being duplicated.
+    pub fn remember(&mut self, hash: String) {
+        self.hashes.retain(|h| *h != hash);
+        self.hashes.push_back(hash);
+        while self.hashes.len() > self.capacity {
+            self.hashes.pop_front();
+        }
+    }
+}
+
+/// The monitor's recent hashes, for code outside it that puts history content
+/// back on the clipboard and doesn't want it stored again.
+#[derive(Clone)]
+pub struct SharedHashes {
+    pub text: Arc<Mutex<RecentHashes>>,
+    pub image: Arc<Mutex<RecentHashes>>,
+    /// The monitor's `capture.dedup_flatten_alpha`, so image hashes match.
+    pub flatten_alpha: bool,
+}
+
+impl SharedHashes {
+    /// Mark text as already seen. Call before putting it on the clipboard.
+    pub fn remember_text(&self, text: &str) {
+        if let Ok(mut recent) = self.text.lock() {
+            recent.remember(content_hash(text.as_bytes()));
+        }
+    }
+
+    /// Mark RGBA pixels as already seen. Call before putting them on the clipboard.
+    pub fn remember_image(&self, rgba: &[u8]) {
+        if let Ok(mut recent) = self.image.lock() {
+            recent.remember(image_hash(rgba, self.flatten_alpha));
+        }
+    }
+}
+
+/// Check that a clipboard image has non-zero dimensions and exactly
+/// `width * height * 4` bytes of RGBA data.
+pub fn is_valid_rgba_image(width: usize, height: usize, len: usize) -> bool {
+    width > 0 && height > 0 && width.checked_mul(height).and_then(|px| px.checked_mul(4)) == Some(len)
+}
+
+/// Watch the X11 root window for `_NET_ACTIVE_WINDOW` changes and send a
+/// nudge on `wake` for each focus change. Blocks; run it on its own thread.
+pub fn watch_active_window(wake: Sender<()>) -> Result<()> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let active_atom = conn.intern_atom(false, b"_NET_ACTIVE_WINDOW")?.reply()?.atom;
+
+    conn.change_window_attributes(
+        root,
+        &xproto::ChangeWindowAttributesAux::new().event_mask(xproto::EventMask::PROPERTY_CHANGE),
+    )?
+    .check()?;
+    log::info!("Watching for focus changes");
+
+    loop {
+        if let Event::PropertyNotify(ev) = conn.wait_for_event()? {
+            if ev.atom == active_atom && wake.send(()).is_err() {
+                // The monitor is gone; nothing left to nudge.
+                return Ok(());
+            }
+        }
+    }
+}
+
 /// Run clipboard monitoring in a background thread.
 /// Polls the clipboard every 750ms (adaptive) and stores new content to the database.
+/// A message on `wake` (sent on window focus changes) triggers an immediate poll.
+///
+/// The clipboard lock is only held while reading; see `main.rs` for the lock order.
+///
+/// The monitor only ever reads. It must never set the clipboard: taking
+/// ownership of the selection would break pasting in the app that copied it.
+///
+/// Returns once `shutdown` is set; send on the `wake` channel as well to stop
+/// without waiting out the current poll interval. A poll in progress always
+/// finishes its database write first.
 pub fn monitor_clipboard(
     clipboard: Arc<Mutex<Clipboard>>,
     db: Arc<Mutex<Database>>,
-    last_text_hash: Arc<Mutex<Option<u64>>>,
-    last_image_hash: Arc<Mutex<Option<u64>>>,
+    last_text_hash: Arc<Mutex<RecentHashes>>,
+    last_image_hash: Arc<Mutex<RecentHashes>>,
+    wake: Receiver<()>,
+    options: MonitorOptions,
+    shutdown: Arc<AtomicBool>,
 ) {
     log::info!("Clipboard monitoring started");
 
@@ -54,60 +255,40 @@ pub fn monitor_clipboard(
         } else {
             750  // 750ms normally
         };
-        std::thread::sleep(Duration::from_millis(poll_interval));
-
-        let mut cb = match clipboard.lock() {
-            Ok(c) => c,
-            Err(e) => {
-                log::error!("Failed to lock clipboard: {}", e);
-                continue;
+        match wake.recv_timeout(Duration::from_millis(poll_interval)) {
+            Ok(()) => {
+                // Focus just changed – likely right after a copy. Poll now and
+                // fall back to the faster interval; coalesce queued nudges.
+                while wake.try_recv().is_ok() {}
+                no_change_count = 0;
             }
-        };
+            Err(RecvTimeoutError::Timeout) => {}
+            // No focus watcher (e.g. not on X11): plain timed polling.
+            Err(RecvTimeoutError::Disconnected) => std::thread::sleep(Duration::from_millis(poll_interval)),
+        }
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
 
-        let mut changed = false;
-
-        // --- Check text ---
-        if let Ok(text) = cb.get_text() {
-            if !text.is_empty() {
-                let hash = calculate_hash(text.as_bytes());
-                let is_new = {
-                    let last = last_text_hash.lock().unwrap();
-                    *last != Some(hash)
-                };
-                if is_new {
-                    changed = true;
-                    if let Ok(db) = db.lock() {
-                        if db.insert_text(&text).is_ok() {
-                            log::debug!("Stored text clipboard entry ({} bytes)", text.len());
-                        }
-                    }
-                    *last_text_hash.lock().unwrap() = Some(hash);
+        // Read both formats into locals and release the clipboard right away,
+        // so the UI is never stuck behind hashing, encoding or DB work.
+        let (text, image) = {
+            let mut cb = match clipboard.lock() {
+                Ok(c) => c,
+                Err(e) => {
+                    log::error!("Failed to lock clipboard: {}", e);
+                    continue;
                 }
-            }
-        }
+            };
+            (cb.get_text().ok(), cb.get_image().ok())
+        };
 
-        // --- Check image ---
-        if let Ok(img) = cb.get_image() {
-            if !img.bytes.is_empty() {
-                let hash = calculate_hash(&img.bytes);
-                let is_new = {
-                    let last = last_image_hash.lock().unwrap();
-                    *last != Some(hash)
-                };
-                if is_new {
-                    changed = true;
-                    // Convert RGBA to PNG and generate thumbnail
-                    let width = img.width as u32;
-                    let height = img.height as u32;
-                    if let Ok(png) = screenshot::encode_png(&img.bytes, width, height) {
-                        let thumb = screenshot::create_thumbnail(&png, 150).unwrap_or_default();
-                        if let Ok(db) = db.lock() {
-                            if db.insert_image(&png, &thumb).is_ok() {
-                                log::debug!("Stored image clipboard entry ({}×{})", width, height);
-                            }
-                        }
-                    }
-                    *last_image_hash.lock().unwrap() = Some(hash);
+        let changed = store_new_content(text, image, &db, &last_text_hash, &last_image_hash, &options);
+
+        if options.sensitive_ttl_secs > 0 {
+            if let Ok(db) = db.lock() {
+                if let Err(e) = db.purge_expired_sensitive(options.sensitive_ttl_secs, chrono::Utc::now().timestamp()) {
+                    log::warn!("Failed to purge sensitive entries: {}", e);
                 }
             }
         }
@@ -119,4 +300,275 @@ pub fn monitor_clipboard(
             no_change_count = no_change_count.saturating_add(1);
         }
     }
+
+    log::info!("Clipboard monitoring stopped");
+}
+
+/// Store whatever clipboard content is new since the last poll.
+/// Returns whether anything new was seen.
+fn store_new_content(
+    text: Option<String>,
+    image: Option<ImageData<'static>>,
+    db: &Mutex<Database>,
+    last_text_hash: &Mutex<RecentHashes>,
+    last_image_hash: &Mutex<RecentHashes>,
+    options: &MonitorOptions,
+) -> bool {
+    let new_text = text.filter(|t| !t.is_empty()).and_then(|text| {
+        let hash = content_hash(text.as_bytes());
+        (!last_text_hash.lock().unwrap().contains(&hash)).then_some((text, hash))
+    });
+
+    let new_image = image.and_then(|img| {
+        if !is_valid_rgba_image(img.width, img.height, img.bytes.len()) {
+            if !img.bytes.is_empty() {
+                log::debug!(
+                    "Skipping clipboard image with invalid geometry ({}×{}, {} bytes)",
+                    img.width,
+                    img.height,
+                    img.bytes.len()
+                );
+            }
+            return None;
+        }
+        let hash = image_hash(&img.bytes, options.flatten_alpha);
+        (!last_image_hash.lock().unwrap().contains(&hash)).then_some((img, hash))
+    });
+
+    let changed = new_text.is_some() || new_image.is_some();
+    // Content from a blocked app is remembered like any other, just not stored
+    let blocked = changed && source_app::owner_is_blocked(&options.blocked_source_apps);
+    let (store_text, store_image) = match (&new_text, &new_image, options.prefer_on_dual) {
+        _ if blocked => (false, false),
+        (Some(_), Some(_), DualPreference::Image) => (false, true),
+        (Some(_), Some(_), DualPreference::Text) => (true, false),
+        _ => (true, true),
+    };
+
+    // --- Text ---
+    if let Some((text, hash)) = new_text {
+        let secret = store_text && options.exclude_passwords && sensitive::is_secret_copy(&text);
+        if secret {
+            log::info!("Not storing copied password ({} characters)", text.chars().count());
+        }
+        if store_text && !secret {
+            if let Ok(db) = db.lock() {
+                if let Ok(id) = db.insert_text(&text) {
+                    log::debug!("Stored text clipboard entry ({} bytes)", text.len());
+                    events::publish(&EntryEvent::text(id, &text));
+                }
+            }
+        }
+        // Remember skipped content too, so it is not stored on the next poll.
+        // Hashes of secrets and blocked apps' content stay in memory: a short
+        // password's hash could be brute-forced from the database file.
+        if !secret && !blocked {
+            save_latest_hash(db, ContentType::Text, &hash);
+        }
+        last_text_hash.lock().unwrap().remember(hash);
+    }
+
+    // --- Image ---
+    if let Some((img, hash)) = new_image {
+        if store_image {
+            // Convert RGBA to PNG and generate thumbnail
+            let (rgba, width, height) = match options.max_image_dimension {
+                Some(max) => match screenshot::downscale_to_fit(&img.bytes, img.width as u32, img.height as u32, max) {
+                    Ok(scaled) => scaled,
+                    Err(e) => {
+                        log::warn!("Failed to downscale clipboard image: {}", e);
+                        return changed;
+                    }
+                },
+                None => (Cow::Borrowed(&img.bytes[..]), img.width as u32, img.height as u32),
+            };
+            if let Ok(png) = screenshot::encode_png(&rgba, width, height) {
+                let thumb = screenshot::create_thumbnail(&png, screenshot::thumbnail_px()).unwrap_or_default();
+                if let Ok(db) = db.lock() {
+                    if let Ok(id) = db.insert_image(&png, &thumb, width, height) {
+                        log::debug!("Stored image clipboard entry ({}×{})", width, height);
+                        events::publish(&EntryEvent::image(id, width, height));
+                    }
+                }
+            }
+        }
+        if !blocked {
+            save_latest_hash(db, ContentType::Image, &hash);
+        }
+        last_image_hash.lock().unwrap().remember(hash);
+    }
+
+    changed
+}
+
+/// Persist the hash of what is now on the clipboard, for `seed_hashes`
+/// after a restart.
+fn save_latest_hash(db: &Mutex<Database>, content_type: ContentType, hash: &str) {
+    if let Ok(db) = db.lock() {
+        if let Err(e) = db.set_latest_hash(content_type.clone(), hash) {
+            log::warn!("Failed to save {} hash: {}", content_type.to_str(), e);
+        }
+    }
+}
+
+/// Start `recent` off with the hash saved by the previous run, so content
+/// still on the clipboard isn't stored again on the first poll.
+pub fn seed_hashes(db: &Mutex<Database>, content_type: ContentType, recent: &Mutex<RecentHashes>) {
+    let saved = match db.lock() {
+        Ok(db) => db.latest_hash(content_type.clone()),
+        Err(_) => return,
+    };
+    match saved {
+        Ok(Some(hash)) => recent.lock().unwrap().remember(hash),
+        Ok(None) => {}
+        Err(e) => log::warn!("Failed to load saved {} hash: {}", content_type.to_str(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ContentType;
+
+    #[test]
+    fn test_valid_rgba_image() {
+        assert!(is_valid_rgba_image(2, 3, 24));
+    }
+
+    #[test]
+    fn test_zero_dimension_image_rejected() {
+        assert!(!is_valid_rgba_image(0, 10, 0));
+        assert!(!is_valid_rgba_image(10, 0, 0));
+        assert!(!is_valid_rgba_image(0, 0, 16));
+    }
+
+    #[test]
+    fn test_mismatched_length_rejected() {
+        assert!(!is_valid_rgba_image(2, 2, 15));
+        assert!(!is_valid_rgba_image(2, 2, 17));
+        assert!(!is_valid_rgba_image(usize::MAX, 2, 8));
+    }
+
+    fn dual_poll(prefer: DualPreference) -> (usize, usize) {
+        let db = Mutex::new(Database::new_in_memory().unwrap());
+        let (last_text, last_image) = (Mutex::new(RecentHashes::new(1)), Mutex::new(RecentHashes::new(1)));
+        let image = || ImageData {
+            width: 2,
+            height: 2,
+            bytes: vec![255u8; 16].into(),
+        };
+
+        let options = MonitorOptions {
+            prefer_on_dual: prefer,
+            flatten_alpha: false,
+            sensitive_ttl_secs: 0,
+            max_image_dimension: None,
+            blocked_source_apps: Vec::new(),
+            exclude_passwords: false,
+        };
+        let changed = store_new_content(Some("caption".into()), Some(image()), &db, &last_text, &last_image, &options);
+        assert!(changed);
+        // The same dual content on the next poll is not new any more
+        assert!(!store_new_content(Some("caption".into()), Some(image()), &db, &last_text, &last_image, &options));
+
+        let db = db.lock().unwrap();
+        (
+            db.count(Some(ContentType::Text)).unwrap(),
+            db.count(Some(ContentType::Image)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_recent_hashes_window_eviction() {
+        let mut recent = RecentHashes::new(2);
+        recent.remember("1".into());
+        recent.remember("2".into());
+        assert!(recent.contains("1") && recent.contains("2"));
+
+        // Seeing 1 again refreshes it, so 2 is the one evicted next
+        recent.remember("1".into());
+        recent.remember("3".into());
+        assert!(!recent.contains("2"));
+        assert!(recent.contains("1") && recent.contains("3"));
+        assert_eq!(recent.latest(), Some("3"));
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        // Stored hashes are compared after restarts, so these must never change
+        assert_eq!(content_hash(b""), "cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+        assert_eq!(content_hash(b"hello world"), content_hash(b"hello world"));
+
+        let mut hasher = ContentHasher::default();
+        hasher.update(b"hello ");
+        hasher.update(b"world");
+        assert_eq!(hasher.finish(), content_hash(b"hello world"));
+    }
+
+    #[test]
+    fn test_dedup_window_skips_alternating_text() {
+        let db = Mutex::new(Database::new_in_memory().unwrap());
+        let (last_text, last_image) = (Mutex::new(RecentHashes::new(2)), Mutex::new(RecentHashes::new(2)));
+        let options = MonitorOptions {
+            prefer_on_dual: DualPreference::Both,
+            flatten_alpha: false,
+            sensitive_ttl_secs: 0,
+            max_image_dimension: None,
+            blocked_source_apps: Vec::new(),
+            exclude_passwords: false,
+        };
+        for text in ["A", "B", "A", "B", "C", "A"] {
+            store_new_content(Some(text.into()), None, &db, &last_text, &last_image, &options);
+        }
+        // The second A and B were still in the window. A was evicted by C, so
+        // it was seen again, but the database moves the existing row up
+        let db = db.lock().unwrap();
+        assert_eq!(db.count(Some(ContentType::Text)).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_exclude_passwords_skips_secret_text() {
+        let db = Mutex::new(Database::new_in_memory().unwrap());
+        let (last_text, last_image) = (Mutex::new(RecentHashes::new(1)), Mutex::new(RecentHashes::new(1)));
+        let mut options = MonitorOptions {
+            prefer_on_dual: DualPreference::Both,
+            flatten_alpha: false,
+            sensitive_ttl_secs: 0,
+            max_image_dimension: None,
+            blocked_source_apps: Vec::new(),
+            exclude_passwords: true,
+        };
+        assert!(store_new_content(Some("Tr0ub4dor&3x".into()), None, &db, &last_text, &last_image, &options));
+        assert_eq!(db.lock().unwrap().count(Some(ContentType::Text)).unwrap(), 0);
+        // Remembered all the same, so it isn't reconsidered on the next poll,
+        // but only in memory: its hash is never written to the database
+        assert!(!store_new_content(Some("Tr0ub4dor&3x".into()), None, &db, &last_text, &last_image, &options));
+        assert_eq!(db.lock().unwrap().latest_hash(ContentType::Text).unwrap(), None);
+
+        options.exclude_passwords = false;
+        store_new_content(Some("aK8#mQ2!zX".into()), None, &db, &last_text, &last_image, &options);
+        assert_eq!(db.lock().unwrap().count(Some(ContentType::Text)).unwrap(), 1);
+        assert_eq!(db.lock().unwrap().latest_hash(ContentType::Text).unwrap(), Some(content_hash(b"aK8#mQ2!zX")));
+    }
+
+    #[test]
+    fn test_flattened_hash_ignores_invisible_alpha() {
+        // Same pixels, but the white backdrop is transparent in the second copy
+        let opaque = [12u8, 200, 64, 255, 255, 255, 255, 255, 255, 255, 255, 255];
+        let with_alpha = [12u8, 200, 64, 255, 255, 255, 255, 0, 255, 255, 255, 128];
+        assert_ne!(image_hash(&opaque, false), image_hash(&with_alpha, false));
+        assert_eq!(image_hash(&opaque, true), image_hash(&with_alpha, true));
+
+        // Alpha that changes the look still counts
+        let translucent = [12u8, 200, 64, 128, 255, 255, 255, 255, 255, 255, 255, 255];
+        assert_ne!(image_hash(&opaque, true), image_hash(&translucent, true));
+    }
+
+    #[test]
+    fn test_dual_content_preference() {
+        assert_eq!(dual_poll(DualPreference::Image), (0, 1));
+        assert_eq!(dual_poll(DualPreference::Text), (1, 0));
+        assert_eq!(dual_poll(DualPreference::Both), (1, 1));
+    }
 }
@@ -0,0 +1,113 @@
+//! Pick the color of a screen pixel and copy it as text
+//! (`shortcuts.pick_color`, `capture.color_format`).
+
+use crate::clipboard::{self, SharedHashes};
+use crate::config::Config;
+use crate::database::Database;
+use crate::events::{self, EntryEvent};
+use crate::models::ColorFormat;
+use crate::{notifications, screenshot};
+use anyhow::{Context, Result};
+use arboard::Clipboard;
+use std::sync::{Arc, Mutex};
+
+/// Write an RGB color in `format`.
+pub fn format_color([r, g, b]: [u8; 3], format: ColorFormat) -> String {
+    match format {
+        ColorFormat::Hex => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        ColorFormat::Rgb => format!("rgb({}, {}, {})", r, g, b),
+        ColorFormat::Hsl => {
+            let (h, s, l) = rgb_to_hsl([r, g, b]);
+            format!("hsl({}, {}%, {}%)", h.round() as u32 % 360, (s * 100.0).round(), (l * 100.0).round())
+        }
+    }
+}
+
+/// Hue in degrees, saturation and lightness in 0..=1.
+fn rgb_to_hsl(rgb: [u8; 3]) -> (f64, f64, f64) {
+    let [r, g, b] = rgb.map(|c| c as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0.0, 0.0, l);
+    }
+    let s = d / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / d + 2.0)
+    } else {
+        60.0 * ((r - g) / d + 4.0)
+    };
+    (h, s, l)
+}
+
+/// The color of the screen pixel under the pointer.
+pub fn color_under_pointer() -> Result<[u8; 3]> {
+    let (x, y) = screenshot::pointer_position().context("Failed to find the pointer")?;
+    let (bgra, _, _) = screenshot::capture_region(x, y, 1, 1).context("Failed to read the pixel")?;
+    match bgra.get(..3) {
+        Some(&[b, g, r]) => Ok([r, g, b]),
+        _ => Err(anyhow::anyhow!("X11 returned no pixel data")),
+    }
+}
+
+/// Copy the color under the pointer as text, store it as a text entry and
+/// show it in a notification. Errors are reported to the user.
+pub fn pick_color(
+    db: &Arc<Mutex<Database>>,
+    clipboard: &Arc<Mutex<Clipboard>>,
+    hashes: &SharedHashes,
+    config: &Config,
+) {
+    if config.capture.refuse_when_locked && screenshot::is_secure_context() {
+        notifications::notify_screenshot_error(config, "The screen looks locked, so its colors can't be read.");
+        return;
+    }
+    let rgb = match color_under_pointer() {
+        Ok(rgb) => rgb,
+        Err(e) => {
+            log::error!("Color pick failed: {:#}", e);
+            notifications::notify_screenshot_error(config, &format!("Color pick failed: {:#}", e));
+            return;
+        }
+    };
+    let text = format_color(rgb, config.color_format());
+
+    if let Ok(db) = db.lock() {
+        match db.insert_text(&text) {
+            Ok(id) => events::publish(&EntryEvent::text(id, &text)),
+            Err(e) => log::error!("Failed to save picked color: {}", e),
+        }
+    }
+    // Already stored above; the monitor shouldn't store it again
+    hashes.remember_text(&text);
+    if let Ok(mut cb) = clipboard.lock() {
+        if let Err(e) = clipboard::set_clipboard_text(&mut cb, &text) {
+            log::error!("Failed to copy picked color: {}", e);
+        }
+    }
+    notifications::notify_color_picked(config, &text, rgb);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_color() {
+        let dodger_blue = [30, 144, 255];
+        assert_eq!(format_color(dodger_blue, ColorFormat::Hex), "#1e90ff");
+        assert_eq!(format_color(dodger_blue, ColorFormat::Rgb), "rgb(30, 144, 255)");
+        assert_eq!(format_color(dodger_blue, ColorFormat::Hsl), "hsl(210, 100%, 56%)");
+
+        assert_eq!(format_color([0, 0, 0], ColorFormat::Hsl), "hsl(0, 0%, 0%)");
+        assert_eq!(format_color([255, 255, 255], ColorFormat::Hex), "#ffffff");
+        assert_eq!(format_color([128, 128, 128], ColorFormat::Hsl), "hsl(0, 0%, 50%)");
+        // Hues wrap below red rather than going negative
+        assert_eq!(format_color([255, 0, 64], ColorFormat::Hsl), "hsl(345, 100%, 50%)");
+        assert_eq!(format_color([0, 128, 0], ColorFormat::Hsl), "hsl(120, 100%, 25%)");
+    }
+}
@@ -1,7 +1,36 @@
-use anyhow::{Context, Result};
+use crate::models::{CaptureMethod, ColorFormat, DualPreference, LargeImageFallback, PasteMethod, Rectangle, RedactStyle, SelectionMode, SortOrder, StorageMode, TimeFormat};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// How often `Config::watch` checks the config file.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Why the configuration could not be loaded or saved.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file: {path:?}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to parse config TOML")]
+    Parse(#[from] toml::de::Error),
+    #[error("Failed to serialize config")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("Failed to write config: {path:?}")]
+    Write {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
 
 /// Top-level application configuration.
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -12,12 +41,34 @@ pub struct Config {
     pub storage: StorageConfig,
     pub ui: UiConfig,
     pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Shortcuts {
     pub screenshot: String,
     pub history: String,
+    /// Re-capture the last selected region without opening the overlay.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_region: Option<String>,
+    /// Pick a window with the next click and capture just that window.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_capture: Option<String>,
+    /// Open the capture overlay and pin the capture, so cleanup keeps it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture_pinned: Option<String>,
+    /// Copy the color of the pixel clicked next.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pick_color: Option<String>,
+    /// Capture every monitor at once, without the overlay.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fullscreen: Option<String>,
+    /// Capture the focused window, frame included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_window: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -25,6 +76,162 @@ pub struct CaptureConfig {
     pub format: String,
     pub quality: u8,
     pub show_dimensions: bool,
+    /// Shell command run after each capture; `{path}` expands to a saved PNG.
+    #[serde(default)]
+    pub post_capture_command: String,
+    /// Pipe the PNG to the command's stdin.
+    #[serde(default)]
+    pub post_capture_stdin: bool,
+    /// Copy a URL printed by the command to the clipboard.
+    #[serde(default)]
+    pub post_capture_copy_url: bool,
+    /// Smallest selection (device pixels per side) captured without question.
+    #[serde(default = "default_min_selection_px")]
+    pub min_selection_px: u32,
+    /// What to do with a smaller selection: "notify", "capture" or "ignore".
+    #[serde(default = "default_small_selection")]
+    pub small_selection: String,
+    /// How a region is selected in the overlay: "drag" (press, drag, release)
+    /// or "click-click" (click two opposite corners).
+    #[serde(default = "default_selection_mode")]
+    pub selection_mode: String,
+    /// What to store when a copy puts both text and an image on the
+    /// clipboard: "image", "text" or "both".
+    #[serde(default = "default_prefer_on_dual")]
+    pub prefer_on_dual: String,
+    /// How many recent contents per type are remembered for de-duplication;
+    /// 1 only skips a repeat of the very last copy.
+    #[serde(default = "default_dedup_window")]
+    pub dedup_window: usize,
+    /// Composite clipboard images over white before hashing them for
+    /// de-duplication, so copies differing only in alpha count as repeats.
+    #[serde(default)]
+    pub dedup_flatten_alpha: bool,
+    /// Formats copied images are offered in on X11, as MIME types
+    /// ("image/png", "image/bmp"). Fewer targets make pasting negotiate faster.
+    #[serde(default = "default_clipboard_targets")]
+    pub clipboard_targets: Vec<String>,
+    /// How many captures (overlays, window picks, repeats) may run at once.
+    /// Hotkey presses beyond that are ignored.
+    #[serde(default = "default_max_concurrent_captures")]
+    pub max_concurrent_captures: usize,
+    /// Seconds to count down on screen between picking a region or window
+    /// and taking the shot, to capture menus and other transient UI.
+    #[serde(default)]
+    pub countdown_seconds: u32,
+    /// Snap the overlay selection to nearby window edges.
+    #[serde(default)]
+    pub snap_to_windows: bool,
+    /// How close (in logical pixels) the pointer must be to an edge to snap.
+    #[serde(default = "default_snap_distance_px")]
+    pub snap_distance_px: u32,
+    /// How areas right-dragged in the overlay are hidden: "blur" or "black".
+    #[serde(default = "default_redact_style")]
+    pub redact_style: String,
+    /// What to copy when a capture is too large for the clipboard or copying
+    /// it fails: "uri" copies the saved PNG as a file, "none" nothing.
+    #[serde(default = "default_large_image_fallback")]
+    pub large_image_fallback: String,
+    /// Refuse to capture while the screen looks locked (screen saver active
+    /// or the keyboard grabbed by a lock screen or secure prompt), rather
+    /// than storing a black image.
+    #[serde(default = "default_true")]
+    pub refuse_when_locked: bool,
+    /// How a picked color is copied: "hex", "rgb" or "hsl".
+    #[serde(default = "default_color_format")]
+    pub color_format: String,
+    /// Per-action format/quality overrides, keyed by action name
+    /// ("region", "repeat_region", "clipboard_image", "window", "fullscreen").
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub actions: HashMap<String, ActionCaptureConfig>,
+}
+
+/// Overrides for a single capture action; unset fields use `[capture]`.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ActionCaptureConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+}
+
+/// Encoding settings resolved for one capture, and how it is taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureOutput {
+    pub format: String,
+    pub quality: u8,
+    /// Recorded with the stored image.
+    pub method: CaptureMethod,
+    /// Areas to hide before the image is copied or stored, in pixels of the
+    /// captured image.
+    pub redactions: Vec<Rectangle>,
+    /// Pin the stored image (`shortcuts.capture_pinned`).
+    pub pin: bool,
+}
+
+impl CaptureConfig {
+    /// Format and quality for `action`, falling back to the global settings.
+    pub fn output_for(&self, action: &str) -> CaptureOutput {
+        let overrides = self.actions.get(action);
+        CaptureOutput {
+            format: overrides
+                .and_then(|o| o.format.clone())
+                .unwrap_or_else(|| self.format.clone()),
+            quality: overrides.and_then(|o| o.quality).unwrap_or(self.quality),
+            method: match action {
+                "window" => CaptureMethod::Window,
+                "fullscreen" => CaptureMethod::Fullscreen,
+                "clipboard_image" => CaptureMethod::ClipboardImport,
+                _ => CaptureMethod::Region,
+            },
+            redactions: Vec::new(),
+            pin: false,
+        }
+    }
+}
+
+fn default_min_selection_px() -> u32 {
+    10
+}
+
+fn default_dedup_window() -> usize {
+    1
+}
+
+fn default_max_concurrent_captures() -> usize {
+    1
+}
+
+fn default_snap_distance_px() -> u32 {
+    8
+}
+
+fn default_clipboard_targets() -> Vec<String> {
+    vec!["image/png".to_string(), "image/bmp".to_string()]
+}
+
+fn default_prefer_on_dual() -> String {
+    "both".to_string()
+}
+
+fn default_redact_style() -> String {
+    "blur".to_string()
+}
+
+fn default_large_image_fallback() -> String {
+    "uri".to_string()
+}
+
+fn default_color_format() -> String {
+    "hex".to_string()
+}
+
+fn default_small_selection() -> String {
+    "notify".to_string()
+}
+
+fn default_selection_mode() -> String {
+    "drag".to_string()
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -32,12 +239,47 @@ pub struct HistoryConfig {
     pub max_entries: usize,
     pub retention_days: i64,
     pub auto_cleanup: bool,
+    /// Move an entry to the top of the history when it is restored, instead
+    /// of leaving it in place.
+    #[serde(default)]
+    pub bump_on_restore: bool,
+    /// Downscale images wider or taller than this many pixels before storing
+    /// them. The copy on the clipboard keeps its full size. Unset = no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_image_dimension: Option<u32>,
+    /// Keep the database file under this many bytes by deleting the oldest
+    /// entries at startup. Unset = no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_db_bytes: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct StorageConfig {
+    /// "disk" keeps the history in `database_path`; "memory" keeps it in
+    /// memory only, so nothing survives a restart.
+    #[serde(default = "default_storage_mode")]
+    pub mode: String,
     pub database_path: String,
     pub image_storage: String,
+    /// Store long text entries compressed. Compressed entries are not found
+    /// by the history search.
+    #[serde(default)]
+    pub compress_text: bool,
+    /// Text longer than this many bytes is compressed when `compress_text` is on.
+    #[serde(default = "default_compress_text_min_bytes")]
+    pub compress_text_min_bytes: usize,
+    /// Reload the config when `config.toml` changes, instead of only at
+    /// startup.
+    #[serde(default)]
+    pub watch_config: bool,
+}
+
+fn default_storage_mode() -> String {
+    "disk".to_string()
+}
+
+fn default_compress_text_min_bytes() -> usize {
+    16 * 1024
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -45,11 +287,159 @@ pub struct UiConfig {
     pub theme: String,
     pub thumbnail_size: u32,
     pub notification_duration: u32,
+    /// Tab shown when the history dialog opens: "text" or "images".
+    #[serde(default = "default_tab")]
+    pub default_tab: String,
+    /// History list ordering: "newest", "oldest", "largest" or "most_used".
+    #[serde(default = "default_sort_order")]
+    pub sort_order: String,
+    /// Show the "Drag to select" hint when the overlay opens.
+    #[serde(default = "default_true")]
+    pub show_overlay_hint: bool,
+    /// Paste a picked history entry into the previous window. When off, the
+    /// entry is only copied; holding Shift while picking copies only too.
+    #[serde(default = "default_true")]
+    pub auto_paste: bool,
+    /// Delay before simulating Ctrl+V after picking an entry, giving focus
+    /// time to return to the previous window.
+    #[serde(default = "default_auto_paste_delay_ms")]
+    pub auto_paste_delay_ms: u64,
+    /// How auto-paste delivers the entry: "key" (Ctrl+V) or "type" (type
+    /// text entries as keystrokes, for apps that ignore Ctrl+V).
+    #[serde(default = "default_paste_method")]
+    pub paste_method: String,
+    /// Search history with typo-tolerant matching, best match first, instead
+    /// of exact substrings. Only the most recent entries are searched.
+    #[serde(default)]
+    pub fuzzy_search: bool,
+    /// Entry timestamps: "relative" ("5m ago") or "absolute".
+    #[serde(default = "default_time_format")]
+    pub time_format: String,
+    /// strftime pattern for absolute timestamps, in local time. Unset uses
+    /// `DEFAULT_TIME_PATTERN`.
+    #[serde(default)]
+    pub absolute_time_pattern: Option<String>,
+    /// Add debugging actions (e.g. "Copy raw bytes") to the entry menu.
+    #[serde(default)]
+    pub developer_actions: bool,
+}
+
+fn default_tab() -> String {
+    "text".to_string()
+}
+
+fn default_sort_order() -> String {
+    "newest".to_string()
+}
+
+fn default_auto_paste_delay_ms() -> u64 {
+    150
+}
+
+fn default_paste_method() -> String {
+    "key".to_string()
+}
+
+fn default_time_format() -> String {
+    "relative".to_string()
+}
+
+/// Absolute timestamp pattern used when `ui.absolute_time_pattern` is unset.
+pub const DEFAULT_TIME_PATTERN: &str = "%Y-%m-%d %H:%M";
+
+/// Whether `pattern` is a strftime pattern chrono can format, checked by
+/// formatting a sample timestamp.
+pub fn is_valid_time_pattern(pattern: &str) -> bool {
+    use std::fmt::Write;
+    let Some(sample) = chrono::DateTime::<chrono::Utc>::from_timestamp(1_700_000_000, 0) else {
+        return false;
+    };
+    write!(String::new(), "{}", sample.format(pattern)).is_ok()
+}
+
+/// The window variant `ui.theme` asks for: `Some(true)` for "dark",
+/// `Some(false)` for "light", `None` to follow the desktop ("auto").
+pub fn theme_prefers_dark(theme: &str) -> Option<bool> {
+    match theme {
+        "dark" => Some(true),
+        "light" => Some(false),
+        "auto" => None,
+        other => {
+            log::warn!("Unknown ui.theme {:?}, using \"auto\"", other);
+            None
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PrivacyConfig {
+    /// Don't store copied text that the copying app marks as a password, or
+    /// that looks like one.
     pub exclude_passwords: bool,
+    /// Write captures to a private temp file for the post-capture command, and
+    /// for the notification icon when the daemon can show one. When off,
+    /// nothing touches disk outside the database.
+    #[serde(default = "default_true")]
+    pub temp_file: bool,
+    /// Delete text that looks like a password or token this many seconds
+    /// after it was copied, if `exclude_passwords` let it be stored. 0 keeps
+    /// it like any other entry.
+    #[serde(default)]
+    pub sensitive_ttl_seconds: u64,
+    /// Apps whose clipboard changes are never stored, by X11 WM_CLASS
+    /// (instance or class name, any case).
+    #[serde(default)]
+    pub blocked_source_apps: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct NotificationsConfig {
+    /// Show an in-app toast when no notification daemon is available.
+    pub fallback_inapp: bool,
+    /// Notify after each successful capture.
+    #[serde(default = "default_true")]
+    pub success: bool,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        NotificationsConfig {
+            fallback_inapp: true,
+            success: true,
+        }
+    }
+}
+
+/// One-way text sync through a shared directory. Only used by builds with
+/// the `sync` feature.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    /// Directory shared between machines (e.g. by Syncthing or NFS); `~` is
+    /// expanded.
+    #[serde(default)]
+    pub directory: String,
+    /// Seconds between reads of other machines' entries; 0 only pushes.
+    #[serde(default = "default_sync_pull_interval_secs")]
+    pub pull_interval_secs: u64,
+}
+
+fn default_sync_pull_interval_secs() -> u64 {
+    60
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            enabled: false,
+            directory: String::new(),
+            pull_interval_secs: default_sync_pull_interval_secs(),
+        }
+    }
 }
 
 impl Default for Config {
@@ -58,29 +448,76 @@ impl Default for Config {
             shortcuts: Shortcuts {
                 screenshot: "Ctrl+Alt+S".to_string(),
                 history: "Alt+H".to_string(),
+                repeat_region: None,
+                window_capture: None,
+                capture_pinned: None,
+                pick_color: None,
+                fullscreen: None,
+                active_window: None,
             },
             capture: CaptureConfig {
                 format: "png".to_string(),
                 quality: 95,
                 show_dimensions: true,
+                post_capture_command: String::new(),
+                post_capture_stdin: false,
+                post_capture_copy_url: false,
+                min_selection_px: default_min_selection_px(),
+                small_selection: default_small_selection(),
+                selection_mode: default_selection_mode(),
+                prefer_on_dual: default_prefer_on_dual(),
+                dedup_window: default_dedup_window(),
+                dedup_flatten_alpha: false,
+                clipboard_targets: default_clipboard_targets(),
+                max_concurrent_captures: default_max_concurrent_captures(),
+                countdown_seconds: 0,
+                snap_to_windows: false,
+                snap_distance_px: default_snap_distance_px(),
+                redact_style: default_redact_style(),
+                large_image_fallback: default_large_image_fallback(),
+                refuse_when_locked: true,
+                color_format: default_color_format(),
+                actions: HashMap::new(),
             },
             history: HistoryConfig {
                 max_entries: 200,
                 retention_days: 5,
                 auto_cleanup: true,
+                bump_on_restore: false,
+                max_image_dimension: None,
+                max_db_bytes: None,
             },
             storage: StorageConfig {
+                mode: default_storage_mode(),
                 database_path: "~/.config/clipboard-capture/history.db".to_string(),
                 image_storage: "database".to_string(),
+                compress_text: false,
+                compress_text_min_bytes: default_compress_text_min_bytes(),
+                watch_config: false,
             },
             ui: UiConfig {
                 theme: "auto".to_string(),
                 thumbnail_size: 150,
                 notification_duration: 2,
+                default_tab: default_tab(),
+                sort_order: default_sort_order(),
+                show_overlay_hint: true,
+                auto_paste: true,
+                auto_paste_delay_ms: default_auto_paste_delay_ms(),
+                paste_method: default_paste_method(),
+                fuzzy_search: false,
+                time_format: default_time_format(),
+                absolute_time_pattern: None,
+                developer_actions: false,
             },
             privacy: PrivacyConfig {
                 exclude_passwords: true,
+                temp_file: true,
+                sensitive_ttl_seconds: 0,
+                blocked_source_apps: Vec::new(),
             },
+            notifications: NotificationsConfig::default(),
+            sync: SyncConfig::default(),
         }
     }
 }
@@ -113,8 +550,89 @@ impl Config {
         Self::expand_path(&self.storage.database_path)
     }
 
+    /// Parsed history sort order, falling back to newest-first.
+    pub fn sort_order(&self) -> SortOrder {
+        SortOrder::from_str(&self.ui.sort_order).unwrap_or_else(|| {
+            log::warn!("Unknown ui.sort_order {:?}, using \"newest\"", self.ui.sort_order);
+            SortOrder::Newest
+        })
+    }
+
+    /// Parsed `ui.paste_method`, falling back to Ctrl+V.
+    pub fn paste_method(&self) -> PasteMethod {
+        PasteMethod::from_str(&self.ui.paste_method).unwrap_or_else(|| {
+            log::warn!("Unknown ui.paste_method {:?}, using \"key\"", self.ui.paste_method);
+            PasteMethod::Key
+        })
+    }
+
+    /// Parsed `ui.time_format`, falling back to relative times.
+    pub fn time_format(&self) -> TimeFormat {
+        TimeFormat::from_str(&self.ui.time_format).unwrap_or_else(|| {
+            log::warn!("Unknown ui.time_format {:?}, using \"relative\"", self.ui.time_format);
+            TimeFormat::Relative
+        })
+    }
+
+    /// strftime pattern for absolute timestamps. Checked at load, so it
+    /// always formats.
+    pub fn absolute_time_pattern(&self) -> &str {
+        self.ui.absolute_time_pattern.as_deref().unwrap_or(DEFAULT_TIME_PATTERN)
+    }
+
+    /// Parsed `capture.redact_style`, falling back to blurring.
+    pub fn redact_style(&self) -> RedactStyle {
+        RedactStyle::from_str(&self.capture.redact_style).unwrap_or_else(|| {
+            log::warn!("Unknown capture.redact_style {:?}, using \"blur\"", self.capture.redact_style);
+            RedactStyle::Blur
+        })
+    }
+
+    /// Parsed `capture.selection_mode`, falling back to dragging.
+    pub fn selection_mode(&self) -> SelectionMode {
+        SelectionMode::from_str(&self.capture.selection_mode).unwrap_or_else(|| {
+            log::warn!("Unknown capture.selection_mode {:?}, using \"drag\"", self.capture.selection_mode);
+            SelectionMode::Drag
+        })
+    }
+
+    /// Parsed `storage.mode`, falling back to the database file.
+    pub fn storage_mode(&self) -> StorageMode {
+        StorageMode::from_str(&self.storage.mode).unwrap_or_else(|| {
+            log::warn!("Unknown storage.mode {:?}, using \"disk\"", self.storage.mode);
+            StorageMode::Disk
+        })
+    }
+
+    /// Parsed `capture.color_format`, falling back to hex.
+    pub fn color_format(&self) -> ColorFormat {
+        ColorFormat::from_str(&self.capture.color_format).unwrap_or_else(|| {
+            log::warn!("Unknown capture.color_format {:?}, using \"hex\"", self.capture.color_format);
+            ColorFormat::Hex
+        })
+    }
+
+    /// Parsed `capture.large_image_fallback`, falling back to copying a file.
+    pub fn large_image_fallback(&self) -> LargeImageFallback {
+        LargeImageFallback::from_str(&self.capture.large_image_fallback).unwrap_or_else(|| {
+            log::warn!(
+                "Unknown capture.large_image_fallback {:?}, using \"uri\"",
+                self.capture.large_image_fallback
+            );
+            LargeImageFallback::Uri
+        })
+    }
+
+    /// Parsed `capture.prefer_on_dual`, falling back to storing both.
+    pub fn prefer_on_dual(&self) -> DualPreference {
+        DualPreference::from_str(&self.capture.prefer_on_dual).unwrap_or_else(|| {
+            log::warn!("Unknown capture.prefer_on_dual {:?}, using \"both\"", self.capture.prefer_on_dual);
+            DualPreference::Both
+        })
+    }
+
     /// Load config from the default path, or create a default config if missing.
-    pub fn load_or_create_default() -> Result<Self> {
+    pub fn load_or_create_default() -> Result<Self, ConfigError> {
         let config_path = Self::config_path();
 
         if config_path.exists() {
@@ -127,31 +645,109 @@ impl Config {
     }
 
     /// Load config from a specific file path.
-    pub fn load(path: &PathBuf) -> Result<Self> {
-        let content = fs::read_to_string(path)
-            .with_context(|| format!("Failed to read config file: {:?}", path))?;
-        let config: Config =
-            toml::from_str(&content).with_context(|| "Failed to parse config TOML")?;
+    pub fn load(path: &PathBuf) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.clone(),
+            source,
+        })?;
+        let mut config: Config = toml::from_str(&content)?;
+        if let Some(pattern) = &config.ui.absolute_time_pattern {
+            if !is_valid_time_pattern(pattern) {
+                log::warn!("Invalid ui.absolute_time_pattern {:?}, using {:?}", pattern, DEFAULT_TIME_PATTERN);
+                config.ui.absolute_time_pattern = None;
+            }
+        }
         Ok(config)
     }
 
+    /// Watch the config file at `path` from a background thread, calling
+    /// `on_change` with the reloaded config after each edit. A file that no
+    /// longer parses is logged and skipped, so the previous config stays in
+    /// use until the next valid edit. The thread exits once `shutdown` is
+    /// set, within one `WATCH_INTERVAL`.
+    pub fn watch(
+        path: PathBuf,
+        shutdown: Arc<AtomicBool>,
+        on_change: impl Fn(Config) + Send + 'static,
+    ) -> std::io::Result<JoinHandle<()>> {
+        std::thread::Builder::new().name("config-watcher".into()).spawn(move || {
+            log::info!("Watching {:?} for changes", path);
+            let mut watcher = ConfigWatcher::new(path);
+            while !shutdown.load(Ordering::Relaxed) {
+                std::thread::sleep(WATCH_INTERVAL);
+                if let Some(config) = watcher.poll() {
+                    on_change(config);
+                }
+            }
+        })
+    }
+
     /// Save config to the default path.
-    pub fn save(&self) -> Result<()> {
+    pub fn save(&self) -> Result<(), ConfigError> {
         let config_dir = Self::config_dir();
-        fs::create_dir_all(&config_dir)
-            .with_context(|| format!("Failed to create config dir: {:?}", config_dir))?;
+        fs::create_dir_all(&config_dir).map_err(|source| ConfigError::Write {
+            path: config_dir.clone(),
+            source,
+        })?;
 
         let config_path = Self::config_path();
-        let content =
-            toml::to_string_pretty(self).with_context(|| "Failed to serialize config")?;
-        fs::write(&config_path, content)
-            .with_context(|| format!("Failed to write config file: {:?}", config_path))?;
+        let content = toml::to_string_pretty(self)?;
+        fs::write(&config_path, content).map_err(|source| ConfigError::Write {
+            path: config_path.clone(),
+            source,
+        })?;
 
         log::info!("Config saved to {:?}", config_path);
         Ok(())
     }
 }
 
+/// Modification time and size of a file, to notice that it changed.
+type FileStamp = (SystemTime, u64);
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Polls a config file for `Config::watch`. Editors often save in several
+/// steps (truncate, write, rename), so a change is only reloaded once the
+/// file has looked the same for two polls in a row.
+struct ConfigWatcher {
+    path: PathBuf,
+    last: Option<FileStamp>,
+    changed: bool,
+}
+
+impl ConfigWatcher {
+    fn new(path: PathBuf) -> Self {
+        let last = file_stamp(&path);
+        ConfigWatcher { path, last, changed: false }
+    }
+
+    /// The reloaded config, once a change has settled and parses.
+    fn poll(&mut self) -> Option<Config> {
+        let current = file_stamp(&self.path);
+        if current != self.last {
+            self.last = current;
+            self.changed = true;
+            return None;
+        }
+        // A file that is gone mid-save is reloaded when it comes back
+        if !self.changed || current.is_none() {
+            return None;
+        }
+        self.changed = false;
+        match Config::load(&self.path) {
+            Ok(config) => Some(config),
+            Err(e) => {
+                log::error!("Not reloading {:?}: {}; keeping the previous config", self.path, e);
+                None
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,6 +759,7 @@ mod tests {
         assert_eq!(config.shortcuts.history, "Alt+H");
         assert_eq!(config.history.max_entries, 200);
         assert_eq!(config.history.retention_days, 5);
+        assert!(config.ui.auto_paste);
     }
 
     #[test]
@@ -174,9 +771,116 @@ mod tests {
         assert_eq!(parsed.history.max_entries, config.history.max_entries);
     }
 
+    #[test]
+    fn test_time_pattern_validation() {
+        assert!(is_valid_time_pattern(DEFAULT_TIME_PATTERN));
+        assert!(is_valid_time_pattern("%I:%M %p, %d %b"));
+        assert!(!is_valid_time_pattern("%Y-%m-%d %Q"));
+        assert!(!is_valid_time_pattern("%"));
+    }
+
+    #[test]
+    fn test_theme_prefers_dark() {
+        assert_eq!(theme_prefers_dark("dark"), Some(true));
+        assert_eq!(theme_prefers_dark("light"), Some(false));
+        assert_eq!(theme_prefers_dark("auto"), None);
+        assert_eq!(theme_prefers_dark(&Config::default().ui.theme), None);
+        assert_eq!(theme_prefers_dark("Dark"), None);
+    }
+
     #[test]
     fn test_expand_path() {
         let expanded = Config::expand_path("~/.config/clipboard-capture/history.db");
         assert!(!expanded.to_str().unwrap().starts_with("~"));
     }
+
+    #[test]
+    fn test_action_capture_override_wins_over_global() {
+        let config: Config = toml::from_str(&format!(
+            "{}\n[capture.actions.repeat_region]\nformat = \"jpeg\"\n",
+            toml::to_string_pretty(&Config::default()).unwrap()
+        ))
+        .unwrap();
+
+        let repeat = config.capture.output_for("repeat_region");
+        assert_eq!(repeat.format, "jpeg");
+        assert_eq!(repeat.quality, config.capture.quality);
+        assert_eq!(config.capture.output_for("region").format, "png");
+        assert_eq!(repeat.method, CaptureMethod::Region);
+        assert_eq!(config.capture.output_for("window").method, CaptureMethod::Window);
+        assert_eq!(config.capture.output_for("fullscreen").method, CaptureMethod::Fullscreen);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let path = std::env::temp_dir().join(format!("clipsnap_config_{}.toml", std::process::id()));
+        assert!(matches!(Config::load(&path), Err(ConfigError::Read { .. })));
+
+        fs::write(&path, "[shortcuts\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(ConfigError::Parse(_))));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_watcher_reloads_settled_changes() {
+        let path = std::env::temp_dir().join(format!("clipsnap_watch_{}.toml", std::process::id()));
+        let mut config = Config::default();
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        let mut watcher = ConfigWatcher::new(path.clone());
+        assert!(watcher.poll().is_none());
+
+        config.shortcuts.history = "Ctrl+Alt+Shift+H".to_string();
+        fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+        // Picked up on the poll after the change, once it has settled
+        assert!(watcher.poll().is_none());
+        let reloaded = watcher.poll().expect("settled change is reloaded");
+        assert_eq!(reloaded.shortcuts.history, "Ctrl+Alt+Shift+H");
+        assert!(watcher.poll().is_none());
+
+        // Invalid TOML is skipped, not reported as a new config
+        fs::write(&path, "[shortcuts\n").unwrap();
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().is_none());
+        assert!(watcher.poll().is_none());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_watch_stops_on_shutdown() {
+        let path = std::env::temp_dir().join(format!("clipsnap_watch_stop_{}.toml", std::process::id()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = Config::watch(path, shutdown.clone(), |_| {}).unwrap();
+        shutdown.store(true, Ordering::Relaxed);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_storage_mode() {
+        let mut config = Config::default();
+        assert_eq!(config.storage_mode(), StorageMode::Disk);
+        config.storage.mode = "memory".to_string();
+        assert_eq!(config.storage_mode(), StorageMode::Memory);
+        config.storage.mode = "ram".to_string();
+        assert_eq!(config.storage_mode(), StorageMode::Disk);
+    }
+
+    #[test]
+    fn test_large_image_fallback() {
+        let mut config = Config::default();
+        assert_eq!(config.large_image_fallback(), LargeImageFallback::Uri);
+        config.capture.large_image_fallback = "none".to_string();
+        assert_eq!(config.large_image_fallback(), LargeImageFallback::None);
+        config.capture.large_image_fallback = "clipboard".to_string();
+        assert_eq!(config.large_image_fallback(), LargeImageFallback::Uri);
+    }
+
+    #[test]
+    fn test_missing_notifications_section_uses_defaults() {
+        let mut config = Config::default();
+        config.notifications.fallback_inapp = false;
+        let mut value: toml::Value = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        value.as_table_mut().unwrap().remove("notifications");
+        let parsed: Config = value.try_into().unwrap();
+        assert!(parsed.notifications.fallback_inapp);
+    }
 }
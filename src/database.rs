@@ -1,24 +1,175 @@
-use crate::models::{ContentType, HistoryEntry};
-use anyhow::{Context, Result};
-use rusqlite::{params, Connection};
-use std::path::Path;
+use crate::clipboard::{self, ContentHasher};
+use crate::config::Config;
+use crate::models::{CaptureMethod, ContentType, HistoryEntry, Snippet, SortOrder, StorageMode};
+use crate::screenshot::{self, ScreenshotError};
+use crate::{fuzzy, sensitive};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use rusqlite::{params, Connection, DatabaseName, ErrorCode, OptionalExtension};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Errors from the history database.
+#[derive(Debug, thiserror::Error)]
+pub enum DatabaseError {
+    /// Another connection kept the database locked past the busy timeout.
+    #[error("Database is locked by another process")]
+    Busy(#[source] rusqlite::Error),
+    #[error("Integrity check reported: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Sqlite(rusqlite::Error),
+    #[error("{action}: {path:?}")]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Failed to compress text")]
+    Compression(#[source] std::io::Error),
+    #[error("A snippet named {0:?} already exists")]
+    DuplicateSnippet(String),
+    #[error("Entry {0} has no image")]
+    NoImage(i64),
+    #[error(transparent)]
+    Image(#[from] ScreenshotError),
+    #[error("Database lock poisoned")]
+    Poisoned,
+}
+
+impl From<rusqlite::Error> for DatabaseError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => DatabaseError::Busy(e),
+            _ => DatabaseError::Sqlite(e),
+        }
+    }
+}
+
+impl DatabaseError {
+    fn io(action: &'static str, path: &Path) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.to_path_buf();
+        move |source| DatabaseError::Io { action, path, source }
+    }
+
+    /// Whether the database file itself is damaged, as opposed to being
+    /// locked, unreadable or on a full or read-only disk.
+    fn is_corruption(&self) -> bool {
+        match self {
+            DatabaseError::Corrupt(_) => true,
+            DatabaseError::Sqlite(e) => matches!(
+                e.sqlite_error_code(),
+                Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+            ),
+            _ => false,
+        }
+    }
+}
+
+type Result<T, E = DatabaseError> = std::result::Result<T, E>;
+
+/// `metadata` value marking a text row stored zlib-compressed in `content_data`.
+const DEFLATE_METADATA: &str = "deflate";
+
+/// The columns `entry_from_row` reads, minus the stored blob: list rows
+/// leave `image_data` as `None` and callers fetch it with `get_entry`.
+/// Compressed text keeps its blob, as it is the only copy of the text.
+const LIST_COLUMNS: &str = "id, content_type,
+    CASE WHEN metadata = 'deflate' THEN content_data END AS content_data,
+    text_content, thumbnail, created_at, file_size, width, height, use_count, metadata, note, pinned,
+    content_hash";
+
+/// What `Database::bump_duplicate` did with content about to be stored.
+enum Dedup {
+    /// The content is in the history already, as this entry, now bumped.
+    Bumped(i64),
+    /// The content is new; store it with this `content_hash`.
+    Store(Option<String>),
+}
+
+/// `metadata` value marking a text row that looks like a secret; see
+/// `sensitive::looks_sensitive`. Such rows are short, so never compressed.
+const SENSITIVE_METADATA: &str = "sensitive";
+
+/// SQL expression for the bytes of content one history row holds.
+const ENTRY_BYTES: &str = "IFNULL(length(content_data), 0) \
+    + IFNULL(length(CAST(text_content AS BLOB)), 0) \
+    + IFNULL(length(thumbnail), 0)";
+
+/// How a text entry is laid out in its row; see `Database::text_columns`.
+struct TextColumns<'a> {
+    text_content: &'a str,
+    content_data: Option<Vec<u8>>,
+    metadata: Option<&'static str>,
+}
 
 /// Database layer wrapping a SQLite connection.
 pub struct Database {
     conn: Connection,
+    /// Compress text entries longer than this many bytes (`storage.compress_text`).
+    compress_text_over: Option<usize>,
+    /// Keep at most this many entries, dropping the oldest on every insert.
+    ring_capacity: Option<usize>,
+    /// Whether `clipboard_fts` exists; SQLite may be built without FTS5.
+    fts: bool,
+}
+
+/// What happened when a corrupt database file was found at startup.
+#[derive(Debug, Clone)]
+pub struct RepairReport {
+    /// Where the corrupt file was moved to.
+    pub backup_path: PathBuf,
+    /// Rows copied from the corrupt file into the fresh database.
+    pub recovered_rows: usize,
 }
 
 impl Database {
     /// Open (or create) the database at the given path.
     pub fn new(db_path: &Path) -> Result<Self> {
+        Self::open_with_repair(db_path).map(|(db, _)| db)
+    }
+
+    /// Open the database, checking its integrity first. A corrupt file is moved
+    /// aside, a fresh database is created in its place and as many rows as can
+    /// still be read are copied over. Any other failure is returned as is and
+    /// leaves the file untouched.
+    pub fn open_with_repair(db_path: &Path) -> Result<(Self, Option<RepairReport>)> {
         // Ensure parent directory exists
         if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create database dir: {:?}", parent))?;
+            std::fs::create_dir_all(parent).map_err(DatabaseError::io("Failed to create database dir", parent))?;
+        }
+
+        match Self::open_checked(db_path) {
+            Ok(db) => return Ok((db, None)),
+            Err(e) if e.is_corruption() => {
+                log::error!("Database {:?} failed integrity check: {:#}", db_path, e)
+            }
+            Err(e) => return Err(e),
         }
 
-        let conn = Connection::open(db_path)
-            .with_context(|| format!("Failed to open database: {:?}", db_path))?;
+        let backup_path = Self::move_aside(db_path)?;
+        let db = Self::open_checked(db_path)?;
+        let recovered_rows = db.salvage_from(&backup_path);
+        log::warn!(
+            "Started a fresh database; recovered {} entries, corrupt file kept at {:?}",
+            recovered_rows,
+            backup_path
+        );
+        Ok((db, Some(RepairReport { backup_path, recovered_rows })))
+    }
+
+    /// Open, configure and verify the database file. Startup only runs
+    /// SQLite's `quick_check`, which skips the index-content comparison that
+    /// makes the full check slow on a large history; see `integrity_problems`.
+    fn open_checked(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        let verdict: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+        if verdict != "ok" {
+            return Err(DatabaseError::Corrupt(verdict));
+        }
 
         // Enable WAL mode for better concurrent access and optimize performance
         conn.execute_batch(
@@ -30,22 +181,129 @@ impl Database {
              PRAGMA mmap_size=30000000000;"
         )?;
 
-        let db = Database { conn };
+        let mut db = Database {
+            conn,
+            compress_text_over: None,
+            ring_capacity: None,
+            fts: false,
+        };
         db.init_schema()?;
         Ok(db)
     }
 
-    /// Create an in-memory database (for testing).
-    #[cfg(test)]
+    /// Run SQLite's full `integrity_check` on the database file for
+    /// `clipsnap doctor`. Returns the problems it reports; none for a healthy
+    /// file. The check changes nothing, but checking the full-text index
+    /// needs a writable connection.
+    pub fn integrity_problems(db_path: &Path) -> Result<Vec<String>> {
+        let conn = Connection::open(db_path)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let lines = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(lines.into_iter().filter(|line| line != "ok").collect())
+    }
+
+    /// Rename a corrupt database (and its WAL/SHM files) out of the way.
+    fn move_aside(db_path: &Path) -> Result<PathBuf> {
+        let stamp = chrono::Utc::now().format("%Y%m%d%H%M%S");
+        let file_name = db_path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let backup_path = db_path.with_file_name(format!("{}.corrupt-{}", file_name, stamp));
+        std::fs::rename(db_path, &backup_path)
+            .map_err(DatabaseError::io("Failed to move corrupt database to", &backup_path))?;
+        for suffix in ["-wal", "-shm"] {
+            let side = db_path.with_file_name(format!("{}{}", file_name, suffix));
+            if side.exists() {
+                let _ = std::fs::rename(&side, backup_path.with_file_name(format!(
+                    "{}.corrupt-{}{}",
+                    file_name, stamp, suffix
+                )));
+            }
+        }
+        Ok(backup_path)
+    }
+
+    /// Copy every still-readable history row from a corrupt database file,
+    /// one row at a time so a damaged page only loses the rows on it.
+    fn salvage_from(&self, corrupt_path: &Path) -> usize {
+        let attached = self.conn.execute(
+            "ATTACH DATABASE ?1 AS corrupt",
+            params![corrupt_path.to_string_lossy()],
+        );
+        if let Err(e) = attached {
+            log::warn!("Could not attach corrupt database for recovery: {}", e);
+            return 0;
+        }
+
+        let recovered = self.copy_readable_rows().unwrap_or_else(|e| {
+            log::warn!("Recovery stopped early: {}", e);
+            0
+        });
+        let _ = self.conn.execute("DETACH DATABASE corrupt", []);
+        recovered
+    }
+
+    fn copy_readable_rows(&self) -> Result<usize> {
+        // Only copy columns both schemas have, so older files can be salvaged too.
+        let ours = self.column_names("main")?;
+        let columns: Vec<String> = self
+            .column_names("corrupt")?
+            .into_iter()
+            .filter(|c| ours.contains(c))
+            .collect();
+        if columns.is_empty() {
+            return Ok(0);
+        }
+        let column_list = columns.join(", ");
+
+        let mut ids = Vec::new();
+        {
+            let mut stmt = self.conn.prepare("SELECT rowid FROM corrupt.clipboard_history")?;
+            let mut rows = stmt.query([])?;
+            // Keep whatever ids were readable before hitting a bad page.
+            while let Ok(Some(row)) = rows.next() {
+                if let Ok(id) = row.get::<_, i64>(0) {
+                    ids.push(id);
+                }
+            }
+        }
+
+        let sql = format!(
+            "INSERT INTO main.clipboard_history ({cols})
+             SELECT {cols} FROM corrupt.clipboard_history WHERE rowid = ?1",
+            cols = column_list
+        );
+        let mut insert = self.conn.prepare(&sql)?;
+        Ok(ids.into_iter().filter(|id| insert.execute(params![id]).is_ok()).count())
+    }
+
+    /// Column names of `clipboard_history` in the given attached schema.
+    fn column_names(&self, schema: &str) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("PRAGMA {}.table_info(clipboard_history)", schema))?;
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(names)
+    }
+
+    /// Create a database that lives in memory only (`storage.mode =
+    /// "memory"`, and tests). Nothing is written to disk.
     pub fn new_in_memory() -> Result<Self> {
         let conn = Connection::open_in_memory()?;
-        let db = Database { conn };
+        let mut db = Database {
+            conn,
+            compress_text_over: None,
+            ring_capacity: None,
+            fts: false,
+        };
         db.init_schema()?;
         Ok(db)
     }
 
     /// Create tables and indexes if they don't exist.
-    fn init_schema(&self) -> Result<()> {
+    fn init_schema(&mut self) -> Result<()> {
         self.conn.execute_batch(
             "CREATE TABLE IF NOT EXISTS clipboard_history (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -56,6 +314,11 @@ impl Database {
                 created_at INTEGER NOT NULL,
                 file_size INTEGER,
                 metadata TEXT,
+                width INTEGER,
+                height INTEGER,
+                use_count INTEGER NOT NULL DEFAULT 0,
+                note TEXT,
+                pinned INTEGER NOT NULL DEFAULT 0,
                 CHECK (
                     (content_type = 'image' AND content_data IS NOT NULL) OR
                     (content_type = 'text' AND text_content IS NOT NULL)
@@ -66,217 +329,1715 @@ impl Database {
                 ON clipboard_history(created_at DESC);
             CREATE INDEX IF NOT EXISTS idx_content_type
                 ON clipboard_history(content_type);
+
+            CREATE TABLE IF NOT EXISTS snippets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                text_content TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
             ",
         )?;
+
+        // Older databases predate these columns
+        let columns = self.column_names("main")?;
+        for (column, decl) in [
+            ("width", "INTEGER"),
+            ("height", "INTEGER"),
+            ("use_count", "INTEGER NOT NULL DEFAULT 0"),
+            ("note", "TEXT"),
+            ("pinned", "INTEGER NOT NULL DEFAULT 0"),
+            ("content_hash", "TEXT"),
+        ] {
+            if !columns.iter().any(|c| c == column) {
+                self.conn.execute_batch(&format!(
+                    "ALTER TABLE clipboard_history ADD COLUMN {} {}",
+                    column, decl
+                ))?;
+            }
+        }
+        // Rows stored before the column existed have no hash, so they are
+        // never matched; NULLs don't collide in a unique index
+        self.conn.execute_batch(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_content_hash ON clipboard_history(content_hash);",
+        )?;
+
+        self.fts = match self.init_fts() {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Full-text search unavailable, searching with LIKE: {}", e);
+                false
+            }
+        };
+
         log::info!("Database schema initialized");
         Ok(())
     }
 
-    /// Insert an image entry. Returns the row id.
-    pub fn insert_image(&self, png_bytes: &[u8], thumbnail: &[u8]) -> Result<i64> {
+    /// Create the full-text index over text and notes, kept in step with
+    /// `clipboard_history` by triggers. Indexes rows stored before it existed.
+    fn init_fts(&self) -> Result<()> {
+        let existed: bool = self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'clipboard_fts')",
+            [],
+            |row| row.get(0),
+        )?;
+        self.conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS clipboard_fts USING fts5(
+                text_content, note, content = 'clipboard_history', content_rowid = 'id'
+            );
+
+            CREATE TRIGGER IF NOT EXISTS clipboard_fts_insert AFTER INSERT ON clipboard_history BEGIN
+                INSERT INTO clipboard_fts (rowid, text_content, note)
+                    VALUES (new.id, new.text_content, new.note);
+            END;
+            CREATE TRIGGER IF NOT EXISTS clipboard_fts_delete AFTER DELETE ON clipboard_history BEGIN
+                INSERT INTO clipboard_fts (clipboard_fts, rowid, text_content, note)
+                    VALUES ('delete', old.id, old.text_content, old.note);
+            END;
+            CREATE TRIGGER IF NOT EXISTS clipboard_fts_update
+                AFTER UPDATE OF text_content, note ON clipboard_history BEGIN
+                INSERT INTO clipboard_fts (clipboard_fts, rowid, text_content, note)
+                    VALUES ('delete', old.id, old.text_content, old.note);
+                INSERT INTO clipboard_fts (rowid, text_content, note)
+                    VALUES (new.id, new.text_content, new.note);
+            END;",
+        )?;
+        if !existed {
+            self.conn.execute("INSERT INTO clipboard_fts (clipboard_fts) VALUES ('rebuild')", [])?;
+        }
+        Ok(())
+    }
+
+    /// Insert an image entry of `width`×`height` pixels. Returns the row id.
+    pub fn insert_image(&self, png_bytes: &[u8], thumbnail: &[u8], width: u32, height: u32) -> Result<i64> {
+        self.insert_image_row(png_bytes, thumbnail, width, height, None)
+    }
+
+    /// Insert an image that arrived encoded, recording the MIME type of the
+    /// original bytes (see `screenshot::convert_to_png`). Returns the row id.
+    pub fn insert_converted_image(
+        &self,
+        png_bytes: &[u8],
+        thumbnail: &[u8],
+        width: u32,
+        height: u32,
+        source_mime: &str,
+    ) -> Result<i64> {
+        self.insert_image_row(png_bytes, thumbnail, width, height, Some(source_mime))
+    }
+
+    /// Insert a screen capture encoded as `mime`, recording how it was taken.
+    /// Returns the row id.
+    pub fn insert_captured_image(
+        &self,
+        image_bytes: &[u8],
+        thumbnail: &[u8],
+        width: u32,
+        height: u32,
+        method: CaptureMethod,
+        mime: &str,
+    ) -> Result<i64> {
+        self.insert_image_row(image_bytes, thumbnail, width, height, Some(&method.to_capture_metadata(mime)))
+    }
+
+    fn insert_image_row(
+        &self,
+        png_bytes: &[u8],
+        thumbnail: &[u8],
+        width: u32,
+        height: u32,
+        metadata: Option<&str>,
+    ) -> Result<i64> {
         let now = chrono::Utc::now().timestamp();
         let size = png_bytes.len() as i64;
+        let hash = clipboard::content_hash(png_bytes);
+        let same_content = |id| self.blob_is(id, png_bytes);
+        let hash = match self.bump_duplicate(hash, ContentType::Image, size, now, same_content)? {
+            Dedup::Bumped(id) => return Ok(id),
+            Dedup::Store(hash) => hash,
+        };
 
         self.conn.execute(
             "INSERT INTO clipboard_history
-                (content_type, content_data, thumbnail, created_at, file_size)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params!["image", png_bytes, thumbnail, now, size],
+                (content_type, content_data, thumbnail, created_at, file_size, width, height, metadata, content_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params!["image", png_bytes, thumbnail, now, size, width, height, metadata, hash],
         )?;
-        Ok(self.conn.last_insert_rowid())
+        let id = self.conn.last_insert_rowid();
+        self.trim_ring()?;
+        Ok(id)
+    }
+
+    /// Insert a screen capture by streaming an image file encoded as `mime`
+    /// into the blob, so the image never has to be held in memory in full.
+    /// Returns the row id.
+    pub fn insert_image_file(
+        &self,
+        path: &Path,
+        thumbnail: &[u8],
+        width: u32,
+        height: u32,
+        method: CaptureMethod,
+        mime: &str,
+    ) -> Result<i64> {
+        let mut file = std::fs::File::open(path).map_err(DatabaseError::io("Failed to open image file", path))?;
+        let size = file.metadata().map_err(DatabaseError::io("Failed to stat image file", path))?.len() as i64;
+        let now = chrono::Utc::now().timestamp();
+        let hash = file_hash(&mut file).map_err(DatabaseError::io("Failed to read image file", path))?;
+        let same_content = |id| {
+            file.rewind().map_err(DatabaseError::io("Failed to read image file", path))?;
+            let blob = self.conn.blob_open(DatabaseName::Main, "clipboard_history", "content_data", id, true)?;
+            same_bytes(blob, &mut file, size as u64).map_err(DatabaseError::io("Failed to read image file", path))
+        };
+        let hash = match self.bump_duplicate(hash, ContentType::Image, size, now, same_content)? {
+            Dedup::Bumped(id) => return Ok(id),
+            Dedup::Store(hash) => hash,
+        };
+        file.rewind().map_err(DatabaseError::io("Failed to read image file", path))?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO clipboard_history
+                (content_type, content_data, thumbnail, created_at, file_size, width, height, metadata, content_hash)
+             VALUES (?1, zeroblob(?2), ?3, ?4, ?2, ?5, ?6, ?7, ?8)",
+            params!["image", size, thumbnail, now, width, height, method.to_capture_metadata(mime), hash],
+        )?;
+        let id = tx.last_insert_rowid();
+        {
+            let mut blob = tx.blob_open(DatabaseName::Main, "clipboard_history", "content_data", id, false)?;
+            std::io::copy(&mut file, &mut blob)
+                .map_err(DatabaseError::io("Failed to stream image into database", path))?;
+        }
+        tx.commit()?;
+        self.trim_ring()?;
+        Ok(id)
     }
 
-    /// Insert a text entry. Returns the row id.
+    /// Keep at most `max` entries from now on, dropping the oldest whenever
+    /// an insert goes over (`storage.mode = "memory"`), or no limit with
+    /// `None`.
+    pub fn set_ring_capacity(&mut self, max: Option<usize>) -> Result<()> {
+        self.ring_capacity = max;
+        self.trim_ring()
+    }
+
+    fn trim_ring(&self) -> Result<()> {
+        match self.ring_capacity {
+            Some(max) => self.enforce_max_entries(max),
+            None => Ok(()),
+        }
+    }
+
+    /// Store text entries longer than `min_bytes` compressed, or every entry
+    /// as plain text with `None`.
+    ///
+    /// Compressed rows keep an empty `text_content` (the schema requires it
+    /// to be non-NULL for text), so `search_text` does not find them.
+    pub fn set_text_compression(&mut self, min_bytes: Option<usize>) {
+        self.compress_text_over = min_bytes;
+    }
+
+    /// Columns for storing `text`, compressed if it is long enough and
+    /// flagged if it looks like a secret.
+    fn text_columns<'a>(&self, text: &'a str) -> Result<TextColumns<'a>> {
+        if sensitive::looks_sensitive(text) {
+            return Ok(TextColumns {
+                text_content: text,
+                content_data: None,
+                metadata: Some(SENSITIVE_METADATA),
+            });
+        }
+        match self.compress_text_over {
+            Some(min) if text.len() > min => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(text.as_bytes()).map_err(DatabaseError::Compression)?;
+                Ok(TextColumns {
+                    text_content: "",
+                    content_data: Some(encoder.finish().map_err(DatabaseError::Compression)?),
+                    metadata: Some(DEFLATE_METADATA),
+                })
+            }
+            _ => Ok(TextColumns {
+                text_content: text,
+                content_data: None,
+                metadata: None,
+            }),
+        }
+    }
+
+    /// Insert a text entry. Text already in the history is moved to the top
+    /// instead of being stored again. Returns the row id either way.
     pub fn insert_text(&self, text: &str) -> Result<i64> {
         let now = chrono::Utc::now().timestamp();
         let size = text.len() as i64;
+        let hash = clipboard::content_hash(text.as_bytes());
+        let hash = match self.bump_duplicate(hash, ContentType::Text, size, now, |id| self.text_is(id, text))? {
+            Dedup::Bumped(id) => return Ok(id),
+            Dedup::Store(hash) => hash,
+        };
+        let cols = self.text_columns(text)?;
 
         self.conn.execute(
             "INSERT INTO clipboard_history
-                (content_type, text_content, created_at, file_size)
-             VALUES (?1, ?2, ?3, ?4)",
-            params!["text", text, now, size],
+                (content_type, text_content, content_data, metadata, created_at, file_size, content_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params!["text", cols.text_content, cols.content_data, cols.metadata, now, size, hash],
         )?;
-        Ok(self.conn.last_insert_rowid())
+        let id = self.conn.last_insert_rowid();
+        self.trim_ring()?;
+        Ok(id)
+    }
+
+    /// Insert many text entries with their original `created_at` timestamps in
+    /// one transaction. Text already in the history keeps a single row, with
+    /// the newer of the two timestamps. Returns how many rows were added,
+    /// including any the ring buffer dropped again.
+    pub fn insert_batch(&self, entries: &[(String, i64)]) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut added = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO clipboard_history
+                    (content_type, text_content, content_data, metadata, created_at, file_size, content_hash)
+                 VALUES ('text', ?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for (text, created_at) in entries {
+                let hash = clipboard::content_hash(text.as_bytes());
+                let size = text.len() as i64;
+                let hash = match self.bump_duplicate(hash, ContentType::Text, size, *created_at, |id| self.text_is(id, text))? {
+                    Dedup::Bumped(_) => continue,
+                    Dedup::Store(hash) => hash,
+                };
+                let cols = self.text_columns(text)?;
+                stmt.execute(params![
+                    cols.text_content,
+                    cols.content_data,
+                    cols.metadata,
+                    created_at,
+                    size,
+                    hash
+                ])?;
+                added += 1;
+            }
+        }
+        tx.commit()?;
+        self.trim_ring()?;
+        Ok(added)
+    }
+
+    /// Move the entry holding the same content up to `created_at`, unless it
+    /// is newer already, rather than storing the content a second time.
+    ///
+    /// The entry is found by `hash`, then must match in type and `size`, and
+    /// `same_content` must confirm it by id: a 64-bit hash can collide. New
+    /// content whose hash another entry holds is stored without one, so it
+    /// is stored but never deduplicated.
+    fn bump_duplicate(
+        &self,
+        hash: String,
+        content_type: ContentType,
+        size: i64,
+        created_at: i64,
+        same_content: impl FnOnce(i64) -> Result<bool>,
+    ) -> Result<Dedup> {
+        let stored = self
+            .conn
+            .query_row(
+                "SELECT id, content_type, file_size FROM clipboard_history WHERE content_hash = ?1",
+                params![hash],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<i64>>(2)?)),
+            )
+            .optional()?;
+        let Some((id, stored_type, stored_size)) = stored else {
+            return Ok(Dedup::Store(Some(hash)));
+        };
+        if stored_type != content_type.to_str() || stored_size != Some(size) || !same_content(id)? {
+            log::warn!("Content hash {} of new content collides with entry {}; storing it unhashed", hash, id);
+            return Ok(Dedup::Store(None));
+        }
+        self.conn.execute(
+            "UPDATE clipboard_history SET created_at = MAX(created_at, ?2) WHERE id = ?1",
+            params![id, created_at],
+        )?;
+        Ok(Dedup::Bumped(id))
+    }
+
+    /// Whether text entry `id` holds exactly `text`, compressed or not.
+    fn text_is(&self, id: i64, text: &str) -> Result<bool> {
+        Ok(self.get_entry(id)?.and_then(|e| e.text_content).as_deref() == Some(text))
+    }
+
+    /// Whether entry `id` stores exactly `bytes` in `content_data`.
+    fn blob_is(&self, id: i64, bytes: &[u8]) -> Result<bool> {
+        let same = self.conn.query_row(
+            "SELECT content_data = ?2 FROM clipboard_history WHERE id = ?1",
+            params![id, bytes],
+            |row| row.get::<_, Option<bool>>(0),
+        )?;
+        Ok(same == Some(true))
     }
 
-    /// Retrieve the most recent entries filtered by type.
-    pub fn get_recent_entries_by_type(&self, limit: usize, content_type: ContentType) -> Result<Vec<HistoryEntry>> {
+    /// Retrieve up to `limit` entries of any type, newest first.
+    pub fn get_recent_entries(&self, limit: usize) -> Result<Vec<HistoryEntry>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, content_type, content_data, text_content,
-                    thumbnail, created_at, file_size
+                    thumbnail, created_at, file_size, width, height, use_count, metadata, note, pinned, content_hash
+             FROM clipboard_history
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?1",
+        )?;
+
+        let entries = stmt
+            .query_map(params![limit as i64], entry_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Retrieve up to `limit` entries of one type in the given order.
+    pub fn get_recent_entries_by_type(
+        &self,
+        limit: usize,
+        content_type: ContentType,
+        order: SortOrder,
+    ) -> Result<Vec<HistoryEntry>> {
+        let sql = format!(
+            "SELECT id, content_type, content_data, text_content,
+                    thumbnail, created_at, file_size, width, height, use_count, metadata, note, pinned, content_hash
              FROM clipboard_history
              WHERE content_type = ?1
-             ORDER BY created_at DESC
+             ORDER BY {}
              LIMIT ?2",
-        )?;
+            order.order_by_clause()
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
 
         let ct_str = content_type.to_str();
         let entries = stmt
-            .query_map(params![ct_str, limit as i64], |row| {
-                let ct_str: String = row.get(1)?;
-                let content_type = ContentType::from_str(&ct_str).unwrap_or(ContentType::Text);
-                Ok(HistoryEntry {
-                    id: row.get(0)?,
-                    content_type,
-                    image_data: row.get(2)?,
-                    text_content: row.get(3)?,
-                    thumbnail: row.get(4)?,
-                    created_at: row.get(5)?,
-                    file_size: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
-                })
-            })?
+            .query_map(params![ct_str, limit as i64], entry_from_row)?
             .filter_map(|r| r.ok())
             .collect();
 
         Ok(entries)
     }
 
-    /// Get a single entry by id (with full image data).
-    pub fn get_entry(&self, id: i64) -> Result<Option<HistoryEntry>> {
+    /// Like `get_recent_entries_by_type`, without loading image blobs; see
+    /// `LIST_COLUMNS`.
+    pub fn get_recent_metadata_by_type(
+        &self,
+        limit: usize,
+        content_type: ContentType,
+        order: SortOrder,
+    ) -> Result<Vec<HistoryEntry>> {
+        let sql = format!(
+            "SELECT {}
+             FROM clipboard_history
+             WHERE content_type = ?1
+             ORDER BY {}
+             LIMIT ?2",
+            LIST_COLUMNS,
+            order.order_by_clause()
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let entries = stmt
+            .query_map(params![content_type.to_str(), limit as i64], entry_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Text entries with an id above `after_id`, oldest first.
+    pub fn text_entries_after(&self, after_id: i64, limit: usize) -> Result<Vec<HistoryEntry>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, content_type, content_data, text_content,
-                    thumbnail, created_at, file_size
+                    thumbnail, created_at, file_size, width, height, use_count, metadata, note, pinned, content_hash
              FROM clipboard_history
-             WHERE id = ?1",
+             WHERE content_type = 'text' AND id > ?1
+             ORDER BY id ASC
+             LIMIT ?2",
         )?;
-
-        let mut entries: Vec<HistoryEntry> = stmt
-            .query_map(params![id], |row| {
-                let ct_str: String = row.get(1)?;
-                let content_type = ContentType::from_str(&ct_str).unwrap_or(ContentType::Text);
-                Ok(HistoryEntry {
-                    id: row.get(0)?,
-                    content_type,
-                    image_data: row.get(2)?,
-                    text_content: row.get(3)?,
-                    thumbnail: row.get(4)?,
-                    created_at: row.get(5)?,
-                    file_size: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
-                })
-            })?
+        let entries = stmt
+            .query_map(params![after_id, limit as i64], entry_from_row)?
             .filter_map(|r| r.ok())
             .collect();
+        Ok(entries)
+    }
 
-        Ok(entries.pop())
+    /// Highest entry id so far, 0 for an empty history.
+    pub fn max_entry_id(&self) -> Result<i64> {
+        let id = self
+            .conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM clipboard_history", [], |row| row.get(0))?;
+        Ok(id)
     }
 
-    /// Search text entries by substring match.
-    pub fn search_text(&self, query: &str) -> Result<Vec<HistoryEntry>> {
-        let pattern = format!("%{}%", query);
+    /// Get a single entry by id (with full image data).
+    pub fn get_entry(&self, id: i64) -> Result<Option<HistoryEntry>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, content_type, content_data, text_content,
-                    thumbnail, created_at, file_size
+                    thumbnail, created_at, file_size, width, height, use_count, metadata, note, pinned, content_hash
              FROM clipboard_history
-             WHERE text_content LIKE ?1
-             ORDER BY created_at DESC
-             LIMIT 50",
+             WHERE id = ?1",
         )?;
 
-        let entries = stmt
-            .query_map(params![pattern], |row| {
-                let ct_str: String = row.get(1)?;
-                let content_type = ContentType::from_str(&ct_str).unwrap_or(ContentType::Text);
-                Ok(HistoryEntry {
-                    id: row.get(0)?,
-                    content_type,
-                    image_data: row.get(2)?,
-                    text_content: row.get(3)?,
-                    thumbnail: row.get(4)?,
-                    created_at: row.get(5)?,
-                    file_size: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
-                })
-            })?
+        let mut entries: Vec<HistoryEntry> = stmt
+            .query_map(params![id], entry_from_row)?
             .filter_map(|r| r.ok())
             .collect();
 
-        Ok(entries)
+        Ok(entries.pop())
     }
 
-    /// Delete a single entry.
-    #[allow(dead_code)]
-    pub fn delete_entry(&self, id: i64) -> Result<()> {
-        self.conn
-            .execute("DELETE FROM clipboard_history WHERE id = ?1", params![id])?;
+    /// Make an entry the newest one by setting its timestamp to now.
+    pub fn bump_entry(&self, id: i64) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        self.conn.execute(
+            "UPDATE clipboard_history SET created_at = ?1 WHERE id = ?2",
+            params![now, id],
+        )?;
         Ok(())
     }
 
-    /// Remove entries older than `days` days.
-    pub fn cleanup_old_entries(&self, days: i64) -> Result<usize> {
-        let cutoff = chrono::Utc::now().timestamp() - (days * 86400);
-        let count = self.conn.execute(
-            "DELETE FROM clipboard_history WHERE created_at < ?1",
-            params![cutoff],
+    /// Record that an entry was restored to the clipboard.
+    pub fn increment_use_count(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE clipboard_history SET use_count = use_count + 1 WHERE id = ?1",
+            params![id],
         )?;
-        if count > 0 {
-            log::info!("Cleaned up {} old clipboard entries", count);
-        }
-        Ok(count)
+        Ok(())
     }
 
-    /// Clear clipboard history. If content_type is Some, only clear that type.
-    pub fn clear_history(&self, content_type: Option<ContentType>) -> Result<usize> {
-        let count = match content_type {
-            Some(ct) => {
-                let ct_str = ct.to_str();
-                self.conn.execute("DELETE FROM clipboard_history WHERE content_type = ?1", params![ct_str])?
-            }
-            None => self.conn.execute("DELETE FROM clipboard_history", [])?,
-        };
-        Ok(count)
+    /// Pin or unpin an entry. Pinned entries are never removed by cleanup
+    /// (`retention_days`, `max_entries`, `max_db_bytes`), only explicitly.
+    pub fn set_pinned(&self, id: i64, pinned: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE clipboard_history SET pinned = ?1 WHERE id = ?2",
+            params![pinned, id],
+        )?;
+        Ok(())
     }
 
-    /// Enforce maximum entry count by deleting oldest entries.
-    pub fn enforce_max_entries(&self, max: usize) -> Result<()> {
+    /// Attach a note to an entry; `None` or a blank note removes it.
+    pub fn set_note(&self, id: i64, note: Option<&str>) -> Result<()> {
+        let note = note.map(str::trim).filter(|n| !n.is_empty());
         self.conn.execute(
-            "DELETE FROM clipboard_history WHERE id NOT IN (
-                SELECT id FROM clipboard_history ORDER BY created_at DESC LIMIT ?1
-            )",
-            params![max as i64],
+            "UPDATE clipboard_history SET note = ?1 WHERE id = ?2",
+            params![note, id],
         )?;
         Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_insert_and_get_text() {
-        let db = Database::new_in_memory().unwrap();
-        let id = db.insert_text("hello world").unwrap();
-        assert!(id > 0);
+    /// Id of the newest entry of the given type, if any.
+    pub fn latest_entry_id(&self, content_type: ContentType) -> Result<Option<i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id FROM clipboard_history
+             WHERE content_type = ?1
+             ORDER BY created_at DESC, id DESC
+             LIMIT 1",
+        )?;
+        let mut rows = stmt.query(params![content_type.to_str()])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get(0)?)),
+            None => Ok(None),
+        }
+    }
 
-        let entries = db.get_recent_entries_by_type(10, ContentType::Text).unwrap();
-        assert_eq!(entries.len(), 1);
-        assert_eq!(entries[0].text_content.as_deref(), Some("hello world"));
-        assert_eq!(entries[0].content_type, ContentType::Text);
+    /// Value saved under `key` by `set_setting`.
+    ///
+    /// Settings hold small runtime state kept across sessions, as opposed to
+    /// the hand-edited config file.
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let value = self
+            .conn
+            .query_row("SELECT value FROM app_settings WHERE key = ?1", params![key], |row| row.get(0))
+            .optional()?;
+        Ok(value)
     }
 
-    #[test]
-    fn test_insert_and_get_image() {
-        let db = Database::new_in_memory().unwrap();
-        let png = vec![0x89, 0x50, 0x4E, 0x47]; // Fake PNG header
-        let thumb = vec![1, 2, 3];
-        let id = db.insert_image(&png, &thumb).unwrap();
-        assert!(id > 0);
+    /// Save `value` under `key`, replacing any previous value.
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
 
-        let entry = db.get_entry(id).unwrap().unwrap();
-        assert_eq!(entry.content_type, ContentType::Image);
-        assert_eq!(entry.image_data.unwrap(), png);
-        assert_eq!(entry.thumbnail.unwrap(), thumb);
+    /// Hash of the last clipboard content of the given type seen by the
+    /// monitor, saved by `set_latest_hash`.
+    pub fn latest_hash(&self, content_type: ContentType) -> Result<Option<String>> {
+        self.get_setting(&latest_hash_key(content_type))
     }
 
-    #[test]
-    fn test_search_text() {
-        let db = Database::new_in_memory().unwrap();
-        db.insert_text("foo bar baz").unwrap();
-        db.insert_text("hello world").unwrap();
-        db.insert_text("foo qux").unwrap();
+    /// Save the hash of the clipboard content of the given type, so dedup
+    /// survives a restart with the same content still on the clipboard.
+    pub fn set_latest_hash(&self, content_type: ContentType, hash: &str) -> Result<()> {
+        self.set_setting(&latest_hash_key(content_type), hash)
+    }
 
-        let results = db.search_text("foo").unwrap();
-        assert_eq!(results.len(), 2);
+    /// Search text entries by their text or note, best match first. Words
+    /// match whole or as a prefix, through the full-text index; without it,
+    /// or for a query with no words to index, the query is matched as a
+    /// substring instead, newest first.
+    /// Compressed text is skipped (see `set_text_compression`): matching it
+    /// would mean decompressing every candidate row. Notes are never
+    /// compressed, so they always match.
+    pub fn search_text(&self, query: &str) -> Result<Vec<HistoryEntry>> {
+        match fts_query(query) {
+            Some(fts) if self.fts => self.search_text_fts(&fts),
+            _ => self.search_text_like(query),
+        }
+    }
+
+    /// Rank matches of an FTS5 query with BM25; ties stay newest first.
+    fn search_text_fts(&self, fts: &str) -> Result<Vec<HistoryEntry>> {
+        let sql = format!(
+            "SELECT {}
+             FROM clipboard_history
+             JOIN (SELECT rowid AS fts_id, bm25(clipboard_fts) AS score
+                   FROM clipboard_fts WHERE clipboard_fts MATCH ?1) ON id = fts_id
+             WHERE content_type = 'text'
+             ORDER BY score, created_at DESC, id DESC
+             LIMIT 50",
+            LIST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let entries = stmt
+            .query_map(params![fts], entry_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn search_text_like(&self, query: &str) -> Result<Vec<HistoryEntry>> {
+        let pattern = format!("%{}%", query);
+        let sql = format!(
+            "SELECT {}
+             FROM clipboard_history
+             WHERE content_type = 'text' AND (text_content LIKE ?1 OR note LIKE ?1)
+             ORDER BY created_at DESC
+             LIMIT 50",
+            LIST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let entries = stmt
+            .query_map(params![pattern], entry_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Fuzzy search over the text and notes of the newest `candidates` text
+    /// entries (see `fuzzy`), best match first; equally good matches stay
+    /// newest first.
+    pub fn fuzzy_search_text(&self, query: &str, candidates: usize) -> Result<Vec<HistoryEntry>> {
+        let recent = self.get_recent_metadata_by_type(candidates, ContentType::Text, SortOrder::Newest)?;
+        let mut scored: Vec<(u32, HistoryEntry)> = recent
+            .into_iter()
+            .filter_map(|entry| {
+                let score = [&entry.text_content, &entry.note]
+                    .into_iter()
+                    .filter_map(|field| fuzzy::score(query, field.as_deref()?))
+                    .max()?;
+                Some((score, entry))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        Ok(scored.into_iter().take(50).map(|(_, entry)| entry).collect())
+    }
+
+    /// The newest `limit` images captured with `method`, without their
+    /// image data (see `LIST_COLUMNS`). Captures stored before their format
+    /// was recorded have the method alone.
+    pub fn get_images_by_method(&self, method: CaptureMethod, limit: usize) -> Result<Vec<HistoryEntry>> {
+        let sql = format!(
+            "SELECT {}
+             FROM clipboard_history
+             WHERE content_type = 'image' AND (metadata = ?1 OR metadata LIKE ?1 || ';%')
+             ORDER BY created_at DESC
+             LIMIT ?2",
+            LIST_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let entries = stmt
+            .query_map(params![method.to_metadata(), limit as i64], entry_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(entries)
+    }
+
+    /// Up to `limit` image entries stored before dimensions were recorded.
+    pub fn images_missing_dimensions(&self, limit: usize) -> Result<Vec<(i64, Vec<u8>)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, content_data FROM clipboard_history
+             WHERE content_type = 'image' AND width IS NULL
+             LIMIT ?1",
+        )?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Record the pixel dimensions of an image entry.
+    pub fn set_dimensions(&self, id: i64, width: u32, height: u32) -> Result<()> {
+        self.conn.execute(
+            "UPDATE clipboard_history SET width = ?1, height = ?2 WHERE id = ?3",
+            params![width, height, id],
+        )?;
+        Ok(())
+    }
+
+    /// Image entries after `after_id` whose thumbnail is missing or empty
+    /// (thumbnailing failed when they were stored), as (id, full image) pairs.
+    pub fn images_missing_thumbnails(&self, after_id: i64, limit: usize) -> Result<Vec<(i64, Vec<u8>)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, content_data FROM clipboard_history
+             WHERE content_type = 'image' AND id > ?1
+               AND (thumbnail IS NULL OR length(thumbnail) = 0)
+             ORDER BY id
+             LIMIT ?2",
+        )?;
+        let rows = stmt
+            .query_map(params![after_id, limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(rows)
+    }
+
+    /// Replace an entry's thumbnail.
+    pub fn set_thumbnail(&self, id: i64, thumbnail: &[u8]) -> Result<()> {
+        self.conn.execute(
+            "UPDATE clipboard_history SET thumbnail = ?1 WHERE id = ?2",
+            params![thumbnail, id],
+        )?;
+        Ok(())
+    }
+
+    /// Delete a single entry.
+    pub fn delete_entry(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM clipboard_history WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Remove unpinned entries older than `days` days.
+    pub fn cleanup_old_entries(&self, days: i64) -> Result<usize> {
+        let cutoff = chrono::Utc::now().timestamp() - (days * 86400);
+        let count = self.conn.execute(
+            "DELETE FROM clipboard_history WHERE created_at < ?1 AND pinned = 0",
+            params![cutoff],
+        )?;
+        if count > 0 {
+            log::info!("Cleaned up {} old clipboard entries", count);
+        }
+        Ok(count)
+    }
+
+    /// Remove entries flagged sensitive that are at least `ttl_secs` old at
+    /// `now` (a Unix timestamp).
+    pub fn purge_expired_sensitive(&self, ttl_secs: u64, now: i64) -> Result<usize> {
+        let cutoff = now - ttl_secs as i64;
+        let count = self.conn.execute(
+            "DELETE FROM clipboard_history WHERE metadata = ?1 AND created_at <= ?2",
+            params![SENSITIVE_METADATA, cutoff],
+        )?;
+        if count > 0 {
+            log::info!("Removed {} expired sensitive entries", count);
+        }
+        Ok(count)
+    }
+
+    /// Clear clipboard history. If content_type is Some, only clear that type.
+    pub fn clear_history(&self, content_type: Option<ContentType>) -> Result<usize> {
+        let count = match content_type {
+            Some(ct) => {
+                let ct_str = ct.to_str();
+                self.conn.execute("DELETE FROM clipboard_history WHERE content_type = ?1", params![ct_str])?
+            }
+            None => self.conn.execute("DELETE FROM clipboard_history", [])?,
+        };
+        Ok(count)
+    }
+
+    /// Count entries, optionally only those of one type.
+    pub fn count(&self, content_type: Option<ContentType>) -> Result<usize> {
+        let count: i64 = match content_type {
+            Some(ct) => self.conn.query_row(
+                "SELECT COUNT(*) FROM clipboard_history WHERE content_type = ?1",
+                params![ct.to_str()],
+                |row| row.get(0),
+            )?,
+            None => self.conn.query_row("SELECT COUNT(*) FROM clipboard_history", [], |row| row.get(0))?,
+        };
+        Ok(count as usize)
+    }
+
+    /// Add a snippet. Names are unique; adding an existing name fails.
+    pub fn add_snippet(&self, name: &str, text: &str) -> Result<i64> {
+        let now = chrono::Utc::now().timestamp();
+        self.conn
+            .execute(
+                "INSERT INTO snippets (name, text_content, created_at) VALUES (?1, ?2, ?3)",
+                params![name, text, now],
+            )
+            .map_err(|e| match e.sqlite_error_code() {
+                Some(ErrorCode::ConstraintViolation) => DatabaseError::DuplicateSnippet(name.to_string()),
+                _ => e.into(),
+            })?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// All snippets, ordered by name.
+    pub fn get_snippets(&self) -> Result<Vec<Snippet>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, text_content, created_at FROM snippets ORDER BY name COLLATE NOCASE",
+        )?;
+        let snippets = stmt
+            .query_map([], |row| {
+                Ok(Snippet {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    text: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(snippets)
+    }
+
+    /// Delete a snippet.
+    pub fn delete_snippet(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM snippets WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Enforce maximum entry count by deleting oldest entries. Pinned entries
+    /// are kept and don't count towards `max`.
+    pub fn enforce_max_entries(&self, max: usize) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM clipboard_history WHERE pinned = 0 AND id NOT IN (
+                SELECT id FROM clipboard_history WHERE pinned = 0
+                ORDER BY created_at DESC, id DESC LIMIT ?1
+            )",
+            params![max as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Size of the database in bytes, including free pages not yet reclaimed
+    /// by a vacuum.
+    pub fn file_size(&self) -> Result<u64> {
+        let size: i64 = self.conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(size as u64)
+    }
+
+    /// Bytes of content held by history entries: text, images and thumbnails.
+    pub fn content_size(&self) -> Result<u64> {
+        let size: i64 = self.conn.query_row(
+            &format!("SELECT IFNULL(SUM({}), 0) FROM clipboard_history", ENTRY_BYTES),
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(size as u64)
+    }
+
+    /// Keep the database under `max_bytes` by deleting the oldest unpinned
+    /// entries, of either type, until their content fits. If the file is still too large
+    /// afterwards, it is vacuumed. Returns how many entries were deleted.
+    pub fn enforce_max_bytes(&self, max_bytes: u64) -> Result<usize> {
+        if self.file_size()? <= max_bytes {
+            return Ok(0);
+        }
+        let mut excess = self.content_size()?.saturating_sub(max_bytes);
+        let mut doomed = Vec::new();
+        if excess > 0 {
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT id, {} FROM clipboard_history WHERE pinned = 0 ORDER BY created_at ASC, id ASC",
+                ENTRY_BYTES
+            ))?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let id: i64 = row.get(0)?;
+                let size: i64 = row.get(1)?;
+                doomed.push(id);
+                excess = excess.saturating_sub(size as u64);
+                if excess == 0 {
+                    break;
+                }
+            }
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut delete = tx.prepare("DELETE FROM clipboard_history WHERE id = ?1")?;
+            for id in &doomed {
+                delete.execute(params![id])?;
+            }
+        }
+        tx.commit()?;
+        if !doomed.is_empty() {
+            log::warn!("Database over {} bytes; removed the {} oldest entries", max_bytes, doomed.len());
+        }
+
+        if self.file_size()? > max_bytes {
+            log::info!("Database file still over {} bytes; vacuuming", max_bytes);
+            self.conn.execute_batch("VACUUM")?;
+        }
+        Ok(doomed.len())
+    }
+}
+
+/// Open the history as `storage.mode` asks: the database file, checked and
+/// repaired if need be, or a ring buffer in memory that never touches disk.
+pub fn open_history(config: &Config) -> Result<(Database, Option<RepairReport>)> {
+    let (mut db, repair) = match config.storage_mode() {
+        StorageMode::Disk => Database::open_with_repair(&config.resolved_db_path())?,
+        StorageMode::Memory => {
+            let mut db = Database::new_in_memory()?;
+            db.set_ring_capacity(Some(config.history.max_entries))?;
+            (db, None)
+        }
+    };
+    db.set_text_compression(config.storage.compress_text.then_some(config.storage.compress_text_min_bytes));
+    Ok((db, repair))
+}
+
+/// `clipboard::content_hash` of everything `reader` yields, read in chunks
+/// so an image file never has to be loaded whole.
+fn file_hash(mut reader: impl Read) -> std::io::Result<String> {
+    let mut hasher = ContentHasher::default();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// Whether `a` and `b`, both `len` bytes long, yield the same bytes. Read
+/// in chunks so an image never has to be held in memory whole.
+fn same_bytes(mut a: impl Read, mut b: impl Read, len: u64) -> std::io::Result<bool> {
+    let (mut buf_a, mut buf_b) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    let mut left = len;
+    while left > 0 {
+        let n = left.min(buf_a.len() as u64) as usize;
+        a.read_exact(&mut buf_a[..n])?;
+        b.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        left -= n as u64;
+    }
+    Ok(true)
+}
+
+/// Setting under which `set_latest_hash` saves a type's hash.
+fn latest_hash_key(content_type: ContentType) -> String {
+    format!("last_{}_hash", content_type.to_str())
+}
+
+/// Map a row selected with the full entry column list (see `get_entry`),
+/// decompressing compressed text.
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<HistoryEntry> {
+    let ct_str: String = row.get(1)?;
+    let content_type = ContentType::from_str(&ct_str).unwrap_or(ContentType::Text);
+    let metadata: Option<String> = row.get(10)?;
+    if content_type == ContentType::Text && metadata.as_deref() == Some(DEFLATE_METADATA) {
+        let compressed: Vec<u8> = row.get(2)?;
+        let mut text = String::new();
+        ZlibDecoder::new(compressed.as_slice())
+            .read_to_string(&mut text)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Blob, Box::new(e)))?;
+        return Ok(HistoryEntry {
+            id: row.get(0)?,
+            content_type,
+            image_data: None,
+            text_content: Some(text),
+            thumbnail: row.get(4)?,
+            created_at: row.get(5)?,
+            file_size: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
+            width: row.get(7)?,
+            height: row.get(8)?,
+            use_count: row.get(9)?,
+            source_mime: None,
+            capture_method: None,
+            note: row.get(11)?,
+            pinned: row.get(12)?,
+            sensitive: false,
+            content_hash: row.get(13)?,
+        });
+    }
+    let sensitive = content_type == ContentType::Text && metadata.as_deref() == Some(SENSITIVE_METADATA);
+    // Image rows keep the MIME type they were converted from in `metadata`;
+    // captures keep how they were taken and the format they were saved in
+    let image_metadata = metadata.filter(|_| content_type == ContentType::Image);
+    let capture_method = image_metadata.as_deref().and_then(CaptureMethod::from_metadata);
+    let source_mime = image_metadata
+        .as_deref()
+        .and_then(|m| m.rsplit(';').next())
+        .filter(|mime| mime.contains('/'))
+        .map(String::from);
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        content_type,
+        image_data: row.get(2)?,
+        text_content: row.get(3)?,
+        thumbnail: row.get(4)?,
+        created_at: row.get(5)?,
+        file_size: row.get::<_, Option<i64>>(6)?.unwrap_or(0),
+        width: row.get(7)?,
+        height: row.get(8)?,
+        use_count: row.get(9)?,
+        source_mime,
+        capture_method,
+        note: row.get(11)?,
+        pinned: row.get(12)?,
+        sensitive,
+        content_hash: row.get(13)?,
+    })
+}
+
+/// An FTS5 query for rows containing every word of `query`, whole or as a
+/// prefix; whole words score higher. `None` when a word has no letters or
+/// digits, which the index doesn't keep.
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| {
+            if !word.chars().any(char::is_alphanumeric) {
+                return None;
+            }
+            let phrase = format!("\"{}\"", word.replace('"', "\"\""));
+            Some(format!("({0} OR {0}*)", phrase))
+        })
+        .collect::<Option<_>>()?;
+    (!terms.is_empty()).then(|| terms.join(" AND "))
+}
+
+/// Fill in dimensions for image entries saved before they were recorded.
+/// Runs in small batches so the database lock is never held for long; rows
+/// that cannot be decoded are marked 0×0 so they are not retried.
+pub fn backfill_image_dimensions(db: &Mutex<Database>) -> usize {
+    let mut updated = 0;
+    loop {
+        let batch = match db.lock() {
+            Ok(db) => db.images_missing_dimensions(20).unwrap_or_default(),
+            Err(_) => return updated,
+        };
+        if batch.is_empty() {
+            return updated;
+        }
+
+        let dims: Vec<(i64, (u32, u32))> = batch
+            .into_iter()
+            .map(|(id, bytes)| (id, screenshot::image_dimensions(&bytes).unwrap_or((0, 0))))
+            .collect();
+
+        let Ok(db) = db.lock() else {
+            return updated;
+        };
+        for (id, (width, height)) in dims {
+            if let Err(e) = db.set_dimensions(id, width, height) {
+                log::warn!("Failed to record dimensions for entry {}: {}", id, e);
+                return updated;
+            }
+            updated += 1;
+        }
+    }
+}
+
+/// Re-derive an image entry's thumbnail from its full image and store it.
+/// The database lock is not held while the thumbnail is computed.
+pub fn regenerate_thumbnail(db: &Mutex<Database>, id: i64) -> Result<Vec<u8>> {
+    let image = {
+        let db = db.lock().map_err(|_| DatabaseError::Poisoned)?;
+        db.get_entry(id)?
+            .and_then(|entry| entry.image_data)
+            .ok_or(DatabaseError::NoImage(id))?
+    };
+    let thumbnail = screenshot::create_thumbnail(&image, screenshot::thumbnail_px())?;
+    db.lock().map_err(|_| DatabaseError::Poisoned)?.set_thumbnail(id, &thumbnail)?;
+    Ok(thumbnail)
+}
+
+/// Create thumbnails for image entries stored without one. Works in small
+/// batches like [`backfill_image_dimensions`]; images that still can't be
+/// thumbnailed are skipped until the next run.
+pub fn backfill_thumbnails(db: &Mutex<Database>) -> usize {
+    let mut updated = 0;
+    let mut after_id = 0;
+    loop {
+        let batch = match db.lock() {
+            Ok(db) => db.images_missing_thumbnails(after_id, 10).unwrap_or_default(),
+            Err(_) => return updated,
+        };
+        let Some(&(last_id, _)) = batch.last() else {
+            return updated;
+        };
+        after_id = last_id;
+
+        let thumbs: Vec<(i64, Vec<u8>)> = batch
+            .into_iter()
+            .filter_map(|(id, bytes)| match screenshot::create_thumbnail(&bytes, screenshot::thumbnail_px()) {
+                Ok(thumb) => Some((id, thumb)),
+                Err(e) => {
+                    log::warn!("Cannot thumbnail entry {}: {:#}", id, e);
+                    None
+                }
+            })
+            .collect();
+
+        let Ok(db) = db.lock() else {
+            return updated;
+        };
+        for (id, thumb) in thumbs {
+            if let Err(e) = db.set_thumbnail(id, &thumb) {
+                log::warn!("Failed to store thumbnail for entry {}: {}", id, e);
+                return updated;
+            }
+            updated += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_get_text() {
+        let db = Database::new_in_memory().unwrap();
+        let id = db.insert_text("hello world").unwrap();
+        assert!(id > 0);
+
+        let entries = db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::Newest).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].text_content.as_deref(), Some("hello world"));
+        assert_eq!(entries[0].content_type, ContentType::Text);
+    }
+
+    #[test]
+    fn test_metadata_queries_skip_image_data() {
+        let db = Database::new_in_memory().unwrap();
+        let png = vec![0x89, 0x50, 0x4E, 0x47];
+        let id = db.insert_image(&png, &[1, 2, 3], 640, 480).unwrap();
+
+        let listed = db.get_recent_metadata_by_type(10, ContentType::Image, SortOrder::Newest).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].image_data.is_none());
+        assert_eq!(listed[0].thumbnail.as_deref(), Some(&[1, 2, 3][..]));
+        assert_eq!((listed[0].width, listed[0].height, listed[0].file_size), (Some(640), Some(480), 4));
+        // The full entry still has it for restoring
+        assert_eq!(db.get_entry(id).unwrap().unwrap().image_data, Some(png));
+    }
+
+    #[test]
+    fn test_get_recent_entries_mixes_types() {
+        let db = Database::new_in_memory().unwrap();
+        let text = db.insert_text("hello").unwrap();
+        let image = db.insert_image(&[0x89, 0x50, 0x4E, 0x47], &[1, 2, 3], 4, 4).unwrap();
+
+        let ids: Vec<i64> = db.get_recent_entries(10).unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, [image, text]);
+        assert_eq!(db.get_recent_entries(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_insert_and_get_image() {
+        let db = Database::new_in_memory().unwrap();
+        let png = vec![0x89, 0x50, 0x4E, 0x47]; // Fake PNG header
+        let thumb = vec![1, 2, 3];
+        let id = db.insert_image(&png, &thumb, 640, 480).unwrap();
+        assert!(id > 0);
+
+        let entry = db.get_entry(id).unwrap().unwrap();
+        assert_eq!(entry.content_type, ContentType::Image);
+        assert_eq!(entry.image_data.unwrap(), png);
+        assert_eq!(entry.thumbnail.unwrap(), thumb);
+        assert_eq!((entry.width, entry.height), (Some(640), Some(480)));
+    }
+
+    #[test]
+    fn test_insert_image_file() {
+        let db = Database::new_in_memory().unwrap();
+        let png = vec![0x89, 0x50, 0x4E, 0x47, 1, 2, 3, 4, 5];
+        let path = std::env::temp_dir().join(format!("clipsnap_db_test_{}.png", std::process::id()));
+        std::fs::write(&path, &png).unwrap();
+        let id = db.insert_image_file(&path, &[9], 1, 1, CaptureMethod::Region, "image/png").unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let entry = db.get_entry(id).unwrap().unwrap();
+        assert_eq!(entry.image_data.unwrap(), png);
+        assert_eq!(entry.file_size, 9);
+        assert_eq!(entry.capture_method, Some(CaptureMethod::Region));
+        assert_eq!(entry.source_mime.as_deref(), Some("image/png"));
+    }
+
+    #[test]
+    fn test_capture_method_is_stored_and_filterable() {
+        let db = Database::new_in_memory().unwrap();
+        let window = db.insert_captured_image(&[1], &[], 1, 1, CaptureMethod::Window, "image/webp").unwrap();
+        db.insert_captured_image(&[2], &[], 1, 1, CaptureMethod::Fullscreen, "image/png").unwrap();
+        let copied = db.insert_image(&[3], &[], 1, 1).unwrap();
+        let converted = db.insert_converted_image(&[4], &[], 1, 1, "image/bmp").unwrap();
+        // Captured before the format was recorded
+        let older = db.insert_image_row(&[5], &[], 1, 1, Some("method:window")).unwrap();
+
+        let entry = db.get_entry(window).unwrap().unwrap();
+        assert_eq!(entry.capture_method, Some(CaptureMethod::Window));
+        assert_eq!(entry.source_mime.as_deref(), Some("image/webp"));
+        let entry = db.get_entry(older).unwrap().unwrap();
+        assert_eq!((entry.capture_method, entry.source_mime), (Some(CaptureMethod::Window), None));
+        assert_eq!(db.get_entry(copied).unwrap().unwrap().capture_method, None);
+        let entry = db.get_entry(converted).unwrap().unwrap();
+        assert_eq!((entry.capture_method, entry.source_mime.as_deref()), (None, Some("image/bmp")));
+
+        let query = CaptureMethod::from_metadata("method:window").unwrap();
+        let mut ids: Vec<i64> = db.get_images_by_method(query, 10).unwrap().iter().map(|e| e.id).collect();
+        ids.sort();
+        assert_eq!(ids, [window, older]);
+        assert!(db.get_images_by_method(CaptureMethod::Region, 10).unwrap().is_empty());
+    }
+
+    fn temp_db_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("clipsnap_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("history.db")
+    }
+
+    #[test]
+    fn test_open_with_repair_garbage_file() {
+        let path = temp_db_path("garbage");
+        std::fs::write(&path, vec![0xAB; 8192]).unwrap();
+
+        let (db, report) = Database::open_with_repair(&path).unwrap();
+        let report = report.expect("garbage file should be repaired");
+        assert!(report.backup_path.exists());
+        assert_eq!(report.recovered_rows, 0);
+        assert!(db.insert_text("fresh start").unwrap() > 0);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_locked_database_is_busy() {
+        let path = temp_db_path("busy");
+        let holder = Database::new(&path).unwrap();
+        let waiter = Database::new(&path).unwrap();
+        waiter.conn.busy_timeout(std::time::Duration::ZERO).unwrap();
+
+        holder.conn.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        assert!(matches!(waiter.insert_text("blocked"), Err(DatabaseError::Busy(_))));
+        holder.conn.execute_batch("COMMIT").unwrap();
+        assert!(waiter.insert_text("unblocked").unwrap() > 0);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_with_repair_truncated_file() {
+        let path = temp_db_path("truncated");
+        {
+            let db = Database::new(&path).unwrap();
+            for i in 0..200 {
+                db.insert_text(&format!("entry {} {}", i, "x".repeat(500))).unwrap();
+            }
+        }
+        let len = std::fs::metadata(&path).unwrap().len();
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len / 2).unwrap();
+
+        let (db, report) = Database::open_with_repair(&path).unwrap();
+        let report = report.expect("truncated file should be repaired");
+        assert!(report.recovered_rows < 200);
+        assert!(db.insert_text("still usable").unwrap() > 0);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_with_repair_leaves_unopenable_path_alone() {
+        let path = temp_db_path("unopenable");
+        std::fs::create_dir(&path).unwrap();
+
+        let err = Database::open_with_repair(&path).err().expect("a directory is not a database file");
+        assert!(!err.is_corruption());
+        assert!(path.is_dir());
+        let siblings = std::fs::read_dir(path.parent().unwrap()).unwrap().count();
+        assert_eq!(siblings, 1, "nothing should be moved aside");
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_integrity_problems() {
+        let path = temp_db_path("integrity");
+        Database::new(&path).unwrap().insert_text("fine").unwrap();
+        assert_eq!(Database::integrity_problems(&path).unwrap(), Vec::<String>::new());
+
+        std::fs::write(&path, b"not a database at all").unwrap();
+        assert!(Database::integrity_problems(&path).is_err());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_with_repair_healthy_file() {
+        let path = temp_db_path("healthy");
+        Database::new(&path).unwrap().insert_text("keep me").unwrap();
+
+        let (db, report) = Database::open_with_repair(&path).unwrap();
+        assert!(report.is_none());
+        assert_eq!(db.search_text("keep").unwrap().len(), 1);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_dimension_columns_migrated_and_backfilled() {
+        let path = temp_db_path("dimensions");
+        {
+            // A database from before width/height existed
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE clipboard_history (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    content_type TEXT NOT NULL,
+                    content_data BLOB,
+                    text_content TEXT,
+                    thumbnail BLOB,
+                    created_at INTEGER NOT NULL,
+                    file_size INTEGER,
+                    metadata TEXT
+                );",
+            )
+            .unwrap();
+            let png = screenshot::encode_png(&[0u8; 3 * 2 * 4], 3, 2).unwrap();
+            conn.execute(
+                "INSERT INTO clipboard_history (content_type, content_data, created_at) VALUES ('image', ?1, 0)",
+                params![png],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO clipboard_history (content_type, content_data, created_at) VALUES ('image', x'00', 0)",
+                [],
+            )
+            .unwrap();
+        }
+
+        let db = Mutex::new(Database::new(&path).unwrap());
+        assert_eq!(backfill_image_dimensions(&db), 2);
+        let db = db.into_inner().unwrap();
+        let first = db.get_entry(1).unwrap().unwrap();
+        assert_eq!((first.width, first.height), (Some(3), Some(2)));
+        let broken = db.get_entry(2).unwrap().unwrap();
+        assert_eq!((broken.width, broken.height), (Some(0), Some(0)));
+        assert!(db.images_missing_dimensions(10).unwrap().is_empty());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_missing_thumbnails_backfilled() {
+        let db = Database::new_in_memory().unwrap();
+        let png = screenshot::encode_png(&[255u8; 4 * 4 * 4], 4, 4).unwrap();
+        let good = db.insert_image(&png, &[], 4, 4).unwrap();
+        let broken = db.insert_image(&[0u8], &[], 0, 0).unwrap();
+
+        let db = Mutex::new(db);
+        assert_eq!(backfill_thumbnails(&db), 1);
+        let thumb = db.lock().unwrap().get_entry(good).unwrap().unwrap().thumbnail.unwrap();
+        assert!(screenshot::image_dimensions(&thumb).is_ok());
+
+        // The undecodable image is left for later rather than retried forever
+        assert_eq!(db.lock().unwrap().images_missing_thumbnails(0, 10).unwrap().len(), 1);
+        assert!(matches!(regenerate_thumbnail(&db, broken), Err(DatabaseError::Image(_))));
+        assert!(regenerate_thumbnail(&db, good).is_ok());
+    }
+
+    #[test]
+    fn test_bump_entry_moves_to_top() {
+        let db = Database::new_in_memory().unwrap();
+        let old = db.insert_text("old").unwrap();
+        let new = db.insert_text("new").unwrap();
+        for (id, age) in [(old, 60), (new, 30)] {
+            db.conn
+                .execute("UPDATE clipboard_history SET created_at = created_at - ?1 WHERE id = ?2", params![age, id])
+                .unwrap();
+        }
+        let order = |db: &Database| db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::Newest).unwrap();
+        assert_eq!(texts(&order(&db)), ["new", "old"]);
+
+        db.bump_entry(old).unwrap();
+        assert_eq!(texts(&order(&db)), ["old", "new"]);
+    }
+
+    #[test]
+    fn test_use_count_and_most_used_order() {
+        let db = Database::new_in_memory().unwrap();
+        let rare = db.insert_text("rarely used").unwrap();
+        let favourite = db.insert_text("go-to phrase").unwrap();
+        db.insert_text("newest, never used").unwrap();
+
+        db.increment_use_count(favourite).unwrap();
+        db.increment_use_count(favourite).unwrap();
+        db.increment_use_count(rare).unwrap();
+        assert_eq!(db.get_entry(favourite).unwrap().unwrap().use_count, 2);
+
+        let entries = db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::MostUsed).unwrap();
+        let texts: Vec<_> = entries.iter().map(|e| e.text_content.as_deref().unwrap()).collect();
+        assert_eq!(texts, ["go-to phrase", "rarely used", "newest, never used"]);
+    }
+
+    #[test]
+    fn test_insert_batch_keeps_timestamps() {
+        let db = Database::new_in_memory().unwrap();
+        let inserted = db
+            .insert_batch(&[("older".to_string(), 1_000), ("newer".to_string(), 2_000)])
+            .unwrap();
+        assert_eq!(inserted, 2);
+
+        let entries = db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::Newest).unwrap();
+        let rows: Vec<_> = entries
+            .iter()
+            .map(|e| (e.text_content.as_deref().unwrap(), e.created_at))
+            .collect();
+        assert_eq!(rows, [("newer", 2_000), ("older", 1_000)]);
+    }
+
+    #[test]
+    fn test_duplicate_text_bumps_existing_row() {
+        let db = Database::new_in_memory().unwrap();
+        let first = db.insert_text("A").unwrap();
+        db.insert_text("B").unwrap();
+        // Pretend A was copied a while ago
+        db.conn
+            .execute("UPDATE clipboard_history SET created_at = 1000 WHERE id = ?1", params![first])
+            .unwrap();
+
+        let again = db.insert_text("A").unwrap();
+        assert_eq!(again, first);
+        let rows: Vec<_> = db
+            .get_recent_entries_by_type(10, ContentType::Text, SortOrder::Newest)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.id, e.created_at))
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows.iter().filter(|(id, _)| *id == first).count(), 1);
+        assert!(rows.iter().all(|&(_, created_at)| created_at > 1000));
+
+        // An older copy, e.g. from an import, doesn't move it back
+        assert_eq!(db.insert_batch(&[("A".to_string(), 500), ("C".to_string(), 500)]).unwrap(), 1);
+        assert!(db.get_entry(first).unwrap().unwrap().created_at > 1000);
+    }
+
+    #[test]
+    fn test_hash_collision_stores_both_entries() {
+        let db = Database::new_in_memory().unwrap();
+        let a = db.insert_text("A").unwrap();
+        // Make A's stored hash that of B, as a real collision would
+        db.conn
+            .execute(
+                "UPDATE clipboard_history SET content_hash = ?2 WHERE id = ?1",
+                params![a, clipboard::content_hash(b"B")],
+            )
+            .unwrap();
+
+        let b = db.insert_text("B").unwrap();
+        assert_ne!(b, a);
+        assert_eq!(db.get_entry(b).unwrap().unwrap().text_content.as_deref(), Some("B"));
+        assert_eq!(db.get_entry(b).unwrap().unwrap().content_hash, None);
+        assert_eq!(db.get_entry(a).unwrap().unwrap().text_content.as_deref(), Some("A"));
+
+        // Same hash and size but another type isn't a duplicate either
+        let png = screenshot::encode_png(&[9u8; 16], 2, 2).unwrap();
+        let image = db.insert_image(&png, &[], 2, 2).unwrap();
+        let text: String = png.iter().map(|&b| (b'a' + b % 26) as char).collect();
+        db.conn
+            .execute(
+                "UPDATE clipboard_history SET content_hash = ?2 WHERE id = ?1",
+                params![image, clipboard::content_hash(text.as_bytes())],
+            )
+            .unwrap();
+        assert_ne!(db.insert_text(&text).unwrap(), image);
+        assert_eq!(db.count(None).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_duplicate_image_file_bumps_existing_row() {
+        let db = Database::new_in_memory().unwrap();
+        let png = screenshot::encode_png(&[9u8; 16], 2, 2).unwrap();
+        let id = db.insert_image(&png, &[], 2, 2).unwrap();
+
+        let path = std::env::temp_dir().join(format!("clipsnap_dup_{}.png", std::process::id()));
+        std::fs::write(&path, &png).unwrap();
+        let again = db.insert_image_file(&path, &[], 2, 2, CaptureMethod::Region, "image/png");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(again.unwrap(), id);
+        assert_eq!(db.count(Some(ContentType::Image)).unwrap(), 1);
+        assert_eq!(file_hash(png.as_slice()).unwrap(), clipboard::content_hash(&png));
+    }
+
+    #[test]
+    fn test_compressed_text_round_trip() {
+        let mut db = Database::new_in_memory().unwrap();
+        db.set_text_compression(Some(64));
+        let small = "short";
+        let large = "{\"log\": \"line\"}\n".repeat(200);
+        let small_id = db.insert_text(small).unwrap();
+        let large_id = db.insert_text(&large).unwrap();
+
+        let raw = |id: i64| -> (String, Option<String>) {
+            db.conn
+                .query_row(
+                    "SELECT text_content, metadata FROM clipboard_history WHERE id = ?1",
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap()
+        };
+        assert_eq!(raw(small_id), (small.to_string(), None));
+        assert_eq!(raw(large_id), (String::new(), Some(DEFLATE_METADATA.to_string())));
+
+        let entry = db.get_entry(large_id).unwrap().unwrap();
+        assert_eq!(entry.text_content.as_deref(), Some(large.as_str()));
+        assert_eq!(entry.file_size, large.len() as i64);
+        assert!(entry.image_data.is_none());
+        let listed = db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::Newest).unwrap();
+        assert!(listed.iter().any(|e| e.text_content.as_deref() == Some(large.as_str())));
+        let listed = db.get_recent_metadata_by_type(10, ContentType::Text, SortOrder::Newest).unwrap();
+        assert!(listed.iter().any(|e| e.text_content.as_deref() == Some(large.as_str())));
+    }
+
+    #[test]
+    fn test_sensitive_entries_expire() {
+        let mut db = Database::new_in_memory().unwrap();
+        // Secrets are flagged even where compression would apply
+        db.set_text_compression(Some(4));
+        let secret = db.insert_text("Tr0ub4dor&3x").unwrap();
+        let plain = db.insert_text("just some notes").unwrap();
+        db.conn
+            .execute("UPDATE clipboard_history SET created_at = 1000", [])
+            .unwrap();
+        assert!(db.get_entry(secret).unwrap().unwrap().sensitive);
+        assert!(!db.get_entry(plain).unwrap().unwrap().sensitive);
+
+        assert_eq!(db.purge_expired_sensitive(60, 1059).unwrap(), 0);
+        assert!(db.get_entry(secret).unwrap().is_some());
+        assert_eq!(db.purge_expired_sensitive(60, 1060).unwrap(), 1);
+        assert!(db.get_entry(secret).unwrap().is_none());
+        assert!(db.get_entry(plain).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_snippets_survive_cleanup() {
+        let db = Database::new_in_memory().unwrap();
+        let sig = db.add_snippet("signature", "Regards,\nSam").unwrap();
+        db.add_snippet("Address", "1 Main St").unwrap();
+        assert!(matches!(
+            db.add_snippet("signature", "other"),
+            Err(DatabaseError::DuplicateSnippet(name)) if name == "signature"
+        ));
+
+        db.insert_text("history").unwrap();
+        db.clear_history(None).unwrap();
+        db.cleanup_old_entries(-1).unwrap();
+        db.enforce_max_entries(0).unwrap();
+
+        let snippets = db.get_snippets().unwrap();
+        let names: Vec<&str> = snippets.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Address", "signature"]);
+        assert_eq!(snippets[1].text, "Regards,\nSam");
+
+        db.delete_snippet(sig).unwrap();
+        assert_eq!(db.get_snippets().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_search_text() {
+        let db = Database::new_in_memory().unwrap();
+        db.insert_text("foo bar baz").unwrap();
+        db.insert_text("hello world").unwrap();
+        db.insert_text("foo qux").unwrap();
+
+        let results = db.search_text("foo").unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_search_text_ranks_whole_words_first() {
+        let db = Database::new_in_memory().unwrap();
+        assert!(db.fts);
+        let rows = ["a deploy script", "deployment notes", "unrelated", "deploy deploy"];
+        db.insert_batch(&rows.iter().zip(1..).map(|(t, ts)| (t.to_string(), ts)).collect::<Vec<_>>()).unwrap();
+
+        let found = db.search_text("deploy").unwrap();
+        let texts: Vec<&str> = found.iter().filter_map(|e| e.text_content.as_deref()).collect();
+        assert_eq!(texts, ["deploy deploy", "a deploy script", "deployment notes"]);
+        assert_eq!(db.search_text("DEPLOY script").unwrap().len(), 1);
+
+        // Kept in step with deletes and edits
+        let id = found[1].id;
+        db.delete_entry(found[0].id).unwrap();
+        db.set_note(id, Some("\"quoted\" runbook")).unwrap();
+        assert_eq!(db.search_text("deploy").unwrap().len(), 2);
+        assert_eq!(db.search_text("\"runbook").unwrap()[0].id, id);
+
+        // Punctuation isn't indexed; it is matched as a substring
+        db.insert_text("https://example.com").unwrap();
+        assert_eq!(db.search_text("://").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_fts_query() {
+        assert_eq!(fts_query("foo").as_deref(), Some("(\"foo\" OR \"foo\"*)"));
+        assert_eq!(
+            fts_query(" a\"b  c ").as_deref(),
+            Some("(\"a\"\"b\" OR \"a\"\"b\"*) AND (\"c\" OR \"c\"*)")
+        );
+        assert_eq!(fts_query("   "), None);
+        assert_eq!(fts_query("foo ->"), None);
+    }
+
+    #[test]
+    fn test_pinned_entries_survive_cleanup() {
+        let db = Database::new_in_memory().unwrap();
+        let old = chrono::Utc::now().timestamp() - 90 * 86400;
+        db.insert_batch(&[("old pinned".to_string(), old), ("old".to_string(), old)]).unwrap();
+        let ids: Vec<i64> = db
+            .get_recent_entries_by_type(10, ContentType::Text, SortOrder::Oldest)
+            .unwrap()
+            .iter()
+            .map(|e| e.id)
+            .collect();
+        let (pinned, unpinned) = (ids[0], ids[1]);
+        db.set_pinned(pinned, true).unwrap();
+        let image = db.insert_captured_image(&[0; 64], &[], 4, 4, CaptureMethod::Region, "image/png").unwrap();
+        db.set_pinned(image, true).unwrap();
+        assert!(db.get_entry(image).unwrap().unwrap().pinned);
+        assert!(!db.get_entry(unpinned).unwrap().unwrap().pinned);
+
+        assert_eq!(db.cleanup_old_entries(30).unwrap(), 1);
+        assert!(db.get_entry(pinned).unwrap().is_some());
+        db.insert_text("new").unwrap();
+        db.enforce_max_entries(0).unwrap();
+        db.enforce_max_bytes(0).unwrap();
+        assert_eq!(db.count(None).unwrap(), 2);
+
+        // Unpinned, it goes like any other entry
+        db.set_pinned(pinned, false).unwrap();
+        db.enforce_max_entries(0).unwrap();
+        assert!(db.get_entry(pinned).unwrap().is_none());
+        assert!(db.get_entry(image).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_notes() {
+        let db = Database::new_in_memory().unwrap();
+        let id = db.insert_text("ssh -L 8080:localhost:80 gateway").unwrap();
+        db.insert_text("unrelated").unwrap();
+        assert_eq!(db.get_entry(id).unwrap().unwrap().note, None);
+
+        db.set_note(id, Some("  staging tunnel ")).unwrap();
+        assert_eq!(db.get_entry(id).unwrap().unwrap().note.as_deref(), Some("staging tunnel"));
+
+        // Found by the note as well as by the text, once
+        let found = db.search_text("staging").unwrap();
+        assert_eq!(found.iter().map(|e| e.id).collect::<Vec<_>>(), [id]);
+        assert_eq!(db.search_text("gateway").unwrap().len(), 1);
+        let fuzzy = db.fuzzy_search_text("stagng", 100).unwrap();
+        assert_eq!(fuzzy.iter().map(|e| e.id).collect::<Vec<_>>(), [id]);
+
+        // A blank note clears it, as does None
+        db.set_note(id, Some("  ")).unwrap();
+        assert_eq!(db.get_entry(id).unwrap().unwrap().note, None);
+        assert!(db.search_text("staging").unwrap().is_empty());
+        db.set_note(id, Some("again")).unwrap();
+        db.set_note(id, None).unwrap();
+        assert_eq!(db.get_entry(id).unwrap().unwrap().note, None);
+    }
+
+    #[test]
+    fn test_fuzzy_search_text_ranks_by_score() {
+        let db = Database::new_in_memory().unwrap();
+        let rows = ["call the function", "a funtcion with a typo", "unrelated", "function pointer"];
+        db.insert_batch(&rows.iter().zip(1..).map(|(t, ts)| (t.to_string(), ts)).collect::<Vec<_>>()).unwrap();
+
+        let found = db.fuzzy_search_text("functoin", 100).unwrap();
+        let texts: Vec<&str> = found.iter().filter_map(|e| e.text_content.as_deref()).collect();
+        // Equal scores keep newest first; the bigger typo ranks last
+        assert_eq!(texts, ["function pointer", "call the function", "a funtcion with a typo"]);
+
+        // Only the newest candidates are scanned
+        assert!(db.fuzzy_search_text("call", 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let db = Database::new_in_memory().unwrap();
+        assert_eq!(db.get_setting("view_mode").unwrap(), None);
+        db.set_setting("view_mode", "grid").unwrap();
+        assert_eq!(db.get_setting("view_mode").unwrap().as_deref(), Some("grid"));
+        db.set_setting("view_mode", "list").unwrap();
+        assert_eq!(db.get_setting("view_mode").unwrap().as_deref(), Some("list"));
+        assert_eq!(db.get_setting("other").unwrap(), None);
+    }
+
+    #[test]
+    fn test_latest_hash_per_type() {
+        let db = Database::new_in_memory().unwrap();
+        assert_eq!(db.latest_hash(ContentType::Text).unwrap(), None);
+        db.set_latest_hash(ContentType::Text, "0000000000000001").unwrap();
+        db.set_latest_hash(ContentType::Text, "ffffffffffffffff").unwrap();
+        db.set_latest_hash(ContentType::Image, "0000000000000007").unwrap();
+        assert_eq!(db.latest_hash(ContentType::Text).unwrap().as_deref(), Some("ffffffffffffffff"));
+        assert_eq!(db.latest_hash(ContentType::Image).unwrap().as_deref(), Some("0000000000000007"));
+        // Not history: clearing it keeps the hashes
+        db.clear_history(None).unwrap();
+        assert_eq!(db.latest_hash(ContentType::Image).unwrap().as_deref(), Some("0000000000000007"));
+    }
+
+    #[test]
+    fn test_text_entries_after() {
+        let db = Database::new_in_memory().unwrap();
+        assert_eq!(db.max_entry_id().unwrap(), 0);
+        let first = db.insert_text("one").unwrap();
+        db.insert_image(&[1, 2, 3], &[], 1, 1).unwrap();
+        db.insert_text("two").unwrap();
+        db.insert_text("three").unwrap();
+
+        let texts: Vec<_> = db
+            .text_entries_after(first, 10)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.text_content.unwrap())
+            .collect();
+        assert_eq!(texts, ["two", "three"]);
+        assert_eq!(db.text_entries_after(first, 1).unwrap().len(), 1);
+        assert_eq!(db.max_entry_id().unwrap(), first + 3);
     }
 
     #[test]
@@ -288,6 +2049,18 @@ mod tests {
         assert!(entry.is_none());
     }
 
+    #[test]
+    fn test_count_by_type() {
+        let db = Database::new_in_memory().unwrap();
+        db.insert_text("one").unwrap();
+        db.insert_text("two").unwrap();
+        db.insert_image(&[1, 2, 3], &[], 1, 1).unwrap();
+
+        assert_eq!(db.count(Some(ContentType::Text)).unwrap(), 2);
+        assert_eq!(db.count(Some(ContentType::Image)).unwrap(), 1);
+        assert_eq!(db.count(None).unwrap(), 3);
+    }
+
     #[test]
     fn test_enforce_max_entries() {
         let db = Database::new_in_memory().unwrap();
@@ -295,7 +2068,118 @@ mod tests {
             db.insert_text(&format!("entry {}", i)).unwrap();
         }
         db.enforce_max_entries(5).unwrap();
-        let entries = db.get_recent_entries_by_type(100, ContentType::Text).unwrap();
+        let entries = db.get_recent_entries_by_type(100, ContentType::Text, SortOrder::Newest).unwrap();
         assert_eq!(entries.len(), 5);
     }
+
+    #[test]
+    fn test_ring_capacity() {
+        let mut db = Database::new_in_memory().unwrap();
+        for i in 0..5 {
+            db.insert_text(&format!("entry {}", i)).unwrap();
+        }
+        db.set_ring_capacity(Some(3)).unwrap();
+        assert_eq!(db.count(None).unwrap(), 3);
+
+        // Every insert drops the oldest entry, whatever its type
+        let image = db.insert_image(&[1, 2, 3], &[], 1, 1).unwrap();
+        db.insert_batch(&[("imported".to_string(), chrono::Utc::now().timestamp())]).unwrap();
+        let mut texts: Vec<String> = db
+            .get_recent_entries_by_type(10, ContentType::Text, SortOrder::Newest)
+            .unwrap()
+            .into_iter()
+            .filter_map(|e| e.text_content)
+            .collect();
+        texts.sort();
+        assert_eq!(texts, ["entry 4", "imported"]);
+        assert!(db.get_entry(image).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_memory_mode_writes_no_file() {
+        let dir = std::env::temp_dir().join(format!("clipsnap_memory_{}", std::process::id()));
+        let mut config = Config::default();
+        config.storage.mode = "memory".to_string();
+        config.storage.database_path = dir.join("history.db").to_string_lossy().into_owned();
+        config.history.max_entries = 2;
+
+        let (db, repair) = open_history(&config).unwrap();
+        assert!(repair.is_none());
+        let first = db.insert_text("first").unwrap();
+        let second = db.insert_text("second").unwrap();
+        assert_eq!(db.get_entry(second).unwrap().unwrap().text_content.as_deref(), Some("second"));
+        db.insert_text("third").unwrap();
+        assert!(db.get_entry(first).unwrap().is_none());
+        assert_eq!(db.search_text("third").unwrap().len(), 1);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_enforce_max_bytes() {
+        let db = Database::new_in_memory().unwrap();
+        for i in 0..40 {
+            let id = if i % 4 == 0 {
+                db.insert_image(&vec![i as u8; 20_000], &[0; 1_000], 10, 10).unwrap()
+            } else {
+                db.insert_text(&format!("{} {}", i, "x".repeat(10_000))).unwrap()
+            };
+            db.conn
+                .execute("UPDATE clipboard_history SET created_at = ?1 WHERE id = ?2", params![i, id])
+                .unwrap();
+        }
+        let budget = 200_000;
+        assert!(db.content_size().unwrap() > budget);
+        assert!(db.file_size().unwrap() > budget);
+
+        let evicted = db.enforce_max_bytes(budget).unwrap();
+        assert!(evicted > 0);
+        assert!(db.content_size().unwrap() <= budget);
+        assert_eq!(db.count(None).unwrap(), 40 - evicted);
+        // The oldest entries went first, whatever their type
+        let newest = db.get_recent_entries_by_type(1, ContentType::Text, SortOrder::Newest).unwrap();
+        assert!(newest[0].text_content.as_deref().unwrap().starts_with("39 "));
+        assert!(db.get_recent_entries_by_type(100, ContentType::Image, SortOrder::Oldest).unwrap()[0].created_at > 0);
+
+        // Under budget, nothing more goes
+        assert_eq!(db.enforce_max_bytes(budget).unwrap(), 0);
+        assert_eq!(db.enforce_max_bytes(u64::MAX).unwrap(), 0);
+    }
+
+    /// Insert three text entries with distinct timestamps and sizes.
+    fn insert_ordering_fixture(db: &Database) {
+        for (text, ts) in [("medium--", 100), ("s", 200), ("largest entry", 300)] {
+            let id = db.insert_text(text).unwrap();
+            db.conn
+                .execute("UPDATE clipboard_history SET created_at = ?1 WHERE id = ?2", params![ts, id])
+                .unwrap();
+        }
+    }
+
+    fn texts(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.text_content.as_deref().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_sort_newest() {
+        let db = Database::new_in_memory().unwrap();
+        insert_ordering_fixture(&db);
+        let entries = db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::Newest).unwrap();
+        assert_eq!(texts(&entries), vec!["largest entry", "s", "medium--"]);
+    }
+
+    #[test]
+    fn test_sort_oldest() {
+        let db = Database::new_in_memory().unwrap();
+        insert_ordering_fixture(&db);
+        let entries = db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::Oldest).unwrap();
+        assert_eq!(texts(&entries), vec!["medium--", "s", "largest entry"]);
+    }
+
+    #[test]
+    fn test_sort_largest() {
+        let db = Database::new_in_memory().unwrap();
+        insert_ordering_fixture(&db);
+        let entries = db.get_recent_entries_by_type(10, ContentType::Text, SortOrder::Largest).unwrap();
+        assert_eq!(texts(&entries), vec!["largest entry", "medium--", "s"]);
+    }
 }
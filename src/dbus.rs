@@ -0,0 +1,72 @@
+use crate::ipc::Ingest;
+use gtk4::prelude::*;
+
+/// Object path the history interface is exported on.
+const OBJECT_PATH: &str = "/com/clipsnap/daemon";
+
+const INTROSPECTION_XML: &str = r#"
+<node>
+  <interface name="com.clipsnap.History">
+    <method name="AddText">
+      <arg type="s" name="text" direction="in"/>
+      <arg type="x" name="id" direction="out"/>
+    </method>
+    <method name="AddImage">
+      <arg type="ay" name="png" direction="in"/>
+      <arg type="x" name="id" direction="out"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Export `AddText(s) -> x` and `AddImage(ay) -> x` on the application's
+/// session-bus connection.
+///
+/// Only the session bus is used, so only processes running as the same user
+/// in the same login session can reach these methods.
+pub fn register(app: &gtk4::Application, ingest: Ingest) {
+    let Some(connection) = app.dbus_connection() else {
+        log::warn!("No D-Bus connection; history API not exported");
+        return;
+    };
+
+    let node = match gio::DBusNodeInfo::for_xml(INTROSPECTION_XML) {
+        Ok(node) => node,
+        Err(e) => {
+            log::error!("Invalid D-Bus introspection data: {}", e);
+            return;
+        }
+    };
+    let Some(interface) = node.lookup_interface("com.clipsnap.History") else {
+        return;
+    };
+
+    let result = connection.register_object(
+        OBJECT_PATH,
+        &interface,
+        move |_conn, _sender, _path, _iface, method, params, invocation| {
+            let result = match method {
+                "AddText" => match params.get::<(String,)>() {
+                    Some((text,)) => ingest.add_text(&text),
+                    None => Err(anyhow::anyhow!("Expected (s)")),
+                },
+                "AddImage" => match params.get::<(Vec<u8>,)>() {
+                    Some((png,)) => ingest.add_image(&png),
+                    None => Err(anyhow::anyhow!("Expected (ay)")),
+                },
+                other => Err(anyhow::anyhow!("Unknown method {}", other)),
+            };
+            match result {
+                Ok(id) => invocation.return_value(Some(&(id,).to_variant())),
+                Err(e) => invocation.return_dbus_error("com.clipsnap.Error.Failed", &e.to_string()),
+            }
+        },
+        |_conn, _sender, _path, _iface, _prop| ().to_variant(),
+        |_conn, _sender, _path, _iface, _prop, _value| false,
+    );
+
+    match result {
+        Ok(_) => log::info!("Exported history API on D-Bus at {}", OBJECT_PATH),
+        Err(e) => log::error!("Failed to export history API on D-Bus: {}", e),
+    }
+}
@@ -0,0 +1,164 @@
+use crate::autostart;
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::StorageMode;
+use crate::screenshot;
+use gtk4::prelude::*;
+
+/// Result of a single diagnostic check.
+struct Check {
+    name: &'static str,
+    passed: bool,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, result: Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Check { name, passed: true, detail },
+            Err(detail) => Check { name, passed: false, detail },
+        }
+    }
+}
+
+/// Run `clipsnap doctor`: print environment details and a pass/fail line per
+/// check. Returns the process exit code (non-zero if any check failed).
+pub fn run() -> i32 {
+    let checks = vec![
+        Check::new("Display backend", check_backend()),
+        Check::new("X11 screen", check_screen()),
+        Check::new("Monitors", check_monitors()),
+        Check::new("Screen capture (1x1)", check_capture()),
+        Check::new("Clipboard read", check_clipboard()),
+        Check::new("Notification daemon", check_notifications()),
+        Check::new("Autostart", autostart::status(&autostart::autostart_path())),
+        Check::new("History database", check_database()),
+    ];
+
+    println!("ClipSnap doctor");
+    for check in &checks {
+        let status = if check.passed { "PASS" } else { "FAIL" };
+        println!("[{}] {}: {}", status, check.name, check.detail);
+    }
+
+    let failed = checks.iter().filter(|c| !c.passed).count();
+    if failed == 0 {
+        println!("All checks passed.");
+        0
+    } else {
+        println!("{} check(s) failed.", failed);
+        1
+    }
+}
+
+fn check_backend() -> Result<String, String> {
+    let session = std::env::var("XDG_SESSION_TYPE").ok();
+    let wayland = std::env::var("WAYLAND_DISPLAY").ok();
+    let display = std::env::var("DISPLAY").ok();
+    detect_backend(session.as_deref(), wayland.as_deref(), display.as_deref())
+}
+
+/// Classify the session from its environment. The overlay needs X11 (or
+/// XWayland); on Wayland, captures go through the screenshot portal.
+fn detect_backend(
+    session_type: Option<&str>,
+    wayland_display: Option<&str>,
+    display: Option<&str>,
+) -> Result<String, String> {
+    let on_wayland = session_type == Some("wayland") || wayland_display.is_some_and(|d| !d.is_empty());
+    match (on_wayland, display.filter(|d| !d.is_empty())) {
+        (false, Some(d)) => Ok(format!("X11 (DISPLAY={})", d)),
+        (true, Some(d)) => Ok(format!("Wayland with XWayland (DISPLAY={}); captures use the screenshot portal", d)),
+        (true, None) => Err("Wayland without XWayland; the overlay and window picking need X11".to_string()),
+        (false, None) => Err("No display found (DISPLAY is not set)".to_string()),
+    }
+}
+
+fn check_screen() -> Result<String, String> {
+    screenshot::get_screen_info()
+        .map(|(_, _, w, h)| format!("{}x{}", w, h))
+        .map_err(|e| format!("{:#}", anyhow::Error::from(e)))
+}
+
+fn check_monitors() -> Result<String, String> {
+    gtk4::init().map_err(|e| format!("GTK failed to initialise: {}", e))?;
+    let display = gdk4::Display::default().ok_or("No GDK display")?;
+    let monitors = display.monitors();
+
+    let mut lines = Vec::new();
+    for i in 0..monitors.n_items() {
+        if let Some(monitor) = monitors.item(i).and_then(|m| m.downcast::<gdk4::Monitor>().ok()) {
+            let geom = monitor.geometry();
+            lines.push(format!(
+                "{}: {}x{}+{}+{} scale {}",
+                monitor.connector().map(|c| c.to_string()).unwrap_or_else(|| format!("#{}", i)),
+                geom.width(),
+                geom.height(),
+                geom.x(),
+                geom.y(),
+                monitor.scale_factor()
+            ));
+        }
+    }
+
+    if lines.is_empty() {
+        Err("No monitors reported by GDK".to_string())
+    } else {
+        Ok(lines.join("; "))
+    }
+}
+
+fn check_capture() -> Result<String, String> {
+    let (data, w, h) = screenshot::capture_region(0, 0, 1, 1).map_err(|e| format!("{:#}", anyhow::Error::from(e)))?;
+    match data.get(..4) {
+        Some(px) => Ok(format!("{}x{} pixel read, BGRA {:?}", w, h, px)),
+        None => Err("get_image returned no pixel data".to_string()),
+    }
+}
+
+fn check_clipboard() -> Result<String, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| format!("Cannot open clipboard: {}", e))?;
+    match clipboard.get_text() {
+        Ok(text) => Ok(format!("text available ({} bytes)", text.len())),
+        Err(arboard::Error::ContentNotAvailable) => Ok("readable (currently empty or non-text)".to_string()),
+        Err(e) => Err(format!("Cannot read clipboard: {}", e)),
+    }
+}
+
+/// The full integrity check, which startup skips for the faster quick check.
+fn check_database() -> Result<String, String> {
+    let config = Config::load_or_create_default().map_err(|e| format!("Cannot load config: {:#}", e))?;
+    if config.storage_mode() == StorageMode::Memory {
+        return Ok("storage.mode is \"memory\"; no file to check".to_string());
+    }
+    let path = config.resolved_db_path();
+    if !path.exists() {
+        return Ok(format!("{:?} doesn't exist yet", path));
+    }
+    match Database::integrity_problems(&path) {
+        Ok(problems) if problems.is_empty() => Ok(format!("{:?} passed the integrity check", path)),
+        Ok(problems) => Err(format!("{:?}: {}", path, problems.join("; "))),
+        Err(e) => Err(format!("Cannot check {:?}: {}", path, e)),
+    }
+}
+
+fn check_notifications() -> Result<String, String> {
+    notify_rust::get_server_information()
+        .map(|info| format!("{} {} ({})", info.name, info.version, info.vendor))
+        .map_err(|e| format!("No notification daemon reachable: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_backend() {
+        assert!(detect_backend(Some("x11"), None, Some(":0")).unwrap().starts_with("X11"));
+        assert!(detect_backend(Some("wayland"), Some("wayland-0"), Some(":0"))
+            .unwrap()
+            .contains("XWayland"));
+        assert!(detect_backend(None, Some("wayland-0"), None).is_err());
+        assert!(detect_backend(None, None, Some("")).is_err());
+    }
+}
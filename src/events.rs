@@ -0,0 +1,177 @@
+//! Live feed of newly stored history entries, one JSON line per entry, for
+//! `clipsnap tail` and the control socket's `SUBSCRIBE` command.
+//!
+//! Publishing never blocks: each subscriber has a bounded buffer, and a
+//! subscriber that falls behind misses entries rather than stalling the
+//! clipboard monitor. Text that looks like a secret is never published.
+
+use crate::export;
+use crate::models::ContentType;
+use crate::sensitive;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+
+/// Lines buffered per subscriber before further entries are dropped for it.
+const SUBSCRIBER_BUFFER: usize = 256;
+
+/// Characters of text included in an event's preview.
+const PREVIEW_CHARS: usize = 120;
+
+static FEED: Broadcaster = Broadcaster::new(SUBSCRIBER_BUFFER);
+
+/// A newly stored history entry.
+pub struct EntryEvent<'a> {
+    pub id: i64,
+    pub content_type: ContentType,
+    pub created_at: i64,
+    pub text: Option<&'a str>,
+    pub dimensions: Option<(u32, u32)>,
+}
+
+impl<'a> EntryEvent<'a> {
+    /// A text entry stored just now.
+    pub fn text(id: i64, text: &'a str) -> Self {
+        EntryEvent {
+            id,
+            content_type: ContentType::Text,
+            created_at: chrono::Utc::now().timestamp(),
+            text: Some(text),
+            dimensions: None,
+        }
+    }
+
+    /// An image entry stored just now.
+    pub fn image(id: i64, width: u32, height: u32) -> Self {
+        EntryEvent {
+            id,
+            content_type: ContentType::Image,
+            created_at: chrono::Utc::now().timestamp(),
+            text: None,
+            dimensions: Some((width, height)),
+        }
+    }
+
+    fn is_sensitive(&self) -> bool {
+        self.text.is_some_and(sensitive::looks_sensitive)
+    }
+
+    /// The event as a JSON object on one line, without the newline.
+    pub fn to_json_line(&self) -> String {
+        let mut fields = vec![
+            format!("\"id\": {}", self.id),
+            format!("\"type\": \"{}\"", self.content_type.to_str()),
+            format!("\"created_at\": \"{}\"", export::format_time(self.created_at)),
+        ];
+        if let Some(text) = self.text {
+            let preview = export::preview_line(text, PREVIEW_CHARS);
+            fields.push(format!("\"preview\": \"{}\"", export::json_escape(&preview)));
+        }
+        if let Some((width, height)) = self.dimensions {
+            fields.push(format!("\"width\": {}, \"height\": {}", width, height));
+        }
+        format!("{{{}}}", fields.join(", "))
+    }
+}
+
+/// Fans events out to any number of subscribers.
+pub struct Broadcaster {
+    capacity: usize,
+    subscribers: Mutex<Vec<SyncSender<String>>>,
+}
+
+impl Broadcaster {
+    pub const fn new(capacity: usize) -> Self {
+        Broadcaster {
+            capacity,
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Receive the JSON line of every entry published from now on.
+    pub fn subscribe(&self) -> Receiver<String> {
+        let (tx, rx) = mpsc::sync_channel(self.capacity);
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send `event` to every subscriber that has room for it, and forget
+    /// subscribers that have gone away.
+    pub fn publish(&self, event: &EntryEvent) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() || event.is_sensitive() {
+            return;
+        }
+        let line = event.to_json_line();
+        subscribers.retain(|tx| match tx.try_send(line.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                log::debug!("Tail subscriber is behind; dropped entry {}", event.id);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+/// Subscribe to the process-wide feed.
+pub fn subscribe() -> Receiver<String> {
+    FEED.subscribe()
+}
+
+/// Publish a newly stored entry to the process-wide feed.
+pub fn publish(event: &EntryEvent) {
+    FEED.publish(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: i64, text: &str) -> EntryEvent<'_> {
+        EntryEvent {
+            created_at: 0,
+            ..EntryEvent::text(id, text)
+        }
+    }
+
+    #[test]
+    fn test_json_line() {
+        assert_eq!(
+            event(7, "say \"hi\"\nbye").to_json_line(),
+            r#"{"id": 7, "type": "text", "created_at": "1970-01-01T00:00:00+00:00", "preview": "say \"hi\" bye"}"#
+        );
+        let image = EntryEvent {
+            created_at: 0,
+            ..EntryEvent::image(8, 640, 480)
+        };
+        assert!(image.to_json_line().ends_with(r#""type": "image", "created_at": "1970-01-01T00:00:00+00:00", "width": 640, "height": 480}"#));
+    }
+
+    #[test]
+    fn test_slow_subscriber_misses_entries() {
+        let feed = Broadcaster::new(2);
+        let slow = feed.subscribe();
+        for id in 1..=3 {
+            feed.publish(&event(id, "entry"));
+        }
+        let received: Vec<String> = slow.try_iter().collect();
+        assert_eq!(received.len(), 2);
+        assert!(received[0].starts_with(r#"{"id": 1,"#));
+
+        // Caught up again, the subscriber gets new entries
+        feed.publish(&event(4, "entry"));
+        assert!(slow.try_recv().unwrap().starts_with(r#"{"id": 4,"#));
+    }
+
+    #[test]
+    fn test_sensitive_and_departed_subscribers() {
+        let feed = Broadcaster::new(8);
+        let rx = feed.subscribe();
+        feed.publish(&event(1, "Tr0ub4dor&3x"));
+        assert!(rx.try_recv().is_err());
+
+        drop(rx);
+        feed.publish(&event(2, "hello"));
+        assert!(feed.subscribers.lock().unwrap().is_empty());
+    }
+}
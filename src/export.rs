@@ -0,0 +1,315 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{ContentType, HistoryEntry, StorageMode};
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+
+/// Output formats for exporting a slice of history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Text,
+    Csv,
+}
+
+impl ExportFormat {
+    /// Pick a format from a file extension, defaulting to JSON.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()) {
+            Some(ext) if ext == "csv" => ExportFormat::Csv,
+            Some(ext) if ext == "txt" => ExportFormat::Text,
+            _ => ExportFormat::Json,
+        }
+    }
+}
+
+/// Write `entries` to `path` in the given format. Returns how many entries
+/// were written; CSV only includes text entries.
+pub fn export_entries(entries: &[HistoryEntry], format: ExportFormat, path: &Path) -> Result<usize> {
+    let (content, count) = match format {
+        ExportFormat::Json => (export_json(entries), entries.len()),
+        ExportFormat::Text => (export_text(entries), entries.len()),
+        ExportFormat::Csv => {
+            let count = entries.iter().filter(|e| e.content_type == ContentType::Text).count();
+            (export_csv(entries), count)
+        }
+    };
+    std::fs::write(path, content).with_context(|| format!("Failed to write export: {:?}", path))?;
+    Ok(count)
+}
+
+/// Write the newest `limit` entries of any type to `path`, leaving out text
+/// that looks like a secret. Returns how many entries were written.
+pub fn export_recent(db: &Database, limit: usize, path: &Path) -> Result<usize> {
+    let entries: Vec<HistoryEntry> = db.get_recent_entries(limit)?.into_iter().filter(|e| !e.sensitive).collect();
+    export_entries(&entries, ExportFormat::from_path(path), path)
+}
+
+/// Run `clipsnap export [--limit N] <path>` with the arguments after
+/// `export`. Without `--limit`, the whole history (`history.max_entries`) is
+/// exported. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let (limit, path) = match args {
+        [path] => (None, Path::new(path)),
+        [flag, n, path] if flag == "--limit" => match n.parse::<usize>() {
+            Ok(n) => (Some(n), Path::new(path)),
+            Err(_) => {
+                eprintln!("Invalid --limit {:?}", n);
+                return 2;
+            }
+        },
+        _ => {
+            eprintln!("Usage: clipsnap export [--limit N] <path.json|path.txt|path.csv>");
+            return 2;
+        }
+    };
+
+    let result = Config::load_or_create_default().map_err(anyhow::Error::from).and_then(|config| {
+        // A running ClipSnap's memory-only history can't be reached from here
+        if config.storage_mode() == StorageMode::Memory {
+            return Err(anyhow!("storage.mode is \"memory\"; there is no history file to export from"));
+        }
+        let db = Database::new(&config.resolved_db_path())?;
+        export_recent(&db, limit.unwrap_or(config.history.max_entries), path)
+    });
+    match result {
+        Ok(count) => {
+            println!("Exported {} entries to {:?}.", count, path);
+            0
+        }
+        Err(e) => {
+            eprintln!("Export failed: {:#}", e);
+            1
+        }
+    }
+}
+
+/// Serialize entries as a JSON array. Images are described by their metadata
+/// only; the image data itself is not embedded.
+pub fn export_json(entries: &[HistoryEntry]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let mut fields = vec![
+                format!("\"id\": {}", entry.id),
+                format!("\"type\": \"{}\"", entry.content_type.to_str()),
+                format!("\"created_at\": \"{}\"", format_time(entry.created_at)),
+                format!("\"size\": {}", entry.file_size),
+            ];
+            if let Some(text) = &entry.text_content {
+                fields.push(format!("\"text\": \"{}\"", json_escape(text)));
+            }
+            format!("  {{{}}}", fields.join(", "))
+        })
+        .collect();
+
+    if items.is_empty() {
+        "[]\n".to_string()
+    } else {
+        format!("[\n{}\n]\n", items.join(",\n"))
+    }
+}
+
+/// Plain text: one block per entry separated by a blank line.
+pub fn export_text(entries: &[HistoryEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| match entry.content_type {
+            ContentType::Text => entry.text_content.clone().unwrap_or_default(),
+            ContentType::Image => format!("[image, {} bytes, {}]", entry.file_size, format_time(entry.created_at)),
+        })
+        .map(|block| block + "\n")
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// CSV of text entries with a `text,created_at` header.
+pub fn export_csv(entries: &[HistoryEntry]) -> String {
+    let mut out = String::from("text,created_at\n");
+    for entry in entries.iter().filter(|e| e.content_type == ContentType::Text) {
+        let text = entry.text_content.as_deref().unwrap_or("");
+        out.push_str(&format!("{},{}\n", csv_field(text), format_time(entry.created_at)));
+    }
+    out
+}
+
+/// Raw stored bytes of an entry as copyable text, for bug reports: base64 for
+/// text entries and a hex dump for images.
+pub fn raw_bytes_dump(entry: &HistoryEntry) -> String {
+    match entry.content_type {
+        ContentType::Text => base64_encode(entry.text_content.as_deref().unwrap_or("").as_bytes()),
+        ContentType::Image => hex_dump(entry.image_data.as_deref().unwrap_or(&[])),
+    }
+}
+
+/// Standard (RFC 4648) base64 with padding.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// `hexdump -C` style dump: offset, 16 hex bytes, printable ASCII.
+pub fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        out.push_str(&format!("{:08x}  {:<47}  |{}|\n", i * 16, hex.join(" "), ascii));
+    }
+    out
+}
+
+pub fn format_time(ts: i64) -> String {
+    chrono::DateTime::<chrono::Utc>::from_timestamp(ts, 0)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| ts.to_string())
+}
+
+/// Collapse whitespace and control characters to single spaces and
+/// truncate to `max_chars` characters, appending `…` when cut.
+pub fn preview_line(text: &str, max_chars: usize) -> String {
+    let cleaned = text
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if cleaned.chars().count() <= max_chars {
+        cleaned
+    } else {
+        let cut: String = cleaned.chars().take(max_chars).collect();
+        format!("{}…", cut.trim_end())
+    }
+}
+
+pub fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_entry(id: i64, text: &str) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            content_type: ContentType::Text,
+            image_data: None,
+            thumbnail: None,
+            text_content: Some(text.to_string()),
+            created_at: 0,
+            file_size: text.len() as i64,
+            width: None,
+            height: None,
+            use_count: 0,
+            source_mime: None,
+            capture_method: None,
+            note: None,
+            pinned: false,
+            sensitive: false,
+            content_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_export_json_escapes_text() {
+        let json = export_json(&[text_entry(1, "say \"hi\"\n")]);
+        assert!(json.contains(r#""text": "say \"hi\"\n""#));
+        assert!(json.contains("\"created_at\": \"1970-01-01T00:00:00+00:00\""));
+        assert_eq!(export_json(&[]), "[]\n");
+    }
+
+    #[test]
+    fn test_export_csv_quotes_fields_and_skips_images() {
+        let mut image = text_entry(2, "");
+        image.content_type = ContentType::Image;
+        image.text_content = None;
+        let csv = export_csv(&[text_entry(1, "a,b \"c\""), image]);
+        assert_eq!(csv, "text,created_at\n\"a,b \"\"c\"\"\",1970-01-01T00:00:00+00:00\n");
+    }
+
+    #[test]
+    fn test_preview_line_collapses_whitespace() {
+        assert_eq!(preview_line("  fn main() {\n\t42\r\n}  ", 60), "fn main() { 42 }");
+    }
+
+    #[test]
+    fn test_preview_line_truncates_on_char_boundary() {
+        assert_eq!(preview_line("héllo wörld", 5), "héllo…");
+        assert_eq!(preview_line("abc", 3), "abc");
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = hex_dump(b"\x89PNG\r\n\x1a\n0123456789");
+        assert_eq!(
+            dump,
+            "00000000  89 50 4e 47 0d 0a 1a 0a 30 31 32 33 34 35 36 37  |.PNG....01234567|\n\
+             00000010  38 39                                            |89|\n"
+        );
+    }
+
+    #[test]
+    fn test_export_recent_skips_secrets() {
+        let db = Database::new_in_memory().unwrap();
+        db.insert_text("older").unwrap();
+        db.insert_text("newest").unwrap();
+        db.insert_text("aK8#mQ2!zX_9pL").unwrap();
+        let path = std::env::temp_dir().join(format!("clipsnap_export_{}.txt", std::process::id()));
+
+        assert_eq!(export_recent(&db, 10, &path).unwrap(), 2);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(text.contains("newest") && text.contains("older"));
+        assert!(!text.contains("aK8#"));
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ExportFormat::from_path(Path::new("out.CSV")), ExportFormat::Csv);
+        assert_eq!(ExportFormat::from_path(Path::new("out.txt")), ExportFormat::Text);
+        assert_eq!(ExportFormat::from_path(Path::new("out")), ExportFormat::Json);
+    }
+}
@@ -0,0 +1,149 @@
+//! Typo-tolerant matching for the history search (`ui.fuzzy_search`).
+//!
+//! Every word of the query has to match the text, in one of three ways, best
+//! first: as a substring, as a tight subsequence ("fnc" in "function"), or
+//! as a word within a small edit distance ("functoin" for "function"). The
+//! scores of the query words add up, so better matches rank higher.
+
+/// Characters of each candidate looked at; matches further in are missed.
+const MAX_SCANNED_CHARS: usize = 10_000;
+
+/// Score of `text` against `query`, or `None` if some query word doesn't
+/// match. Higher is better; case is ignored.
+pub fn score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.chars().take(MAX_SCANNED_CHARS).flat_map(char::to_lowercase).collect();
+    let words: Vec<&[char]> = text.split(|c| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    let mut total = 0;
+    for term in query.split_whitespace() {
+        let term: Vec<char> = term.chars().flat_map(char::to_lowercase).collect();
+        total += substring_score(&term, &text)
+            .or_else(|| subsequence_score(&term, &text))
+            .or_else(|| typo_score(&term, &words))?;
+    }
+    Some(total)
+}
+
+/// `term` appears as is; more if it starts a word.
+fn substring_score(term: &[char], text: &[char]) -> Option<u32> {
+    let starts = (0..=text.len().checked_sub(term.len())?).filter(|&i| &text[i..i + term.len()] == term);
+    let mut found = false;
+    for start in starts {
+        if start == 0 || !text[start - 1].is_alphanumeric() {
+            return Some(120);
+        }
+        found = true;
+    }
+    found.then_some(100)
+}
+
+/// `term`'s characters appear in order, spread over at most three times its
+/// length; tighter is better.
+fn subsequence_score(term: &[char], text: &[char]) -> Option<u32> {
+    if term.len() < 2 {
+        return None;
+    }
+    let max_span = term.len() * 3;
+    let mut best_span = None;
+    for (start, &c) in text.iter().enumerate() {
+        if c != term[0] {
+            continue;
+        }
+        let mut pos = start;
+        let mut matched = 1;
+        for &t in &term[1..] {
+            match text[pos + 1..].iter().take(max_span).position(|&c| c == t) {
+                Some(offset) => {
+                    pos += offset + 1;
+                    matched += 1;
+                }
+                None => break,
+            }
+        }
+        let span = pos - start + 1;
+        if matched == term.len() && span <= max_span {
+            best_span = Some(best_span.map_or(span, |b: usize| b.min(span)));
+        }
+    }
+    // Gaps cost a little each, down to a floor below any typo match's score
+    best_span.map(|span| 80u32.saturating_sub(5 * (span - term.len()) as u32).max(60))
+}
+
+/// Some word of the text is within `max_typos` edits of `term`.
+fn typo_score(term: &[char], words: &[&[char]]) -> Option<u32> {
+    let allowed = max_typos(term.len());
+    if allowed == 0 {
+        return None;
+    }
+    let distance = words
+        .iter()
+        .filter(|w| w.len().abs_diff(term.len()) <= allowed)
+        .map(|w| edit_distance(term, w))
+        .min()
+        .filter(|&d| d <= allowed)?;
+    Some(50 - 15 * distance as u32)
+}
+
+fn max_typos(len: usize) -> usize {
+    match len {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Edits (insert, delete, substitute, swap adjacent) turning `a` into `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut d = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = d;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tolerates_typos() {
+        assert!(score("functoin", "fn main() -> a function call").is_some());
+        assert!(score("recieve", "Please receive the package").is_some());
+        assert!(score("fnc", "function").is_some());
+        assert!(score("functoin", "nothing relevant here").is_none());
+        // Short words must match exactly
+        assert!(score("fnu", "fun").is_none());
+        // Every query word has to match
+        assert!(score("receive zebra", "Please receive the package").is_none());
+    }
+
+    #[test]
+    fn test_ranking() {
+        let exact = score("function", "a Function here").unwrap();
+        let inside = score("function", "dysfunctional").unwrap();
+        let gappy = score("function", "fun ction").unwrap();
+        let typo = score("function", "funtcion").unwrap();
+        assert!(exact > inside && inside > gappy && gappy > typo);
+        assert!(score("kitten puppy", "kitten and puppy").unwrap() > score("kitten puppy", "kitten and pupyp").unwrap());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&chars("functoin"), &chars("function")), 1);
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(edit_distance(&chars(""), &chars("abc")), 3);
+    }
+}
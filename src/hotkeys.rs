@@ -1,12 +1,96 @@
-use anyhow::{anyhow, Result};
 use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::GlobalHotKeyManager;
+use std::collections::HashMap;
+
+/// Why a hotkey string could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HotkeyError {
+    #[error("Empty hotkey string")]
+    Empty,
+    #[error("Unknown modifier: {0}")]
+    UnknownModifier(String),
+    /// A real modifier the hotkey backend can't grab: binding it as another
+    /// modifier, or dropping it, would fire on the wrong combination.
+    #[error("{0} can't be used in a global shortcut; only Ctrl, Alt, Shift and Super can")]
+    UnsupportedModifier(&'static str),
+    #[error("Unknown key code: {0}")]
+    UnknownKey(String),
+}
+
+/// Maps registered hotkey ids to the actions they trigger.
+#[derive(Default)]
+pub struct ActionRegistry {
+    actions: HashMap<u32, Box<dyn Fn()>>,
+    /// Hotkeys registered with the OS through `register`.
+    registered: Vec<HotKey>,
+}
+
+impl ActionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `hotkey` with the OS and store `action` for it.
+    /// Failures are logged and reported; returns the hotkey id on success.
+    pub fn register(
+        &mut self,
+        manager: &GlobalHotKeyManager,
+        accel: &str,
+        hotkey: HotKey,
+        action: impl Fn() + 'static,
+    ) -> Option<u32> {
+        if let Err(e) = manager.register(hotkey) {
+            let message = describe_register_error(accel, &e);
+            log::warn!("{}", message);
+            eprintln!("WARNING: {}", message);
+            return None;
+        }
+        log::info!("Registered hotkey: {} (ID: {})", accel, hotkey.id());
+        self.registered.push(hotkey);
+        Some(self.insert(hotkey, action))
+    }
+
+    /// Unregister every hotkey registered through `register` and drop all
+    /// actions, so a reloaded config's shortcuts can take their place.
+    pub fn clear(&mut self, manager: &GlobalHotKeyManager) {
+        for hotkey in self.registered.drain(..) {
+            if let Err(e) = manager.unregister(hotkey) {
+                log::warn!("Failed to unregister hotkey (ID: {}): {}", hotkey.id(), e);
+            }
+        }
+        self.actions.clear();
+    }
+
+    /// Store `action` for `hotkey` without registering it. Returns the id.
+    pub fn insert(&mut self, hotkey: HotKey, action: impl Fn() + 'static) -> u32 {
+        let id = hotkey.id();
+        self.actions.insert(id, Box::new(action));
+        id
+    }
+
+    /// Run the action bound to `id`. Returns false if there is none.
+    pub fn dispatch(&self, id: u32) -> bool {
+        match self.actions.get(&id) {
+            Some(action) => {
+                action();
+                true
+            }
+            None => false,
+        }
+    }
+}
 
 /// Parse a human-readable hotkey string like `"Ctrl+Super+S"` into a `HotKey`.
-pub fn parse_hotkey(config_str: &str) -> Result<HotKey> {
-    let parts: Vec<&str> = config_str.split('+').map(|s| s.trim()).collect();
-    if parts.is_empty() {
-        return Err(anyhow!("Empty hotkey string"));
+///
+/// The modifiers are Ctrl, Alt, Shift and Super, the ones the X11 backend
+/// grabs. AltGr (ISO Level 3 Shift), Compose and the Menu key are rejected
+/// with [`HotkeyError::UnsupportedModifier`]; Menu still works as the key
+/// itself.
+pub fn parse_hotkey(config_str: &str) -> Result<HotKey, HotkeyError> {
+    if config_str.trim().is_empty() {
+        return Err(HotkeyError::Empty);
     }
+    let parts: Vec<&str> = config_str.split('+').map(|s| s.trim()).collect();
 
     let mut modifiers = Modifiers::empty();
     for part in &parts[..parts.len() - 1] {
@@ -15,7 +99,12 @@ pub fn parse_hotkey(config_str: &str) -> Result<HotKey> {
             "alt" => modifiers |= Modifiers::ALT,
             "shift" => modifiers |= Modifiers::SHIFT,
             "super" | "win" | "meta" => modifiers |= Modifiers::SUPER,
-            other => return Err(anyhow!("Unknown modifier: {}", other)),
+            "altgr" | "alt_gr" | "altgraph" | "iso_level3_shift" => {
+                return Err(HotkeyError::UnsupportedModifier("AltGr"))
+            }
+            "compose" | "multi_key" => return Err(HotkeyError::UnsupportedModifier("Compose")),
+            "menu" | "contextmenu" => return Err(HotkeyError::UnsupportedModifier("Menu")),
+            other => return Err(HotkeyError::UnknownModifier(other.to_string())),
         }
     }
 
@@ -31,8 +120,72 @@ pub fn parse_hotkey(config_str: &str) -> Result<HotKey> {
     Ok(HotKey::new(mods, code))
 }
 
+/// Parse the optional shortcut `name` (e.g. `repeat_region`). An unset
+/// shortcut is `None`, and so is an invalid one, after logging why.
+pub fn parse_optional(name: &str, accel: Option<&str>) -> Option<HotKey> {
+    match parse_hotkey(accel?) {
+        Ok(hotkey) => Some(hotkey),
+        Err(e) => {
+            log::error!("Invalid {} shortcut: {}", name, e);
+            None
+        }
+    }
+}
+
+/// The same hotkey with Shift added, or `None` if it already uses Shift.
+pub fn with_shift(hotkey: HotKey) -> Option<HotKey> {
+    if hotkey.mods.contains(Modifiers::SHIFT) {
+        return None;
+    }
+    Some(HotKey::new(Some(hotkey.mods | Modifiers::SHIFT), hotkey.key))
+}
+
+/// Render `hotkey` as a config string, the inverse of [`parse_hotkey`]:
+/// modifiers in the order Ctrl, Alt, Shift, Super, then the key's canonical
+/// name, e.g. `"Ctrl+Alt+S"`.
+pub fn hotkey_to_string(hotkey: &HotKey) -> String {
+    let mut parts = Vec::new();
+    for (modifier, name) in [
+        (Modifiers::CONTROL, "Ctrl"),
+        (Modifiers::ALT, "Alt"),
+        (Modifiers::SHIFT, "Shift"),
+        (Modifiers::SUPER, "Super"),
+    ] {
+        if hotkey.mods.contains(modifier) {
+            parts.push(name.to_string());
+        }
+    }
+    // Keys `parse_key_code` has no name for can't come from a config string
+    parts.push(key_name(hotkey.key).map_or_else(|| format!("{:?}", hotkey.key), str::to_string));
+    parts.join("+")
+}
+
+/// Explain why registering `accel` failed, calling out keys that are
+/// already grabbed (typically PrintScreen, reserved by most desktops).
+pub fn describe_register_error(accel: &str, err: &global_hotkey::Error) -> String {
+    match err {
+        // The desktop's own screenshot tool usually owns plain PrintScreen
+        global_hotkey::Error::AlreadyRegistered(hotkey) if hotkey.key == Code::PrintScreen && hotkey.mods.is_empty() => {
+            format!(
+                "{} is already taken, most likely by your desktop's screenshot tool; \
+                 unbind it in your keyboard settings to use it for ClipSnap, or add a modifier (e.g. Ctrl+PrintScreen)",
+                accel
+            )
+        }
+        global_hotkey::Error::AlreadyRegistered(_) => format!(
+            "{} is already taken by another application or your desktop environment \
+             (PrintScreen is commonly reserved); free it in your keyboard settings or pick another shortcut",
+            accel
+        ),
+        global_hotkey::Error::FailedToRegister(msg) => {
+            format!("{} cannot be bound on this system: {}", accel, msg)
+        }
+        other => format!("Could not register {}: {}", accel, other),
+    }
+}
+
 /// Map a key name to a `Code` variant.
-fn parse_key_code(key: &str) -> Result<Code> {
+fn parse_key_code(key: &str) -> Result<Code, HotkeyError> {
     match key.to_uppercase().as_str() {
         "A" => Ok(Code::KeyA),
         "B" => Ok(Code::KeyB),
@@ -82,6 +235,33 @@ fn parse_key_code(key: &str) -> Result<Code> {
         "F10" => Ok(Code::F10),
         "F11" => Ok(Code::F11),
         "F12" => Ok(Code::F12),
+        "NUMPAD0" | "KP0" => Ok(Code::Numpad0),
+        "NUMPAD1" | "KP1" => Ok(Code::Numpad1),
+        "NUMPAD2" | "KP2" => Ok(Code::Numpad2),
+        "NUMPAD3" | "KP3" => Ok(Code::Numpad3),
+        "NUMPAD4" | "KP4" => Ok(Code::Numpad4),
+        "NUMPAD5" | "KP5" => Ok(Code::Numpad5),
+        "NUMPAD6" | "KP6" => Ok(Code::Numpad6),
+        "NUMPAD7" | "KP7" => Ok(Code::Numpad7),
+        "NUMPAD8" | "KP8" => Ok(Code::Numpad8),
+        "NUMPAD9" | "KP9" => Ok(Code::Numpad9),
+        "NUMPADADD" | "NUMPADPLUS" => Ok(Code::NumpadAdd),
+        "NUMPADSUBTRACT" | "NUMPADMINUS" => Ok(Code::NumpadSubtract),
+        "NUMPADMULTIPLY" => Ok(Code::NumpadMultiply),
+        "NUMPADDIVIDE" => Ok(Code::NumpadDivide),
+        "NUMPADDECIMAL" => Ok(Code::NumpadDecimal),
+        // `+` separates the parts, so the plus key is only `NumpadAdd`
+        "MINUS" | "-" => Ok(Code::Minus),
+        "EQUAL" | "EQUALS" | "=" => Ok(Code::Equal),
+        "BRACKETLEFT" | "[" => Ok(Code::BracketLeft),
+        "BRACKETRIGHT" | "]" => Ok(Code::BracketRight),
+        "SEMICOLON" | ";" => Ok(Code::Semicolon),
+        "QUOTE" | "APOSTROPHE" | "'" => Ok(Code::Quote),
+        "BACKQUOTE" | "GRAVE" | "`" => Ok(Code::Backquote),
+        "COMMA" | "," => Ok(Code::Comma),
+        "PERIOD" | "." => Ok(Code::Period),
+        "SLASH" | "/" => Ok(Code::Slash),
+        "BACKSLASH" | "\\" => Ok(Code::Backslash),
         "SPACE" => Ok(Code::Space),
         "ENTER" | "RETURN" => Ok(Code::Enter),
         "ESCAPE" | "ESC" => Ok(Code::Escape),
@@ -97,11 +277,135 @@ fn parse_key_code(key: &str) -> Result<Code> {
         "DOWN" | "ARROWDOWN" => Ok(Code::ArrowDown),
         "LEFT" | "ARROWLEFT" => Ok(Code::ArrowLeft),
         "RIGHT" | "ARROWRIGHT" => Ok(Code::ArrowRight),
-        "PRINTSCREEN" => Ok(Code::PrintScreen),
-        other => Err(anyhow!("Unknown key code: {}", other)),
+        "PRINTSCREEN" | "PRINT" => Ok(Code::PrintScreen),
+        "SCROLLLOCK" => Ok(Code::ScrollLock),
+        "PAUSE" => Ok(Code::Pause),
+        "MENU" | "CONTEXTMENU" => Ok(Code::ContextMenu),
+        "NUMLOCK" => Ok(Code::NumLock),
+        "VOLUMEMUTE" | "AUDIOVOLUMEMUTE" | "MUTE" => Ok(Code::AudioVolumeMute),
+        "VOLUMEUP" | "AUDIOVOLUMEUP" => Ok(Code::AudioVolumeUp),
+        "VOLUMEDOWN" | "AUDIOVOLUMEDOWN" => Ok(Code::AudioVolumeDown),
+        "MEDIAPLAY" => Ok(Code::MediaPlay),
+        "MEDIAPAUSE" => Ok(Code::MediaPause),
+        "MEDIAPLAYPAUSE" => Ok(Code::MediaPlayPause),
+        "MEDIASTOP" => Ok(Code::MediaStop),
+        "MEDIANEXT" | "MEDIATRACKNEXT" => Ok(Code::MediaTrackNext),
+        "MEDIAPREV" | "MEDIAPREVIOUS" | "MEDIATRACKPREVIOUS" => Ok(Code::MediaTrackPrevious),
+        other => Err(HotkeyError::UnknownKey(other.to_string())),
     }
 }
 
+/// The canonical name of a key `parse_key_code` accepts.
+fn key_name(code: Code) -> Option<&'static str> {
+    let name = match code {
+        Code::KeyA => "A",
+        Code::KeyB => "B",
+        Code::KeyC => "C",
+        Code::KeyD => "D",
+        Code::KeyE => "E",
+        Code::KeyF => "F",
+        Code::KeyG => "G",
+        Code::KeyH => "H",
+        Code::KeyI => "I",
+        Code::KeyJ => "J",
+        Code::KeyK => "K",
+        Code::KeyL => "L",
+        Code::KeyM => "M",
+        Code::KeyN => "N",
+        Code::KeyO => "O",
+        Code::KeyP => "P",
+        Code::KeyQ => "Q",
+        Code::KeyR => "R",
+        Code::KeyS => "S",
+        Code::KeyT => "T",
+        Code::KeyU => "U",
+        Code::KeyV => "V",
+        Code::KeyW => "W",
+        Code::KeyX => "X",
+        Code::KeyY => "Y",
+        Code::KeyZ => "Z",
+        Code::Digit0 => "0",
+        Code::Digit1 => "1",
+        Code::Digit2 => "2",
+        Code::Digit3 => "3",
+        Code::Digit4 => "4",
+        Code::Digit5 => "5",
+        Code::Digit6 => "6",
+        Code::Digit7 => "7",
+        Code::Digit8 => "8",
+        Code::Digit9 => "9",
+        Code::F1 => "F1",
+        Code::F2 => "F2",
+        Code::F3 => "F3",
+        Code::F4 => "F4",
+        Code::F5 => "F5",
+        Code::F6 => "F6",
+        Code::F7 => "F7",
+        Code::F8 => "F8",
+        Code::F9 => "F9",
+        Code::F10 => "F10",
+        Code::F11 => "F11",
+        Code::F12 => "F12",
+        Code::Numpad0 => "Numpad0",
+        Code::Numpad1 => "Numpad1",
+        Code::Numpad2 => "Numpad2",
+        Code::Numpad3 => "Numpad3",
+        Code::Numpad4 => "Numpad4",
+        Code::Numpad5 => "Numpad5",
+        Code::Numpad6 => "Numpad6",
+        Code::Numpad7 => "Numpad7",
+        Code::Numpad8 => "Numpad8",
+        Code::Numpad9 => "Numpad9",
+        Code::NumpadAdd => "NumpadAdd",
+        Code::NumpadSubtract => "NumpadSubtract",
+        Code::NumpadMultiply => "NumpadMultiply",
+        Code::NumpadDivide => "NumpadDivide",
+        Code::NumpadDecimal => "NumpadDecimal",
+        Code::Minus => "Minus",
+        Code::Equal => "Equal",
+        Code::BracketLeft => "BracketLeft",
+        Code::BracketRight => "BracketRight",
+        Code::Semicolon => "Semicolon",
+        Code::Quote => "Quote",
+        Code::Backquote => "Backquote",
+        Code::Comma => "Comma",
+        Code::Period => "Period",
+        Code::Slash => "Slash",
+        Code::Backslash => "Backslash",
+        Code::Space => "Space",
+        Code::Enter => "Enter",
+        Code::Escape => "Escape",
+        Code::Tab => "Tab",
+        Code::Backspace => "Backspace",
+        Code::Delete => "Delete",
+        Code::Insert => "Insert",
+        Code::Home => "Home",
+        Code::End => "End",
+        Code::PageUp => "PageUp",
+        Code::PageDown => "PageDown",
+        Code::ArrowUp => "Up",
+        Code::ArrowDown => "Down",
+        Code::ArrowLeft => "Left",
+        Code::ArrowRight => "Right",
+        Code::PrintScreen => "PrintScreen",
+        Code::ScrollLock => "ScrollLock",
+        Code::Pause => "Pause",
+        Code::ContextMenu => "Menu",
+        Code::NumLock => "NumLock",
+        Code::AudioVolumeMute => "VolumeMute",
+        Code::AudioVolumeUp => "VolumeUp",
+        Code::AudioVolumeDown => "VolumeDown",
+        Code::MediaPlay => "MediaPlay",
+        Code::MediaPause => "MediaPause",
+        Code::MediaPlayPause => "MediaPlayPause",
+        Code::MediaStop => "MediaStop",
+        Code::MediaTrackNext => "MediaNext",
+        Code::MediaTrackPrevious => "MediaPrev",
+        _ => return None,
+    };
+    Some(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -118,6 +422,166 @@ mod tests {
         assert_ne!(hk.id(), 0);
     }
 
+    #[test]
+    fn test_parse_hotkey_errors() {
+        assert_eq!(parse_hotkey("  "), Err(HotkeyError::Empty));
+        assert_eq!(parse_hotkey("Hyper+S"), Err(HotkeyError::UnknownModifier("hyper".into())));
+        assert_eq!(parse_hotkey("Ctrl+Nope"), Err(HotkeyError::UnknownKey("NOPE".into())));
+    }
+
+    #[test]
+    fn test_parse_hotkey_unsupported_modifiers() {
+        assert_eq!(parse_hotkey("AltGr+K"), Err(HotkeyError::UnsupportedModifier("AltGr")));
+        assert_eq!(parse_hotkey("Ctrl+ISO_Level3_Shift+K"), Err(HotkeyError::UnsupportedModifier("AltGr")));
+        assert_eq!(parse_hotkey("Compose+S"), Err(HotkeyError::UnsupportedModifier("Compose")));
+        assert_eq!(parse_hotkey("Menu+S"), Err(HotkeyError::UnsupportedModifier("Menu")));
+        let err = parse_hotkey("altgr+k").unwrap_err().to_string();
+        assert!(err.contains("only Ctrl, Alt, Shift and Super"), "{}", err);
+
+        // Menu as the key itself is fine
+        assert_eq!(parse_hotkey("Ctrl+Menu").unwrap().key, Code::ContextMenu);
+    }
+
+    #[test]
+    fn test_parse_optional() {
+        assert_eq!(parse_optional("fullscreen", Some("Ctrl+F")), parse_hotkey("Ctrl+F").ok());
+        assert_eq!(parse_optional("fullscreen", Some("Hyper+F")), None);
+        assert_eq!(parse_optional("fullscreen", None), None);
+    }
+
+    #[test]
+    fn test_with_shift() {
+        let hk = parse_hotkey("Ctrl+Alt+S").unwrap();
+        let shifted = with_shift(hk).unwrap();
+        assert_eq!(shifted, parse_hotkey("Ctrl+Alt+Shift+S").unwrap());
+        assert!(with_shift(shifted).is_none());
+    }
+
+    #[test]
+    fn test_parse_extra_key_codes() {
+        let cases = [
+            ("PrintScreen", Code::PrintScreen),
+            ("ScrollLock", Code::ScrollLock),
+            ("Pause", Code::Pause),
+            ("Menu", Code::ContextMenu),
+            ("NumLock", Code::NumLock),
+            ("VolumeMute", Code::AudioVolumeMute),
+            ("VolumeUp", Code::AudioVolumeUp),
+            ("VolumeDown", Code::AudioVolumeDown),
+            ("MediaPlayPause", Code::MediaPlayPause),
+            ("MediaNext", Code::MediaTrackNext),
+            ("MediaPrev", Code::MediaTrackPrevious),
+        ];
+        for (name, code) in cases {
+            assert_eq!(parse_key_code(name).unwrap(), code, "{}", name);
+        }
+        assert_eq!(parse_hotkey("Ctrl+Print").unwrap().key, Code::PrintScreen);
+    }
+
+    #[test]
+    fn test_parse_numpad_and_punctuation() {
+        let cases = [
+            ("Numpad0", Code::Numpad0),
+            ("numpad5", Code::Numpad5),
+            ("KP9", Code::Numpad9),
+            ("NumpadAdd", Code::NumpadAdd),
+            ("numpadsubtract", Code::NumpadSubtract),
+            ("NumpadMultiply", Code::NumpadMultiply),
+            ("NumpadDivide", Code::NumpadDivide),
+            ("NumpadDecimal", Code::NumpadDecimal),
+            ("Minus", Code::Minus),
+            ("-", Code::Minus),
+            ("Equal", Code::Equal),
+            ("BracketLeft", Code::BracketLeft),
+            ("]", Code::BracketRight),
+            ("semicolon", Code::Semicolon),
+            ("Quote", Code::Quote),
+            ("Backquote", Code::Backquote),
+            ("Comma", Code::Comma),
+            ("PERIOD", Code::Period),
+            ("Slash", Code::Slash),
+            ("\\", Code::Backslash),
+        ];
+        for (name, code) in cases {
+            assert_eq!(parse_key_code(name).unwrap(), code, "{}", name);
+        }
+        assert_eq!(parse_hotkey("Ctrl+Alt+Numpad5").unwrap().key, Code::Numpad5);
+        assert_eq!(parse_hotkey("Super+Minus").unwrap().key, Code::Minus);
+        assert_eq!(hotkey_to_string(&parse_hotkey("ctrl+;").unwrap()), "Ctrl+Semicolon");
+    }
+
+    #[test]
+    fn test_describe_register_error_reserved_key() {
+        let hk = parse_hotkey("PrintScreen").unwrap();
+        let msg = describe_register_error("PrintScreen", &global_hotkey::Error::AlreadyRegistered(hk));
+        assert!(msg.contains("already taken"));
+        assert!(msg.contains("screenshot tool"), "{}", msg);
+
+        let hk = parse_hotkey("Ctrl+Alt+S").unwrap();
+        let msg = describe_register_error("Ctrl+Alt+S", &global_hotkey::Error::AlreadyRegistered(hk));
+        assert!(msg.contains("already taken") && !msg.contains("screenshot tool"), "{}", msg);
+    }
+
+    #[test]
+    fn test_parse_bare_print_and_media_keys() {
+        for name in ["Print", "PrintScreen", "print", "PRINTSCREEN"] {
+            let hk = parse_hotkey(name).unwrap();
+            assert_eq!((hk.mods, hk.key), (Modifiers::empty(), Code::PrintScreen), "{}", name);
+        }
+        let cases = [
+            ("VolumeUp", Code::AudioVolumeUp),
+            ("VolumeDown", Code::AudioVolumeDown),
+            ("Mute", Code::AudioVolumeMute),
+            ("mediaplaypause", Code::MediaPlayPause),
+        ];
+        for (name, code) in cases {
+            let hk = parse_hotkey(name).unwrap();
+            assert_eq!((hk.mods, hk.key), (Modifiers::empty(), code), "{}", name);
+        }
+        assert_eq!(hotkey_to_string(&parse_hotkey("Mute").unwrap()), "VolumeMute");
+    }
+
+    #[test]
+    fn test_action_registry_dispatch() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let fired = Rc::new(Cell::new(0));
+        let mut registry = ActionRegistry::new();
+        let counter = fired.clone();
+        let id = registry.insert(parse_hotkey("Ctrl+Alt+T").unwrap(), move || counter.set(counter.get() + 1));
+
+        assert!(registry.dispatch(id));
+        assert_eq!(fired.get(), 1);
+        assert!(!registry.dispatch(id.wrapping_add(1)));
+        assert_eq!(fired.get(), 1);
+    }
+
+    #[test]
+    fn test_hotkey_to_string_round_trips() {
+        let cases = [
+            ("Ctrl+Super+S", "Ctrl+Super+S"),
+            ("super+ctrl+s", "Ctrl+Super+S"),
+            ("Shift + Alt + Control + Win + 5", "Ctrl+Alt+Shift+Super+5"),
+            ("Meta+Digit7", "Super+7"),
+            ("Alt+h", "Alt+H"),
+            ("Ctrl+Print", "Ctrl+PrintScreen"),
+            ("Ctrl+Alt+ArrowLeft", "Ctrl+Alt+Left"),
+            ("ctrl+esc", "Ctrl+Escape"),
+            ("Alt+Return", "Alt+Enter"),
+            ("Ctrl+ContextMenu", "Ctrl+Menu"),
+            ("AudioVolumeUp", "VolumeUp"),
+            ("Super+MediaTrackPrevious", "Super+MediaPrev"),
+            ("F12", "F12"),
+        ];
+        for (input, canonical) in cases {
+            let hotkey = parse_hotkey(input).unwrap();
+            assert_eq!(hotkey_to_string(&hotkey), canonical, "{}", input);
+            // The canonical form parses to the same hotkey
+            assert_eq!(parse_hotkey(canonical).unwrap(), hotkey, "{}", canonical);
+        }
+    }
+
     #[test]
     fn test_parse_hotkey_invalid() {
         assert!(parse_hotkey("").is_err());
@@ -0,0 +1,74 @@
+//! CopyQ history exported as tab-separated text.
+//!
+//! CopyQ's own `.cpq` export is a binary Qt stream, so the supported input is
+//! a plain listing (e.g. written by a `copyq eval` script), one item per line:
+//! an optional Unix timestamp and a tab, then the text with `\n`, `\t`,
+//! `\r` and `\\` escaped. Lines that fail to unescape are skipped.
+
+use super::{ImportedItem, Parsed};
+
+pub fn parse(content: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    for line in content.lines() {
+        if line.is_empty() {
+            continue;
+        }
+        let (created_at, text) = match line.split_once('\t') {
+            Some((ts, rest)) => match ts.parse::<i64>() {
+                Ok(ts) => (Some(ts), rest),
+                Err(_) => (None, line),
+            },
+            None => (None, line),
+        };
+        match unescape(text) {
+            Some(text) if !text.is_empty() => parsed.items.push(ImportedItem { text, created_at }),
+            _ => parsed.skipped += 1,
+        }
+    }
+    parsed
+}
+
+/// Undo `\n`, `\t`, `\r` and `\\` escapes; `None` for an unknown escape.
+fn unescape(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            '\\' => out.push('\\'),
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "1700000000\tfirst line\\nsecond line\n\
+                          plain item with\\ttab\n\
+                          \n\
+                          1700000100\tC:\\\\path\n\
+                          bad \\q escape\n";
+
+    #[test]
+    fn test_parse_copyq_listing() {
+        let parsed = parse(SAMPLE);
+        assert_eq!(
+            parsed.items,
+            [
+                ImportedItem { text: "first line\nsecond line".into(), created_at: Some(1_700_000_000) },
+                ImportedItem { text: "plain item with\ttab".into(), created_at: None },
+                ImportedItem { text: "C:\\path".into(), created_at: Some(1_700_000_100) },
+            ]
+        );
+        assert_eq!(parsed.skipped, 1);
+    }
+}
@@ -0,0 +1,95 @@
+//! GPaste's `history.xml` (usually in `~/.local/share/gpaste/`).
+//!
+//! ```xml
+//! <history version="2.0">
+//!   <item kind="Text" uuid="..."><value><![CDATA[hello]]></value></item>
+//! </history>
+//! ```
+//!
+//! Text and URI items are imported; images and passwords are skipped. The
+//! file keeps no timestamps, and lists the newest item first.
+
+use super::{ImportedItem, Parsed};
+use anyhow::{anyhow, Result};
+
+pub fn parse(content: &str) -> Result<Parsed> {
+    if !content.contains("<history") {
+        return Err(anyhow!("Not a GPaste history file (no <history> element)"));
+    }
+
+    let mut parsed = Parsed::default();
+    let mut rest = content;
+    while let Some(start) = rest.find("<item") {
+        let Some(len) = rest[start..].find("</item>") else {
+            parsed.skipped += 1;
+            break;
+        };
+        let item = &rest[start..start + len];
+        rest = &rest[start + len + "</item>".len()..];
+
+        let kind = attribute(item, "kind").unwrap_or("");
+        let text = match kind {
+            "Text" | "Uris" => value(item),
+            _ => None,
+        };
+        match text {
+            Some(text) if !text.is_empty() => parsed.items.push(ImportedItem { text, created_at: None }),
+            _ => parsed.skipped += 1,
+        }
+    }
+    Ok(parsed)
+}
+
+/// Value of `name="..."` in the item's opening tag.
+fn attribute<'a>(item: &'a str, name: &str) -> Option<&'a str> {
+    let tag = &item[..item.find('>')?];
+    let start = tag.find(&format!("{}=\"", name))? + name.len() + 2;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+/// Contents of the item's `<value>`, from CDATA or entity-escaped text.
+fn value(item: &str) -> Option<String> {
+    let start = item.find("<value>")? + "<value>".len();
+    let len = item[start..].find("</value>")?;
+    let raw = &item[start..start + len];
+    match raw.strip_prefix("<![CDATA[").and_then(|r| r.strip_suffix("]]>")) {
+        Some(cdata) => Some(cdata.to_string()),
+        None => Some(unescape_xml(raw)),
+    }
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<history version="2.0">
+  <item kind="Text" uuid="a1"><value><![CDATA[newest <copy>]]></value></item>
+  <item kind="Password" uuid="a2" name="bank"><value><![CDATA[hunter2]]></value></item>
+  <item kind="Image" uuid="a3" date="0"><value><![CDATA[/home/me/.local/share/gpaste/images/x.png]]></value></item>
+  <item kind="Uris" uuid="a4"><value>file:///tmp/a&amp;b</value></item>
+</history>
+"#;
+
+    #[test]
+    fn test_parse_gpaste_history() {
+        let parsed = parse(SAMPLE).unwrap();
+        let texts: Vec<_> = parsed.items.iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, ["newest <copy>", "file:///tmp/a&b"]);
+        assert_eq!(parsed.skipped, 2);
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(parse("1700000000\tnot xml").is_err());
+    }
+}
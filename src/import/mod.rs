@@ -0,0 +1,88 @@
+//! `clipsnap import`: bring history over from other clipboard managers.
+
+mod copyq;
+mod gpaste;
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::StorageMode;
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+
+/// One item read from another manager's history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedItem {
+    pub text: String,
+    /// Unix timestamp, when the source records one.
+    pub created_at: Option<i64>,
+}
+
+/// Items parsed from a source file, plus how many were not importable
+/// (images, passwords, unknown kinds, malformed lines).
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub items: Vec<ImportedItem>,
+    pub skipped: usize,
+}
+
+/// Parse `content` in the format of `source` ("copyq" or "gpaste").
+pub fn parse(source: &str, content: &str) -> Result<Parsed> {
+    match source {
+        "copyq" => Ok(copyq::parse(content)),
+        "gpaste" => gpaste::parse(content),
+        other => Err(anyhow!("Unknown import source {:?} (expected \"copyq\" or \"gpaste\")", other)),
+    }
+}
+
+/// Parse `path` and store its items. Items without a timestamp are given
+/// consecutive ones ending now, keeping the source's newest-first order.
+/// Returns `(imported, skipped)`.
+pub fn import_file(db: &Database, source: &str, path: &Path) -> Result<(usize, usize)> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let parsed = parse(source, &content)?;
+
+    let now = chrono::Utc::now().timestamp();
+    let rows: Vec<(String, i64)> = parsed
+        .items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let created_at = item.created_at.unwrap_or(now - i as i64);
+            (item.text, created_at)
+        })
+        .collect();
+    let imported = db.insert_batch(&rows)?;
+    Ok((imported, parsed.skipped))
+}
+
+/// Run `clipsnap import --from copyq|gpaste <path>` with the arguments after
+/// `import`. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let (source, path) = match args {
+        [flag, source, path] if flag == "--from" => (source.as_str(), Path::new(path)),
+        _ => {
+            eprintln!("Usage: clipsnap import --from copyq|gpaste <path>");
+            return 2;
+        }
+    };
+
+    let result = Config::load_or_create_default().map_err(anyhow::Error::from).and_then(|config| {
+        // A running ClipSnap's memory-only history can't be reached from here
+        if config.storage_mode() == StorageMode::Memory {
+            return Err(anyhow!("storage.mode is \"memory\"; there is no history file to import into"));
+        }
+        let mut db = Database::new(&config.resolved_db_path())?;
+        db.set_text_compression(config.storage.compress_text.then_some(config.storage.compress_text_min_bytes));
+        import_file(&db, source, path)
+    });
+    match result {
+        Ok((imported, skipped)) => {
+            println!("Imported {} entries, skipped {}.", imported, skipped);
+            0
+        }
+        Err(e) => {
+            eprintln!("Import failed: {:#}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use crate::clipboard::{self, RecentHashes};
+use crate::database::{Database, DatabaseError};
+use crate::events::{self, EntryEvent};
+use crate::models::ContentType;
+use crate::screenshot;
+use anyhow::{anyhow, Context, Result};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bound on a single socket request (command line plus payload).
+const MAX_REQUEST_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How long a client may go quiet while sending its request before the
+/// connection is dropped.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Entry point for content pushed in by external tools.
+///
+/// Shares the monitor's recent hashes, so content added here is not stored a
+/// second time when it later shows up on the clipboard, and re-adding the
+/// newest entry returns its existing id.
+#[derive(Clone)]
+pub struct Ingest {
+    db: Arc<Mutex<Database>>,
+    last_text_hash: Arc<Mutex<RecentHashes>>,
+    last_image_hash: Arc<Mutex<RecentHashes>>,
+    /// Must match the monitor's `capture.dedup_flatten_alpha`, as the image
+    /// hashes are shared.
+    flatten_alpha: bool,
+}
+
+impl Ingest {
+    pub fn new(
+        db: Arc<Mutex<Database>>,
+        last_text_hash: Arc<Mutex<RecentHashes>>,
+        last_image_hash: Arc<Mutex<RecentHashes>>,
+        flatten_alpha: bool,
+    ) -> Self {
+        Ingest {
+            db,
+            last_text_hash,
+            last_image_hash,
+            flatten_alpha,
+        }
+    }
+
+    /// Store a text entry. Returns the new (or deduplicated) row id.
+    pub fn add_text(&self, text: &str) -> Result<i64> {
+        if text.is_empty() {
+            return Err(anyhow!("Text is empty"));
+        }
+        let hash = clipboard::content_hash(text.as_bytes());
+        self.store(&self.last_text_hash, hash, ContentType::Text, |db| {
+            let id = db.insert_text(text)?;
+            events::publish(&EntryEvent::text(id, text));
+            Ok(id)
+        })
+    }
+
+    /// Store an encoded image entry (PNG, JPEG, GIF or BMP, told apart by
+    /// its magic bytes). Anything but PNG is converted to PNG, and the
+    /// original type is kept in the entry's metadata. Returns the new (or
+    /// deduplicated) row id.
+    pub fn add_image(&self, bytes: &[u8]) -> Result<i64> {
+        let image = screenshot::convert_to_png(bytes).context("Image could not be decoded")?;
+        let hash = clipboard::image_hash(&image.rgba, self.flatten_alpha);
+        let thumb = screenshot::create_thumbnail_from_rgba(&image.rgba, image.width, image.height, screenshot::thumbnail_px())
+            .unwrap_or_default();
+        self.store(&self.last_image_hash, hash, ContentType::Image, |db| {
+            let id =
+                db.insert_converted_image(&image.png, &thumb, image.width, image.height, image.source_format.to_mime_type())?;
+            events::publish(&EntryEvent::image(id, image.width, image.height));
+            Ok(id)
+        })
+    }
+
+    fn store(
+        &self,
+        last_hash: &Mutex<RecentHashes>,
+        hash: String,
+        content_type: ContentType,
+        insert: impl FnOnce(&Database) -> Result<i64, DatabaseError>,
+    ) -> Result<i64> {
+        let mut last = last_hash.lock().map_err(|_| anyhow!("Dedup state poisoned"))?;
+        let db = self.db.lock().map_err(|_| anyhow!("Database lock poisoned"))?;
+        // Only the newest entry's id is known; older matches are re-added
+        if last.latest() == Some(hash.as_str()) {
+            if let Some(id) = db.latest_entry_id(content_type)? {
+                return Ok(id);
+            }
+        }
+        let id = insert(&db)?;
+        last.remember(hash);
+        Ok(id)
+    }
+}
+
+/// Path of the control socket, private to the current user's runtime dir.
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("clipsnap.sock")
+}
+
+/// Listen on the control socket in a background thread. Each connection is
+/// served on a thread of its own, so a slow client doesn't hold up others.
+///
+/// Protocol: one command line (`ADD_TEXT` or `ADD_IMAGE`) followed by the raw
+/// payload until the client shuts down its write half. The reply is a single
+/// line, `OK <id>` or `ERR <message>`.
+///
+/// `SUBSCRIBE` (no payload) instead keeps the connection open and writes one
+/// JSON line per newly stored entry; see `events`.
+pub fn serve_socket(path: &Path, ingest: Ingest) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path).with_context(|| format!("Failed to remove stale socket: {:?}", path))?;
+    }
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("Failed to bind control socket: {:?}", path))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict socket permissions: {:?}", path))?;
+    log::info!("Control socket listening on {:?}", path);
+
+    std::thread::Builder::new()
+        .name("ipc-socket".into())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let ingest = ingest.clone();
+                        let spawned = std::thread::Builder::new().name("ipc-client".into()).spawn(move || {
+                            if let Err(e) = handle_connection(stream, &ingest) {
+                                log::warn!("Control socket request failed: {}", e);
+                            }
+                        });
+                        if let Err(e) = spawned {
+                            log::warn!("Failed to spawn control socket handler: {}", e);
+                        }
+                    }
+                    Err(e) => log::warn!("Control socket accept failed: {}", e),
+                }
+            }
+        })
+        .context("Failed to spawn control socket thread")?;
+    Ok(())
+}
+
+fn handle_connection(stream: UnixStream, ingest: &Ingest) -> Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?.take(MAX_REQUEST_BYTES));
+    let mut command = String::new();
+    reader.read_line(&mut command)?;
+    let mut payload = Vec::new();
+    reader.read_to_end(&mut payload)?;
+
+    if command.trim() == "SUBSCRIBE" {
+        stream_entries(stream, events::subscribe());
+        return Ok(());
+    }
+
+    let reply = handle_request(ingest, command.trim(), &payload);
+    let mut stream = stream;
+    stream.write_all(reply.as_bytes())?;
+    Ok(())
+}
+
+/// Write each published entry to a subscriber until it disconnects.
+fn stream_entries(mut stream: UnixStream, feed: Receiver<String>) {
+    for line in feed {
+        if writeln!(stream, "{}", line).is_err() {
+            log::debug!("Tail subscriber disconnected");
+            return;
+        }
+    }
+}
+
+/// `clipsnap tail`: print new history entries as JSON lines until the
+/// daemon exits or the output is closed.
+pub fn tail() -> i32 {
+    let path = socket_path();
+    let result = UnixStream::connect(&path)
+        .with_context(|| format!("Failed to connect to {:?}; is ClipSnap running?", path))
+        .and_then(|mut stream| {
+            stream.write_all(b"SUBSCRIBE\n")?;
+            stream.shutdown(std::net::Shutdown::Write)?;
+            let mut stdout = std::io::stdout().lock();
+            for line in BufReader::new(stream).lines() {
+                writeln!(stdout, "{}", line?)?;
+                stdout.flush()?;
+            }
+            Ok(())
+        });
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Tail failed: {:#}", e);
+            1
+        }
+    }
+}
+
+/// Execute a single socket command and format the reply line.
+fn handle_request(ingest: &Ingest, command: &str, payload: &[u8]) -> String {
+    let result = match command {
+        "ADD_TEXT" => std::str::from_utf8(payload)
+            .context("Text is not valid UTF-8")
+            .and_then(|text| ingest.add_text(text)),
+        "ADD_IMAGE" => ingest.add_image(payload),
+        other => Err(anyhow!("Unknown command: {}", other)),
+    };
+    match result {
+        Ok(id) => format!("OK {}\n", id),
+        Err(e) => format!("ERR {}\n", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ingest() -> Ingest {
+        Ingest::new(
+            Arc::new(Mutex::new(Database::new_in_memory().unwrap())),
+            Arc::new(Mutex::new(RecentHashes::new(1))),
+            Arc::new(Mutex::new(RecentHashes::new(1))),
+            false,
+        )
+    }
+
+    #[test]
+    fn test_add_text_dedups_repeated_content() {
+        let ingest = ingest();
+        let first = ingest.add_text("snippet").unwrap();
+        let second = ingest.add_text("snippet").unwrap();
+        assert_eq!(first, second);
+        assert_ne!(ingest.add_text("other").unwrap(), first);
+    }
+
+    #[test]
+    fn test_add_image_rejects_non_image() {
+        let ingest = ingest();
+        assert!(ingest.add_image(b"definitely not a png").is_err());
+    }
+
+    #[test]
+    fn test_add_image_converts_bmp() {
+        let ingest = ingest();
+        let mut bmp = Vec::new();
+        image::codecs::bmp::BmpEncoder::new(&mut bmp)
+            .encode(&[255u8; 3 * 2 * 4], 3, 2, image::ColorType::Rgba8)
+            .unwrap();
+        let id = ingest.add_image(&bmp).unwrap();
+        let entry = ingest.db.lock().unwrap().get_entry(id).unwrap().unwrap();
+        assert_eq!(entry.source_mime.as_deref(), Some("image/bmp"));
+        assert_eq!((entry.width, entry.height), (Some(3), Some(2)));
+        let png = entry.image_data.unwrap();
+        assert_eq!(screenshot::detect_format(&png), Some(image::ImageFormat::Png));
+    }
+
+    #[test]
+    fn test_add_image_stores_png() {
+        let ingest = ingest();
+        let png = screenshot::encode_png(&[0u8; 4 * 4 * 4], 4, 4).unwrap();
+        let id = ingest.add_image(&png).unwrap();
+        let entry = ingest.db.lock().unwrap().get_entry(id).unwrap().unwrap();
+        assert_eq!(entry.content_type, ContentType::Image);
+        assert_eq!(entry.image_data.unwrap(), png);
+        assert_eq!(entry.source_mime.as_deref(), Some("image/png"));
+    }
+
+    #[test]
+    fn test_stuck_client_does_not_block_others() {
+        let path = std::env::temp_dir().join(format!("clipsnap_ipc_{}.sock", std::process::id()));
+        serve_socket(&path, ingest()).unwrap();
+
+        // Connects but never finishes its request
+        let mut stuck = UnixStream::connect(&path).unwrap();
+        stuck.write_all(b"ADD_TEXT\n").unwrap();
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.write_all(b"ADD_TEXT\nhello").unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(reply.starts_with("OK "), "{}", reply);
+    }
+
+    #[test]
+    fn test_handle_request() {
+        let ingest = ingest();
+        assert!(handle_request(&ingest, "ADD_TEXT", b"hello").starts_with("OK "));
+        assert!(handle_request(&ingest, "ADD_TEXT", b"").starts_with("ERR "));
+        assert!(handle_request(&ingest, "REMOVE", b"").starts_with("ERR Unknown command"));
+    }
+}
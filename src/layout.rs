@@ -0,0 +1,272 @@
+//! Where the capture overlay sits on the desktop, and how its coordinates
+//! map to the screen pixels X11 captures.
+//!
+//! The overlay spans the bounding box of all monitors. GDK reports monitor
+//! geometry in logical pixels; the screen is read in device pixels, which are
+//! logical pixels times the scale factor. Overlay coordinates are logical and
+//! start at the bounding box's top-left corner, which can be left of or
+//! above the X11 origin.
+
+use crate::models::Rectangle;
+
+/// A monitor as GDK reports it: its geometry in logical pixels and its
+/// scale factor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Monitor {
+    pub geometry: Rectangle,
+    pub scale: i32,
+}
+
+/// The monitors as the overlay sees them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DesktopLayout {
+    /// Bounding box of all monitors, in logical pixels.
+    pub bounds: Rectangle,
+    /// Device pixels per logical pixel. X11 has one scale for the whole
+    /// screen; should the monitors disagree, the primary one's is used.
+    pub scale: f64,
+    /// The first monitor, taken as the primary one (see
+    /// `ui::overlay::primary_monitor`).
+    pub primary: Option<Rectangle>,
+}
+
+impl DesktopLayout {
+    pub fn new(monitors: &[Monitor]) -> Self {
+        let Some(first) = monitors.first() else {
+            return DesktopLayout {
+                bounds: Rectangle { x: 0, y: 0, width: 0, height: 0 },
+                scale: 1.0,
+                primary: None,
+            };
+        };
+        let edges = |m: &Monitor| {
+            let g = m.geometry;
+            (g.x as i64, g.y as i64, g.x as i64 + g.width as i64, g.y as i64 + g.height as i64)
+        };
+        let (mut left, mut top, mut right, mut bottom) = edges(first);
+        for monitor in &monitors[1..] {
+            let (l, t, r, b) = edges(monitor);
+            left = left.min(l);
+            top = top.min(t);
+            right = right.max(r);
+            bottom = bottom.max(b);
+        }
+        DesktopLayout {
+            bounds: Rectangle {
+                x: left as i32,
+                y: top as i32,
+                width: (right - left) as u32,
+                height: (bottom - top) as u32,
+            },
+            scale: first.scale.max(1) as f64,
+            primary: Some(first.geometry),
+        }
+    }
+
+    /// Centre of the primary monitor in overlay coordinates, or of the whole
+    /// desktop without monitors.
+    pub fn primary_center(&self) -> (f64, f64) {
+        let area = self.primary.unwrap_or(self.bounds);
+        (
+            (area.x - self.bounds.x) as f64 + area.width as f64 / 2.0,
+            (area.y - self.bounds.y) as f64 + area.height as f64 / 2.0,
+        )
+    }
+
+    /// The screen pixel under an overlay point.
+    pub fn screen_point(&self, (x, y): (f64, f64)) -> (i32, i32) {
+        (
+            ((self.bounds.x as f64 + x) * self.scale).round() as i32,
+            ((self.bounds.y as f64 + y) * self.scale).round() as i32,
+        )
+    }
+
+    /// The overlay point over a screen pixel.
+    pub fn overlay_point(&self, (x, y): (i32, i32)) -> (f64, f64) {
+        (
+            x as f64 / self.scale - self.bounds.x as f64,
+            y as f64 / self.scale - self.bounds.y as f64,
+        )
+    }
+
+    /// The screen pixels between two opposite overlay corners.
+    pub fn screen_rect(&self, a: (f64, f64), b: (f64, f64)) -> Rectangle {
+        let (x0, y0) = self.screen_point((a.0.min(b.0), a.1.min(b.1)));
+        let (x1, y1) = self.screen_point((a.0.max(b.0), a.1.max(b.1)));
+        Rectangle {
+            x: x0,
+            y: y0,
+            width: (x1 - x0) as u32,
+            height: (y1 - y0) as u32,
+        }
+    }
+
+    /// The whole desktop in screen pixels.
+    pub fn screen_bounds(&self) -> Rectangle {
+        let (width, height) = (self.bounds.width as f64, self.bounds.height as f64);
+        self.screen_rect((0.0, 0.0), (width, height))
+    }
+
+    /// A rectangle of screen pixels as `(x, y, width, height)` in overlay
+    /// coordinates.
+    pub fn overlay_rect(&self, rect: Rectangle) -> (f64, f64, f64, f64) {
+        let (x, y) = self.overlay_point((rect.x, rect.y));
+        (x, y, rect.width as f64 / self.scale, rect.height as f64 / self.scale)
+    }
+
+    /// Top-left overlay corner of a `width`×`height` image shown one image
+    /// pixel per device pixel, centred on the primary monitor.
+    pub fn place_image(&self, width: u32, height: u32) -> (f64, f64) {
+        let (cx, cy) = self.primary_center();
+        (cx - width as f64 / self.scale / 2.0, cy - height as f64 / self.scale / 2.0)
+    }
+
+    /// The pixels of an image placed at `origin` (see `place_image`) between
+    /// two opposite overlay corners. May reach past the image's edges.
+    pub fn image_rect(&self, a: (f64, f64), b: (f64, f64), origin: (f64, f64)) -> Rectangle {
+        let corner = |x: f64, y: f64| {
+            (
+                ((x - origin.0) * self.scale).round() as i32,
+                ((y - origin.1) * self.scale).round() as i32,
+            )
+        };
+        let (x0, y0) = corner(a.0.min(b.0), a.1.min(b.1));
+        let (x1, y1) = corner(a.0.max(b.0), a.1.max(b.1));
+        Rectangle {
+            x: x0,
+            y: y0,
+            width: (x1 - x0) as u32,
+            height: (y1 - y0) as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(x: i32, y: i32, width: u32, height: u32, scale: i32) -> Monitor {
+        Monitor {
+            geometry: Rectangle { x, y, width, height },
+            scale,
+        }
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        Rectangle { x, y, width, height }
+    }
+
+    /// Monitor layouts, as GDK reports them.
+    fn fixtures() -> Vec<(&'static str, Vec<Monitor>)> {
+        vec![
+            ("single", vec![monitor(0, 0, 1920, 1080, 1)]),
+            ("side by side", vec![monitor(0, 0, 1920, 1080, 1), monitor(1920, 0, 2560, 1440, 1)]),
+            ("stacked", vec![monitor(0, 1080, 1920, 1080, 1), monitor(0, 0, 1920, 1080, 1)]),
+            ("hidpi", vec![monitor(0, 0, 1920, 1080, 2), monitor(1920, 0, 1280, 720, 2)]),
+            ("mixed dpi", vec![monitor(0, 0, 1920, 1080, 2), monitor(1920, 0, 1920, 1080, 1)]),
+            ("negative offset", vec![monitor(0, 0, 1920, 1080, 1), monitor(-1280, -200, 1280, 1024, 1)]),
+            ("negative offset, hidpi", vec![monitor(-960, 0, 960, 540, 2), monitor(0, 0, 1920, 1080, 2)]),
+        ]
+    }
+
+    #[test]
+    fn test_bounds() {
+        let bounds: Vec<Rectangle> = fixtures().iter().map(|(_, m)| DesktopLayout::new(m).bounds).collect();
+        assert_eq!(
+            bounds,
+            [
+                rect(0, 0, 1920, 1080),
+                rect(0, 0, 4480, 1440),
+                rect(0, 0, 1920, 2160),
+                rect(0, 0, 3200, 1080),
+                rect(0, 0, 3840, 1080),
+                rect(-1280, -200, 3200, 1280),
+                rect(-960, 0, 2880, 1080),
+            ]
+        );
+        // The primary monitor's scale wins
+        let scales: Vec<f64> = fixtures().iter().map(|(_, m)| DesktopLayout::new(m).scale).collect();
+        assert_eq!(scales, [1.0, 1.0, 1.0, 2.0, 2.0, 1.0, 2.0]);
+
+        let empty = DesktopLayout::new(&[]);
+        assert_eq!((empty.bounds, empty.scale, empty.primary), (rect(0, 0, 0, 0), 1.0, None));
+    }
+
+    #[test]
+    fn test_primary_monitor_maps_to_its_screen_pixels() {
+        for (name, monitors) in fixtures() {
+            let layout = DesktopLayout::new(&monitors);
+            let primary = monitors[0].geometry;
+            let scale = layout.scale as i32;
+            let (x, y) = layout.overlay_point((primary.x * scale, primary.y * scale));
+            let (w, h) = (primary.width as f64, primary.height as f64);
+            assert_eq!(
+                layout.screen_rect((x, y), (x + w, y + h)),
+                rect(primary.x * scale, primary.y * scale, primary.width * scale as u32, primary.height * scale as u32),
+                "{}",
+                name
+            );
+            let (cx, cy) = layout.primary_center();
+            assert_eq!((cx, cy), (x + w / 2.0, y + h / 2.0), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (name, monitors) in fixtures() {
+            let layout = DesktopLayout::new(&monitors);
+            let screen = layout.screen_bounds();
+            for point in [(screen.x, screen.y), (0, 0), (screen.x + 17, screen.y + 301)] {
+                assert_eq!(layout.screen_point(layout.overlay_point(point)), point, "{}", name);
+            }
+            // The overlay's corner is the desktop's corner
+            assert_eq!(layout.screen_point((0.0, 0.0)), (screen.x, screen.y), "{}", name);
+            let window = rect(screen.x + 40, screen.y + 60, 800, 600);
+            let (x, y, w, h) = layout.overlay_rect(window);
+            assert_eq!(layout.screen_rect((x, y), (x + w, y + h)), window, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_screen_bounds() {
+        let bounds: Vec<Rectangle> = fixtures().iter().map(|(_, m)| DesktopLayout::new(m).screen_bounds()).collect();
+        assert_eq!(
+            bounds,
+            [
+                rect(0, 0, 1920, 1080),
+                rect(0, 0, 4480, 1440),
+                rect(0, 0, 1920, 2160),
+                rect(0, 0, 6400, 2160),
+                rect(0, 0, 7680, 2160),
+                rect(-1280, -200, 3200, 1280),
+                rect(-1920, 0, 5760, 2160),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selection_drawn_backwards() {
+        let layout = DesktopLayout::new(&[monitor(-1280, 0, 1280, 1024, 2), monitor(0, 0, 1920, 1080, 2)]);
+        // Dragged from bottom right to top left, on the left monitor
+        assert_eq!(layout.screen_rect((300.5, 200.0), (100.0, 50.0)), rect(-2360, 100, 401, 300));
+    }
+
+    #[test]
+    fn test_image_placement() {
+        for (name, monitors) in fixtures() {
+            let layout = DesktopLayout::new(&monitors);
+            let origin = layout.place_image(400, 300);
+            let scale = layout.scale;
+            let (cx, cy) = layout.primary_center();
+            assert_eq!(origin, (cx - 200.0 / scale, cy - 150.0 / scale), "{}", name);
+
+            // Selecting the image exactly gives all of its pixels
+            let end = (origin.0 + 400.0 / scale, origin.1 + 300.0 / scale);
+            assert_eq!(layout.image_rect(origin, end, origin), rect(0, 0, 400, 300), "{}", name);
+            // A selection hanging off the top left starts before the image
+            let before = (origin.0 - 10.0, origin.1 - 10.0);
+            let rect_before = layout.image_rect(before, end, origin);
+            assert_eq!((rect_before.x, rect_before.y), (-10 * scale as i32, -10 * scale as i32), "{}", name);
+        }
+    }
+}
@@ -1,16 +1,38 @@
+mod autostart;
+mod capture;
 mod clipboard;
+mod color;
 mod config;
 mod database;
+mod dbus;
+mod doctor;
+mod events;
+mod export;
+mod fuzzy;
 mod hotkeys;
+mod import;
+mod ipc;
+mod layout;
 mod models;
 mod notifications;
+mod paste;
+mod portal;
+mod post_capture;
 mod screenshot;
+mod selection;
+mod sensitive;
+mod snippets;
+mod source_app;
+#[cfg(feature = "sync")]
+mod sync;
+mod temp_files;
 mod ui;
 
 use config::Config;
-use database::Database;
 use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
 use gtk4::prelude::*;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -20,6 +42,17 @@ fn main() {
         .format_timestamp_secs()
         .init();
 
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("doctor") => std::process::exit(doctor::run()),
+        Some("import") => std::process::exit(import::run(&args[2..])),
+        Some("export") => std::process::exit(export::run(&args[2..])),
+        Some("tail") => std::process::exit(ipc::tail()),
+        Some("--install-autostart") => std::process::exit(autostart::run_install()),
+        Some("--uninstall-autostart") => std::process::exit(autostart::run_uninstall()),
+        _ => {}
+    }
+
     log::info!("ClipSnap starting…");
 
     // ── Configuration ───────────────────────────────
@@ -31,10 +64,14 @@ fn main() {
     );
 
     // ── Database ────────────────────────────────────
-    let db_path = config.resolved_db_path();
-    let db = Arc::new(Mutex::new(
-        Database::new(&db_path).expect("Failed to initialise database"),
-    ));
+    let (db, repair) = database::open_history(&config).expect("Failed to initialise database");
+    if config.storage_mode() == models::StorageMode::Memory {
+        log::info!("History is kept in memory only (storage.mode = \"memory\")");
+    }
+    if let Some(report) = repair {
+        notifications::notify_database_repaired(&config, &report);
+    }
+    let db = Arc::new(Mutex::new(db));
 
     // Run maintenance on startup
     {
@@ -43,28 +80,131 @@ fn main() {
             let _ = db.cleanup_old_entries(config.history.retention_days);
             let _ = db.enforce_max_entries(config.history.max_entries);
         }
+        if let Some(max_bytes) = config.history.max_db_bytes {
+            match db.enforce_max_bytes(max_bytes) {
+                Ok(0) => {}
+                Ok(removed) => notifications::notify_history_trimmed(&config, removed, max_bytes),
+                Err(e) => log::warn!("Failed to enforce history.max_db_bytes: {:#}", e),
+            }
+        }
+        match snippets::sync_from_file(&db, &snippets::snippets_path()) {
+            Ok(0) => {}
+            Ok(n) => log::info!("Added {} snippet(s) from snippets.toml", n),
+            Err(e) => log::warn!("Failed to load snippets: {:#}", e),
+        }
+    }
+
+    // Captures from earlier runs are no longer needed
+    let removed = temp_files::cleanup();
+    if removed > 0 {
+        log::info!("Removed {} stale temp file(s)", removed);
+    }
+
+    // Fill in width/height for images stored before those columns existed,
+    // and thumbnails that failed to generate when an image was stored
+    {
+        let db = db.clone();
+        std::thread::spawn(move || {
+            let updated = database::backfill_image_dimensions(&db);
+            if updated > 0 {
+                log::info!("Backfilled dimensions for {} image(s)", updated);
+            }
+            let updated = database::backfill_thumbnails(&db);
+            if updated > 0 {
+                log::info!("Regenerated {} missing thumbnail(s)", updated);
+            }
+        });
+    }
+
+    #[cfg(feature = "sync")]
+    sync::spawn(db.clone(), &config.sync);
+    #[cfg(not(feature = "sync"))]
+    if config.sync.enabled {
+        log::warn!("sync.enabled is set, but this build has no sync support (build with --features sync)");
     }
 
     // ── Shared state for deduplication ───────────────
-    let last_text_hash: Arc<Mutex<Option<u64>>> = Arc::new(Mutex::new(None));
-    let last_image_hash: Arc<Mutex<Option<u64>>> = Arc::new(Mutex::new(None));
+    // Lock order, to keep every thread deadlock-free:
+    //   last_*_hash → db
+    // The clipboard lock is never held while taking any other lock: copy
+    // data in or out, drop it, then touch the hashes or the database.
+    let dedup_window = config.capture.dedup_window;
+    let last_text_hash = Arc::new(Mutex::new(clipboard::RecentHashes::new(dedup_window)));
+    let last_image_hash = Arc::new(Mutex::new(clipboard::RecentHashes::new(dedup_window)));
+    clipboard::seed_hashes(&db, models::ContentType::Text, &last_text_hash);
+    clipboard::seed_hashes(&db, models::ContentType::Image, &last_image_hash);
 
     // ── Clipboard Monitoring Thread ─────────────
+    // This instance lives for the whole process and owns the X11 selection
+    // for everything ClipSnap copies; see `clipboard::set_clipboard_image`.
+    selection::set_image_targets(selection::parse_targets(&config.capture.clipboard_targets));
+    screenshot::set_thumbnail_size(config.ui.thumbnail_size);
     let clipboard = Arc::new(Mutex::new(
         arboard::Clipboard::new().expect("Failed to initialise clipboard"),
     ));
 
-    {
+    // Set on app shutdown; the monitor finishes its current poll and exits so
+    // no database write is cut off half-way.
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let (wake_tx, wake_rx) = std::sync::mpsc::channel();
+
+    // Edits are picked up on the GTK main loop, next to the hotkeys they rebind
+    let (reload_tx, reload_rx) = std::sync::mpsc::channel();
+    let watcher_handle = if config.storage.watch_config {
+        let watched = Config::watch(Config::config_path(), shutdown.clone(), move |config| {
+            let _ = reload_tx.send(config);
+        });
+        watched.map_err(|e| log::warn!("Failed to watch the config file: {}", e)).ok()
+    } else {
+        None
+    };
+    let reload_rx = Rc::new(reload_rx);
+    let monitor_handle = {
         let db_monitor = db.clone();
         let cb_monitor = clipboard.clone();
         let lth = last_text_hash.clone();
         let lih = last_image_hash.clone();
-        std::thread::Builder::new()
+        let options = clipboard::MonitorOptions {
+            prefer_on_dual: config.prefer_on_dual(),
+            flatten_alpha: config.capture.dedup_flatten_alpha,
+            sensitive_ttl_secs: config.privacy.sensitive_ttl_seconds,
+            max_image_dimension: config.history.max_image_dimension,
+            blocked_source_apps: config.privacy.blocked_source_apps.clone(),
+            exclude_passwords: config.privacy.exclude_passwords,
+        };
+        let shutdown = shutdown.clone();
+        let monitor_handle = std::thread::Builder::new()
             .name("clipboard-monitor".into())
             .spawn(move || {
-                clipboard::monitor_clipboard(cb_monitor, db_monitor, lth, lih);
+                clipboard::monitor_clipboard(cb_monitor, db_monitor, lth, lih, wake_rx, options, shutdown);
             })
             .expect("Failed to spawn clipboard monitor thread");
+
+        let wake_tx = wake_tx.clone();
+
+        // Nudge the monitor on focus changes, when copies are most likely
+        let spawned = std::thread::Builder::new()
+            .name("focus-watcher".into())
+            .spawn(move || {
+                if let Err(e) = clipboard::watch_active_window(wake_tx) {
+                    log::warn!("Focus watcher stopped, using timed polling only: {}", e);
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("Failed to spawn focus watcher thread: {}", e);
+        }
+        monitor_handle
+    };
+
+    // ── External insert API (Unix socket + D-Bus) ───
+    let ingest = ipc::Ingest::new(
+        db.clone(),
+        last_text_hash.clone(),
+        last_image_hash.clone(),
+        config.capture.dedup_flatten_alpha,
+    );
+    if let Err(e) = ipc::serve_socket(&ipc::socket_path(), ingest.clone()) {
+        log::error!("Failed to start control socket: {:#}", e);
     }
 
     // ── GTK Application ─────────────────────────────
@@ -76,8 +216,27 @@ fn main() {
     let db_activate = db.clone();
     let cb_activate = clipboard.clone();
     let config_activate = config.clone();
+    let hashes_activate = clipboard::SharedHashes {
+        text: last_text_hash.clone(),
+        image: last_image_hash.clone(),
+        flatten_alpha: config.capture.dedup_flatten_alpha,
+    };
 
     app.connect_activate(move |app| {
+        dbus::register(app, ingest.clone());
+
+        // Make thumbnails sharp on the highest-density monitor
+        if let Some(display) = gdk4::Display::default() {
+            let monitors = display.monitors();
+            let scale = (0..monitors.n_items())
+                .filter_map(|i| monitors.item(i).and_then(|m| m.downcast::<gdk4::Monitor>().ok()))
+                .map(|m| m.scale_factor())
+                .max()
+                .unwrap_or(1);
+            screenshot::set_thumbnail_scale(scale.max(1) as u32);
+        }
+        ui::theme::apply(&config_activate.ui.theme);
+
         // ── Global Hotkeys ──────────────────────────
         let hotkey_manager = match GlobalHotKeyManager::new() {
             Ok(m) => m,
@@ -88,66 +247,52 @@ fn main() {
             }
         };
 
-        let screenshot_hk = match hotkeys::parse_hotkey(&config_activate.shortcuts.screenshot) {
-            Ok(hk) => hk,
-            Err(e) => {
-                log::error!("Invalid screenshot shortcut: {}", e);
-                eprintln!("ERROR: Invalid screenshot shortcut – {}", e);
-                return;
-            }
+        // ── Actions ─────────────────────────────────
+        let actions = Actions {
+            app: app.downgrade(),
+            db: db_activate.clone(),
+            cb: cb_activate.clone(),
+            hashes: hashes_activate.clone(),
+            open_overlay: Rc::new(glib::WeakRef::new()),
+            open_history: Rc::new(glib::WeakRef::new()),
+            previews: ui::preview_cache::PreviewCache::shared(),
         };
-        let history_hk = match hotkeys::parse_hotkey(&config_activate.shortcuts.history) {
-            Ok(hk) => hk,
-            Err(e) => {
-                log::error!("Invalid history shortcut: {}", e);
-                eprintln!("ERROR: Invalid history shortcut – {}", e);
-                return;
-            }
+        let Some(mut registry) = register_actions(&hotkey_manager, &config_activate, &actions) else {
+            return;
         };
-
-        let screenshot_id = screenshot_hk.id();
-        let history_id = history_hk.id();
-
-        if let Err(e) = hotkey_manager.register(screenshot_hk) {
-            log::error!("Failed to register screenshot hotkey: {}", e);
-            eprintln!(
-                "WARNING: Could not register screenshot hotkey ({}). It may conflict with your DE.",
-                config_activate.shortcuts.screenshot
-            );
-        } else {
-            log::info!("Registered screenshot hotkey: {} (ID: {})", config_activate.shortcuts.screenshot, screenshot_id);
-        }
-        if let Err(e) = hotkey_manager.register(history_hk) {
-            log::error!("Failed to register history hotkey: {}", e);
-            eprintln!(
-                "WARNING: Could not register history hotkey ({}). It may conflict with your DE.",
-                config_activate.shortcuts.history
-            );
-        } else {
-            log::info!("Registered history hotkey: {} (ID: {})", config_activate.shortcuts.history, history_id);
-        }
+        let mut current_config = config_activate.clone();
+        let reload_rx = reload_rx.clone();
 
         // ── Hotkey Polling (on GTK main loop) ───────
-        let app_weak = app.downgrade();
-        let db_hotkey = db_activate.clone();
-        let cb_hotkey = cb_activate.clone();
         let hold_guard = app.hold();
         glib::timeout_add_local(Duration::from_millis(100), move || {
             let _hold = &hold_guard;
-            let _keep = &hotkey_manager;
 
             while let Ok(event) = GlobalHotKeyEvent::receiver().try_recv() {
                 if event.state == HotKeyState::Pressed {
-                    if event.id == screenshot_id {
-                        log::info!("Screenshot hotkey pressed - launching capture overlay");
-                        if let Some(ref app) = app_weak.upgrade() {
-                            ui::overlay::show_overlay(app, db_hotkey.clone(), cb_hotkey.clone());
-                        }
-                    } else if event.id == history_id {
-                        log::info!("History hotkey pressed - opening history dialog");
-                        if let Some(ref app) = app_weak.upgrade() {
-                            ui::history_dialog::show_history(app, db_hotkey.clone(), cb_hotkey.clone());
-                        }
+                    registry.dispatch(event.id);
+                }
+            }
+
+            // Edits to config.toml, with storage.watch_config on
+            while let Ok(config) = reload_rx.try_recv() {
+                log::info!("Config changed – reloading shortcuts and UI settings");
+                registry.clear(&hotkey_manager);
+                let config = Arc::new(config);
+                match register_actions(&hotkey_manager, &config, &actions) {
+                    Some(reloaded) => {
+                        registry = reloaded;
+                        ui::theme::apply(&config.ui.theme);
+                        screenshot::set_thumbnail_size(config.ui.thumbnail_size);
+                        current_config = config;
+                    }
+                    None => {
+                        log::error!("Keeping the previous config");
+                        registry = register_actions(&hotkey_manager, &current_config, &actions).unwrap_or_else(|| {
+                            log::error!("Could not restore the previous shortcuts either; no hotkeys are active");
+                            notifications::notify_shortcuts_disabled(&current_config);
+                            hotkeys::ActionRegistry::default()
+                        });
                     }
                 }
             }
@@ -158,7 +303,241 @@ fn main() {
         log::info!("ClipSnap ready");
     });
 
+    app.connect_shutdown(move |_| {
+        shutdown.store(true, Ordering::Relaxed);
+        // Wake the monitor so it need not wait out its poll interval
+        let _ = wake_tx.send(());
+    });
+
     // Run the GTK event loop (blocks until quit).
     let exit_code = app.run();
+    if monitor_handle.join().is_err() {
+        log::error!("Clipboard monitor thread panicked");
+    }
+    if watcher_handle.is_some_and(|handle| handle.join().is_err()) {
+        log::error!("Config watcher thread panicked");
+    }
     log::info!("ClipSnap exiting with code {:?}", exit_code);
 }
+
+/// What hotkey actions share, kept across config reloads.
+struct Actions {
+    app: glib::WeakRef<gtk4::Application>,
+    db: Arc<Mutex<database::Database>>,
+    cb: Arc<Mutex<arboard::Clipboard>>,
+    hashes: clipboard::SharedHashes,
+    /// The open overlay or window picker; pressing a capture hotkey again
+    /// raises it instead of stacking another one on top.
+    open_overlay: Rc<glib::WeakRef<gtk4::Window>>,
+    open_history: Rc<glib::WeakRef<gtk4::Window>>,
+    previews: ui::preview_cache::SharedPreviewCache,
+}
+
+/// Register `config`'s shortcuts with `manager`, each bound to its action.
+/// Optional shortcuts that are invalid or can't be registered are logged and
+/// left out; `None` if the screenshot or history shortcut is invalid.
+fn register_actions(manager: &GlobalHotKeyManager, config: &Arc<Config>, actions: &Actions) -> Option<hotkeys::ActionRegistry> {
+    let screenshot_hk = match hotkeys::parse_hotkey(&config.shortcuts.screenshot) {
+        Ok(hk) => hk,
+        Err(e) => {
+            log::error!("Invalid screenshot shortcut: {}", e);
+            eprintln!("ERROR: Invalid screenshot shortcut – {}", e);
+            return None;
+        }
+    };
+    let history_hk = match hotkeys::parse_hotkey(&config.shortcuts.history) {
+        Ok(hk) => hk,
+        Err(e) => {
+            log::error!("Invalid history shortcut: {}", e);
+            eprintln!("ERROR: Invalid history shortcut – {}", e);
+            return None;
+        }
+    };
+
+    let repeat_hk = hotkeys::parse_optional("repeat_region", config.shortcuts.repeat_region.as_deref());
+    let window_hk = hotkeys::parse_optional("window_capture", config.shortcuts.window_capture.as_deref());
+    let pinned_hk = hotkeys::parse_optional("capture_pinned", config.shortcuts.capture_pinned.as_deref());
+    let color_hk = hotkeys::parse_optional("pick_color", config.shortcuts.pick_color.as_deref());
+    let fullscreen_hk = hotkeys::parse_optional("fullscreen", config.shortcuts.fullscreen.as_deref());
+    let active_window_hk = hotkeys::parse_optional("active_window", config.shortcuts.active_window.as_deref());
+
+    let mut registry = hotkeys::ActionRegistry::new();
+    let Actions { app: app_weak, db: db_hotkey, cb: cb_hotkey, hashes, open_overlay, open_history, previews } = actions;
+
+    let shortcuts = &config.shortcuts;
+    registry.register(manager, &shortcuts.screenshot, screenshot_hk, {
+        let (app_weak, db, cb, config) = (app_weak.clone(), db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        let open_overlay = open_overlay.clone();
+        move || {
+            log::info!("Screenshot hotkey pressed - launching capture overlay");
+            if raise_if_open(&open_overlay) {
+                return;
+            }
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            if let Some(ref app) = app_weak.upgrade() {
+                let output = config.capture.output_for(capture::ACTION_REGION);
+                let window = ui::overlay::show_overlay(app, db.clone(), cb.clone(), config.clone(), None, output, session);
+                open_overlay.set(Some(&window));
+            }
+        }
+    });
+
+    // Screenshot hotkey + Shift opens the overlay on the clipboard image
+    if let Some(hk) = hotkeys::with_shift(screenshot_hk) {
+        let (app_weak, db, cb, config) = (app_weak.clone(), db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        let open_overlay = open_overlay.clone();
+        registry.register(manager, &hotkeys::hotkey_to_string(&hk), hk, move || {
+            log::info!("Screenshot+Shift pressed - opening overlay on clipboard image");
+            if raise_if_open(&open_overlay) {
+                return;
+            }
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            let base = capture::clipboard_base_image(&cb);
+            if base.is_none() {
+                log::info!("No image on the clipboard; falling back to a live capture");
+            }
+            if let Some(ref app) = app_weak.upgrade() {
+                let action = if base.is_some() { capture::ACTION_CLIPBOARD_IMAGE } else { capture::ACTION_REGION };
+                let output = config.capture.output_for(action);
+                let window = ui::overlay::show_overlay(app, db.clone(), cb.clone(), config.clone(), base, output, session);
+                open_overlay.set(Some(&window));
+            }
+        });
+    }
+
+    registry.register(manager, &shortcuts.history, history_hk, {
+        let (app_weak, db, cb, config) = (app_weak.clone(), db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        let (hashes, previews, open_history) = (hashes.clone(), previews.clone(), open_history.clone());
+        move || {
+            log::info!("History hotkey pressed - opening history dialog");
+            if let Some(window) = open_history.upgrade().filter(|w| w.is_visible()) {
+                ui::history_dialog::present_history(&window);
+                return;
+            }
+            if let Some(ref app) = app_weak.upgrade() {
+                let (db, cb, config) = (db.clone(), cb.clone(), config.clone());
+                let window = ui::history_dialog::show_history(app, db, cb, config, hashes.clone(), previews.clone());
+                open_history.set(Some(&window));
+            }
+        }
+    });
+
+    if let (Some(hk), Some(accel)) = (repeat_hk, shortcuts.repeat_region.as_deref()) {
+        let (db, cb, config) = (db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        registry.register(manager, accel, hk, move || {
+            log::info!("Repeat-region hotkey pressed - capturing last region");
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            match capture::load_last_region() {
+                Some(rect) => {
+                    let output = config.capture.output_for(capture::ACTION_REPEAT_REGION);
+                    capture::capture_to_history(rect, &output, &db, &cb, &config, Rc::new(session))
+                }
+                None => notifications::notify_screenshot_error(&config, "No previous region to repeat"),
+            }
+        });
+    }
+
+    if let (Some(hk), Some(accel)) = (window_hk, shortcuts.window_capture.as_deref()) {
+        let (app_weak, db, cb, config) = (app_weak.clone(), db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        let open_overlay = open_overlay.clone();
+        registry.register(manager, accel, hk, move || {
+            log::info!("Window-capture hotkey pressed - waiting for a window click");
+            if raise_if_open(&open_overlay) {
+                return;
+            }
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            if let Some(ref app) = app_weak.upgrade() {
+                let output = config.capture.output_for(capture::ACTION_WINDOW);
+                let window = ui::overlay::show_window_picker(app, db.clone(), cb.clone(), config.clone(), output, session);
+                open_overlay.set(Some(&window));
+            }
+        });
+    }
+
+    if let (Some(hk), Some(accel)) = (pinned_hk, shortcuts.capture_pinned.as_deref()) {
+        let (app_weak, db, cb, config) = (app_weak.clone(), db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        let open_overlay = open_overlay.clone();
+        registry.register(manager, accel, hk, move || {
+            log::info!("Capture-and-pin hotkey pressed - launching capture overlay");
+            if raise_if_open(&open_overlay) {
+                return;
+            }
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            if let Some(ref app) = app_weak.upgrade() {
+                let mut output = config.capture.output_for(capture::ACTION_REGION);
+                output.pin = true;
+                let window = ui::overlay::show_overlay(app, db.clone(), cb.clone(), config.clone(), None, output, session);
+                open_overlay.set(Some(&window));
+            }
+        });
+    }
+
+    if let (Some(hk), Some(accel)) = (color_hk, shortcuts.pick_color.as_deref()) {
+        let (app_weak, db, cb, config) = (app_weak.clone(), db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        let hashes = hashes.clone();
+        let open_overlay = open_overlay.clone();
+        registry.register(manager, accel, hk, move || {
+            log::info!("Pick-color hotkey pressed - waiting for a click");
+            if raise_if_open(&open_overlay) {
+                return;
+            }
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            if let Some(ref app) = app_weak.upgrade() {
+                let window = ui::overlay::show_color_picker(app, db.clone(), cb.clone(), config.clone(), hashes.clone(), session);
+                open_overlay.set(Some(&window));
+            }
+        });
+    }
+
+    if let (Some(hk), Some(accel)) = (fullscreen_hk, shortcuts.fullscreen.as_deref()) {
+        let (db, cb, config) = (db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        registry.register(manager, accel, hk, move || {
+            log::info!("Full-screen hotkey pressed - capturing all monitors");
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            // The same bounding box the overlay covers, in screen pixels
+            let rect = ui::overlay::desktop_layout().screen_bounds();
+            let output = config.capture.output_for(capture::ACTION_FULLSCREEN);
+            capture::capture_to_history(rect, &output, &db, &cb, &config, Rc::new(session));
+        });
+    }
+
+    if let (Some(hk), Some(accel)) = (active_window_hk, shortcuts.active_window.as_deref()) {
+        let (db, cb, config) = (db_hotkey.clone(), cb_hotkey.clone(), config.clone());
+        registry.register(manager, accel, hk, move || {
+            log::info!("Active-window hotkey pressed - capturing the focused window");
+            let Some(session) = capture::begin_session(&config) else {
+                return;
+            };
+            let output = config.capture.output_for(capture::ACTION_WINDOW);
+            capture::capture_active_window(&output, &db, &cb, &config, Rc::new(session));
+        });
+    }
+
+    Some(registry)
+}
+
+/// Raise `window` if it is still open; false if a new one has to be shown.
+fn raise_if_open(window: &glib::WeakRef<gtk4::Window>) -> bool {
+    match window.upgrade().filter(|w| w.is_visible()) {
+        Some(window) => {
+            log::info!("Already open; raising the existing window");
+            window.present();
+            true
+        }
+        None => false,
+    }
+}
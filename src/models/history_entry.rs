@@ -24,6 +24,266 @@ impl ContentType {
     }
 }
 
+/// Ordering applied to history list queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Newest,
+    Oldest,
+    Largest,
+    MostUsed,
+}
+
+impl SortOrder {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "newest" => Some(SortOrder::Newest),
+            "oldest" => Some(SortOrder::Oldest),
+            "largest" => Some(SortOrder::Largest),
+            "most_used" => Some(SortOrder::MostUsed),
+            _ => None,
+        }
+    }
+
+    /// SQL `ORDER BY` expression for this ordering.
+    pub fn order_by_clause(&self) -> &'static str {
+        match self {
+            SortOrder::Newest => "created_at DESC",
+            SortOrder::Oldest => "created_at ASC",
+            SortOrder::Largest => "file_size DESC, created_at DESC",
+            SortOrder::MostUsed => "use_count DESC, created_at DESC",
+        }
+    }
+}
+
+/// What to store when text and an image are both new on the same clipboard poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DualPreference {
+    Image,
+    Text,
+    Both,
+}
+
+impl DualPreference {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "image" => Some(DualPreference::Image),
+            "text" => Some(DualPreference::Text),
+            "both" => Some(DualPreference::Both),
+            _ => None,
+        }
+    }
+}
+
+/// How a picked entry is pasted into the previous window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteMethod {
+    /// Press Ctrl+V.
+    Key,
+    /// Type text entries character by character; images still use Ctrl+V.
+    Type,
+}
+
+impl PasteMethod {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "key" => Some(PasteMethod::Key),
+            "type" => Some(PasteMethod::Type),
+            _ => None,
+        }
+    }
+}
+
+/// How areas marked for redaction in the overlay are hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactStyle {
+    /// Gaussian blur, strong enough to make text unreadable.
+    Blur,
+    /// Solid black.
+    Black,
+}
+
+impl RedactStyle {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "blur" => Some(RedactStyle::Blur),
+            "black" => Some(RedactStyle::Black),
+            _ => None,
+        }
+    }
+}
+
+/// How a region is selected in the overlay (`capture.selection_mode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionMode {
+    /// Press, drag and release.
+    Drag,
+    /// Click one corner, then the opposite one; no button is held.
+    ClickClick,
+}
+
+impl SelectionMode {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "drag" => Some(SelectionMode::Drag),
+            "click-click" => Some(SelectionMode::ClickClick),
+            _ => None,
+        }
+    }
+}
+
+/// What to copy when a captured image is too large for the clipboard
+/// (`capture.large_image_fallback`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LargeImageFallback {
+    /// Copy the saved PNG as a file (`text/uri-list`) instead.
+    Uri,
+    /// Leave the clipboard as it is.
+    None,
+}
+
+impl LargeImageFallback {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "uri" => Some(LargeImageFallback::Uri),
+            "none" => Some(LargeImageFallback::None),
+            _ => None,
+        }
+    }
+}
+
+/// How a picked color is written (`capture.color_format`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFormat {
+    /// `#1e90ff`
+    Hex,
+    /// `rgb(30, 144, 255)`
+    Rgb,
+    /// `hsl(210, 100%, 56%)`
+    Hsl,
+}
+
+impl ColorFormat {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "hex" => Some(ColorFormat::Hex),
+            "rgb" => Some(ColorFormat::Rgb),
+            "hsl" => Some(ColorFormat::Hsl),
+            _ => None,
+        }
+    }
+}
+
+/// Where the history is kept (`storage.mode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageMode {
+    /// In the database file at `storage.database_path`.
+    Disk,
+    /// In memory only, capped at `history.max_entries`; gone on exit.
+    Memory,
+}
+
+impl StorageMode {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "disk" => Some(StorageMode::Disk),
+            "memory" => Some(StorageMode::Memory),
+            _ => None,
+        }
+    }
+}
+
+/// How entry timestamps are shown in the history dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// "5m ago", "3d ago".
+    Relative,
+    /// Local date and time, per `ui.absolute_time_pattern`.
+    Absolute,
+}
+
+impl TimeFormat {
+    /// Parse from config string.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "relative" => Some(TimeFormat::Relative),
+            "absolute" => Some(TimeFormat::Absolute),
+            _ => None,
+        }
+    }
+}
+
+/// How a captured image was taken. Kept in the entry's metadata, and
+/// searchable in the history dialog as `method:<name>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureMethod {
+    Region,
+    Window,
+    Fullscreen,
+    /// Cropped from the image on the clipboard.
+    ClipboardImport,
+}
+
+impl CaptureMethod {
+    /// Metadata value prefix marking a capture method.
+    const METADATA_PREFIX: &'static str = "method:";
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "region" => Some(CaptureMethod::Region),
+            "window" => Some(CaptureMethod::Window),
+            "fullscreen" => Some(CaptureMethod::Fullscreen),
+            "clipboard-import" => Some(CaptureMethod::ClipboardImport),
+            _ => None,
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            CaptureMethod::Region => "region",
+            CaptureMethod::Window => "window",
+            CaptureMethod::Fullscreen => "fullscreen",
+            CaptureMethod::ClipboardImport => "clipboard-import",
+        }
+    }
+
+    /// Short label for history cards.
+    pub fn label(self) -> &'static str {
+        match self {
+            CaptureMethod::Region => "Region",
+            CaptureMethod::Window => "Window",
+            CaptureMethod::Fullscreen => "Full screen",
+            CaptureMethod::ClipboardImport => "From clipboard",
+        }
+    }
+
+    /// The method part of the `metadata` column, e.g. `method:window`.
+    pub fn to_metadata(self) -> String {
+        format!("{}{}", Self::METADATA_PREFIX, self.to_str())
+    }
+
+    /// The value stored in the `metadata` column for a capture saved as
+    /// `mime`, e.g. `method:window;image/webp`.
+    pub fn to_capture_metadata(self, mime: &str) -> String {
+        format!("{};{}", self.to_metadata(), mime)
+    }
+
+    /// Parse a `metadata` column value written by [`to_metadata`](Self::to_metadata)
+    /// or [`to_capture_metadata`](Self::to_capture_metadata), or a
+    /// `method:<name>` search query, which uses the same syntax.
+    pub fn from_metadata(s: &str) -> Option<Self> {
+        let method = s.strip_prefix(Self::METADATA_PREFIX)?;
+        Self::from_str(method.split(';').next().unwrap_or(method))
+    }
+}
+
 /// A single entry in the clipboard history.
 #[derive(Debug, Clone)]
 pub struct HistoryEntry {
@@ -34,11 +294,64 @@ pub struct HistoryEntry {
     pub text_content: Option<String>,
     pub created_at: i64,
     pub file_size: i64,
+    /// Pixel dimensions of image entries, when known.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// How many times the entry was restored from the history.
+    pub use_count: i64,
+    /// MIME type an image arrived in before it was converted to PNG, or the
+    /// format a capture was saved in (`capture.format`).
+    pub source_mime: Option<String>,
+    /// How a captured image was taken; `None` for copied content.
+    pub capture_method: Option<CaptureMethod>,
+    /// Free-form note the user attached to the entry.
+    pub note: Option<String>,
+    /// Kept by automatic cleanup; see `Database::set_pinned`.
+    pub pinned: bool,
+    /// Text that looked like a secret when it was stored; see
+    /// `sensitive::looks_sensitive`.
+    pub sensitive: bool,
+    /// `clipboard::content_hash` of the stored content; `None` for snippets
+    /// and rows stored before hashes were kept.
+    pub content_hash: Option<String>,
+}
+
+/// A user-defined text snippet. Kept apart from the history, so cleanup and
+/// "clear history" never remove it.
+#[derive(Debug, Clone)]
+pub struct Snippet {
+    pub id: i64,
+    pub name: String,
+    pub text: String,
+    pub created_at: i64,
+}
+
+impl Snippet {
+    /// View as a text history entry, for code that restores or displays entries.
+    pub fn to_entry(&self) -> HistoryEntry {
+        HistoryEntry {
+            id: self.id,
+            content_type: ContentType::Text,
+            image_data: None,
+            thumbnail: None,
+            text_content: Some(self.text.clone()),
+            created_at: self.created_at,
+            file_size: self.text.len() as i64,
+            width: None,
+            height: None,
+            use_count: 0,
+            source_mime: None,
+            capture_method: None,
+            note: None,
+            pinned: false,
+            sensitive: false,
+            content_hash: None,
+        }
+    }
 }
 
 /// A rectangular screen region.
-#[derive(Debug, Clone, Copy)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Rectangle {
     pub x: i32,
     pub y: i32,
@@ -1,24 +1,347 @@
-/// Send a desktop notification for a successful screenshot,
-/// showing the captured image as the notification icon.
-pub fn notify_screenshot_success(png_path: &std::path::Path) {
-    let _ = notify_rust::Notification::new()
-        .summary("📸 Screenshot Captured")
-        .body("Image copied to clipboard and saved to history")
-        .icon(&png_path.to_string_lossy())
-        .timeout(2500)
-        .urgency(notify_rust::Urgency::Low)
-        .show();
+use crate::capture::EncodedImage;
+use crate::config::Config;
+use crate::export;
+use crate::database::RepairReport;
+use crate::{screenshot, temp_files};
+use gtk4::prelude::*;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::sync::{Mutex, Once, OnceLock};
+
+/// Send a desktop notification for a successful screenshot, unless
+/// `notifications.success` is off.
+///
+/// The capture is shown as the notification icon when the daemon can display
+/// images. A capture still in memory is written to a private temp file for
+/// that, which is returned so the caller can delete it later; otherwise a
+/// stock icon is used and nothing is written.
+pub fn notify_screenshot_success(config: &Config, image: &EncodedImage, pinned: bool) -> Option<PathBuf> {
+    if !config.notifications.success {
+        return None;
+    }
+    let (summary, body) = if pinned {
+        ("📌 Screenshot Captured & Pinned", "Image copied to clipboard and pinned in history")
+    } else {
+        ("📸 Screenshot Captured", "Image copied to clipboard and saved to history")
+    };
+    let mut written = None;
+    let icon_path = match image {
+        EncodedImage::File(path) => Some(path.clone()),
+        EncodedImage::Memory(bytes) if config.privacy.temp_file && daemon_shows_icons() => {
+            written = write_icon_file(bytes);
+            written.clone()
+        }
+        EncodedImage::Memory(_) => None,
+    };
+    let icon = icon_path.map_or_else(|| "camera-photo".to_string(), |p| p.to_string_lossy().into_owned());
+    show(config, summary, body, &icon, 1, notify_rust::Urgency::Low);
+    written
+}
+
+/// Whether the notification daemon displays icons, asked once per session.
+/// An unreachable daemon counts as no, as the fallback toast shows none.
+fn daemon_shows_icons() -> bool {
+    static SHOWS_ICONS: OnceLock<bool> = OnceLock::new();
+    *SHOWS_ICONS.get_or_init(|| match notify_rust::get_capabilities() {
+        Ok(caps) => caps.iter().any(|cap| cap == "icon-static" || cap == "icon-multi"),
+        Err(e) => {
+            log::debug!("Could not query notification capabilities: {}", e);
+            false
+        }
+    })
+}
+
+/// Write an encoded image to a private temp file for use as a notification
+/// icon, named with the extension of its format.
+fn write_icon_file(bytes: &[u8]) -> Option<PathBuf> {
+    let ext = screenshot::detect_format(bytes).map_or("png", |f| f.extensions_str()[0]);
+    let written = temp_files::create("capture", ext).and_then(|path| {
+        std::fs::write(&path, bytes).map_err(anyhow::Error::from)?;
+        Ok(path)
+    });
+    match written {
+        Ok(path) => Some(path),
+        Err(e) => {
+            log::warn!("Failed to write notification icon: {:#}", e);
+            None
+        }
+    }
 }
 
 /// Send a desktop notification for a failed screenshot.
-pub fn notify_screenshot_error(msg: &str) {
-    let _ = notify_rust::Notification::new()
-        .summary("❌ Screenshot Failed")
-        .body(&format!("Error: {}", msg))
-        .icon("dialog-error")
-        .timeout(4000)
-        .urgency(notify_rust::Urgency::Normal)
-        .show();
+pub fn notify_screenshot_error(config: &Config, msg: &str) {
+    let summary = "❌ Screenshot Failed";
+    let body = format!("Error: {}", msg);
+    show(config, summary, &body, "dialog-error", 2, notify_rust::Urgency::Normal);
+}
+
+/// Report a failed capture with a "Retry" button that calls `on_retry` on the
+/// main loop. Without a notification daemon the fallback toast has no button.
+pub fn notify_capture_failed(config: &Config, msg: &str, on_retry: impl FnOnce() + Send + 'static) {
+    let summary = "❌ Screenshot Failed";
+    let body = format!("Error: {}", msg);
+    let config = config.clone();
+    // Waiting for the button blocks, so do it off the main loop
+    let spawned = std::thread::Builder::new().name("capture-retry".into()).spawn(move || {
+        let duration_secs = config.ui.notification_duration;
+        let result = notification(summary, &body, "dialog-error", duration_secs, 4, notify_rust::Urgency::Normal)
+            .action("retry", "Retry")
+            .show();
+        match result {
+            Ok(handle) => handle.wait_for_action(|action| {
+                if action == "retry" {
+                    glib::MainContext::default().invoke(on_retry);
+                }
+            }),
+            Err(e) => fallback(&config, summary, &body, 4, &e),
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to spawn notification thread: {}", e);
+    }
+}
+
+/// Tell the user the history database was corrupt and had to be rebuilt.
+pub fn notify_database_repaired(config: &Config, report: &RepairReport) {
+    let summary = "⚠️ History Database Repaired";
+    let body = format!(
+        "The history database was damaged. Recovered {} entries; the old file was kept at {}",
+        report.recovered_rows,
+        report.backup_path.display()
+    );
+    show(config, summary, &body, "dialog-warning", 4, notify_rust::Urgency::Normal);
+}
+
+/// Tell the user a capture was copied as a file because it was too large
+/// for the clipboard (`capture.large_image_fallback`).
+pub fn notify_copied_as_file(config: &Config) {
+    let summary = "📁 Screenshot Copied as File";
+    let body = "The image was too large for the clipboard, so it was copied as a PNG file. \
+                Paste it into a file manager, chat or any app that accepts files.";
+    show(config, summary, body, "dialog-information", 3, notify_rust::Urgency::Normal);
+}
+
+/// Tell the user that no global shortcut works any more: the edited config
+/// was rejected and the previous shortcuts couldn't be registered again.
+pub fn notify_shortcuts_disabled(config: &Config) {
+    let summary = "⚠️ Shortcuts Disabled";
+    let body = "The edited config.toml has an invalid shortcut, and the previous shortcuts couldn't be \
+                registered again. Fix the config and save it to bring them back.";
+    show(config, summary, body, "dialog-warning", 4, notify_rust::Urgency::Normal);
+}
+
+/// Size of the swatch shown with a picked color.
+const SWATCH_PX: u32 = 48;
+
+/// The previous color swatch, deleted when the next one is written.
+static LAST_SWATCH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Show a picked color, with a swatch of it as the icon when the daemon can
+/// display images and `privacy.temp_file` allows writing one.
+pub fn notify_color_picked(config: &Config, text: &str, [r, g, b]: [u8; 3]) {
+    let summary = "🎨 Color Copied";
+    let swatch = (config.privacy.temp_file && daemon_shows_icons())
+        .then(|| {
+            let pixels: Vec<u8> = [r, g, b, 255].repeat((SWATCH_PX * SWATCH_PX) as usize);
+            screenshot::encode_png(&pixels, SWATCH_PX, SWATCH_PX).ok()
+        })
+        .flatten()
+        .and_then(|png| write_icon_file(&png));
+    let icon = swatch.as_ref().map_or_else(|| "color-select".to_string(), |p| p.to_string_lossy().into_owned());
+    show(config, summary, text, &icon, 1, notify_rust::Urgency::Low);
+    if let Some(path) = swatch {
+        let mut last = LAST_SWATCH.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(old) = last.replace(path) {
+            let _ = std::fs::remove_file(old);
+        }
+    }
+}
+
+/// Tell the user the oldest entries were deleted to keep the database under
+/// `history.max_db_bytes`.
+pub fn notify_history_trimmed(config: &Config, removed: usize, max_bytes: u64) {
+    let summary = "🗑️ Clipboard History Trimmed";
+    let body = format!(
+        "The history database reached its {:.1} MB limit, so the {} oldest entries were removed",
+        max_bytes as f64 / (1024.0 * 1024.0),
+        removed
+    );
+    show(config, summary, &body, "dialog-information", 3, notify_rust::Urgency::Normal);
+}
+
+/// Send a desktop notification after an entry is restored from history.
+/// Text restores include a short single-line preview of what was copied,
+/// unless the text is `sensitive`. `duration_secs` is
+/// `ui.notification_duration`.
+pub fn notify_clipboard_restored(text: Option<&str>, sensitive: bool, duration_secs: u32) {
+    let body = restored_body(text, sensitive);
+    let summary = "📋 Copied to Clipboard";
+    let _ = notification(summary, &body, "edit-paste", duration_secs, 1, notify_rust::Urgency::Low).show();
+}
+
+/// Body of the restore notification. Sensitive text is masked: daemons may
+/// log or keep notification history.
+fn restored_body(text: Option<&str>, sensitive: bool) -> String {
+    match text {
+        Some(_) if sensitive => "Copied: •••••• (sensitive)".to_string(),
+        Some(text) => format!("Copied: {}", export::preview_line(text, 60)),
+        None => "Item copied from history".to_string(),
+    }
+}
+
+/// Tell the user once that auto-paste can't work on this system, so a
+/// restored entry has to be pasted by hand. `duration_secs` is
+/// `ui.notification_duration`.
+pub fn notify_paste_unavailable(duration_secs: u32) {
+    let summary = "⚠️ Auto-paste Unavailable";
+    let body = "Auto-paste is unavailable on this system. The content was copied; paste it manually.";
+    let _ = notification(summary, body, "dialog-information", duration_secs, 3, notify_rust::Urgency::Normal).show();
+}
+
+/// Send a notification, falling back to an in-app toast when the daemon is
+/// unreachable. `factor` scales `ui.notification_duration` as in
+/// `display_time`.
+fn show(config: &Config, summary: &str, body: &str, icon: &str, factor: u32, urgency: notify_rust::Urgency) {
+    let result = notification(summary, body, icon, config.ui.notification_duration, factor, urgency).show();
+    if let Err(e) = result {
+        fallback(config, summary, body, factor, &e);
+    }
+}
+
+/// A notification shown for `duration_secs` times `factor`; see
+/// `display_time`.
+fn notification(
+    summary: &str,
+    body: &str,
+    icon: &str,
+    duration_secs: u32,
+    factor: u32,
+    urgency: notify_rust::Urgency,
+) -> notify_rust::Notification {
+    let mut notification = notify_rust::Notification::new();
+    notification
+        .summary(summary)
+        .body(body)
+        .icon(icon)
+        .timeout(timeout(duration_secs, factor))
+        .urgency(urgency);
+    notification
+}
+
+/// How long a notification stays: `ui.notification_duration` seconds for a
+/// confirmation (`factor` 1), a multiple of that for errors and notices that
+/// take longer to read. `None` for a duration of 0, which keeps
+/// notifications until dismissed.
+fn display_time(duration_secs: u32, factor: u32) -> Option<Duration> {
+    match duration_secs {
+        0 => None,
+        secs => Some(Duration::from_secs(u64::from(secs) * u64::from(factor))),
+    }
+}
+
+/// `display_time` as a daemon notification timeout.
+fn timeout(duration_secs: u32, factor: u32) -> notify_rust::Timeout {
+    match display_time(duration_secs, factor) {
+        None => notify_rust::Timeout::Never,
+        Some(time) => notify_rust::Timeout::Milliseconds(u32::try_from(time.as_millis()).unwrap_or(u32::MAX)),
+    }
+}
+
+/// Fall back to an in-app toast when the notification daemon is unreachable.
+/// The toast stays as long as the notification would have; see `timeout`.
+fn fallback(config: &Config, summary: &str, body: &str, factor: u32, err: &notify_rust::error::Error) {
+    log::warn!("Desktop notification failed: {}", err);
+    if !config.notifications.fallback_inapp {
+        return;
+    }
+
+    let summary = summary.to_string();
+    let body = body.to_string();
+    let shown_for = display_time(config.ui.notification_duration, factor);
+    glib::MainContext::default().invoke(move || show_toast(&summary, &body, shown_for));
 }
 
+/// Show a small undecorated window that closes itself after `shown_for`, or
+/// stays until clicked with `None`. A click always closes it.
+fn show_toast(summary: &str, body: &str, shown_for: Option<Duration>) {
+    let window = gtk4::Window::builder()
+        .decorated(false)
+        .resizable(false)
+        .title("ClipSnap")
+        .build();
+    window.add_css_class("clipsnap-toast");
 
+    let vbox = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+    vbox.set_margin_start(16);
+    vbox.set_margin_end(16);
+    vbox.set_margin_top(12);
+    vbox.set_margin_bottom(12);
+
+    let summary_label = gtk4::Label::builder()
+        .label(summary)
+        .xalign(0.0)
+        .css_classes(["toast-summary"])
+        .build();
+    vbox.append(&summary_label);
+
+    let body_label = gtk4::Label::builder()
+        .label(body)
+        .xalign(0.0)
+        .wrap(true)
+        .max_width_chars(48)
+        .build();
+    vbox.append(&body_label);
+    window.set_child(Some(&vbox));
+    add_toast_style();
+
+    let click = gtk4::GestureClick::new();
+    let win_click = window.clone();
+    click.connect_released(move |_, _, _, _| win_click.close());
+    window.add_controller(click);
+
+    window.present();
+
+    if let Some(shown_for) = shown_for {
+        let win_close = window.clone();
+        glib::timeout_add_local_once(shown_for, move || win_close.close());
+    }
+}
+
+/// Add the toast CSS to the display, once per session.
+fn add_toast_style() {
+    static ADDED: Once = Once::new();
+    ADDED.call_once(|| {
+        let Some(display) = gdk4::Display::default() else {
+            return;
+        };
+        let provider = gtk4::CssProvider::new();
+        provider.load_from_data(
+            ".clipsnap-toast { background-color: @theme_bg_color; border-radius: 8px; }
+            .toast-summary { font-weight: 700; }",
+        );
+        gtk4::style_context_add_provider_for_display(&display, &provider, gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_time_scales_and_zero_means_until_dismissed() {
+        assert_eq!(display_time(2, 1), Some(Duration::from_secs(2)));
+        assert_eq!(display_time(2, 4), Some(Duration::from_secs(8)));
+        assert_eq!(display_time(0, 3), None);
+        assert!(matches!(timeout(0, 1), notify_rust::Timeout::Never));
+        assert!(matches!(timeout(3, 2), notify_rust::Timeout::Milliseconds(6000)));
+    }
+
+    #[test]
+    fn test_restored_body_masks_sensitive_text() {
+        assert_eq!(restored_body(Some("hello\nworld"), false), "Copied: hello world");
+        let masked = restored_body(Some("Tr0ub4dor&3x"), true);
+        assert_eq!(masked, "Copied: •••••• (sensitive)");
+        assert!(!masked.contains("Tr0ub"));
+        assert_eq!(restored_body(None, false), "Item copied from history");
+    }
+}
@@ -0,0 +1,132 @@
+//! Simulated paste into the window that had focus before the history dialog.
+
+use crate::models::PasteMethod;
+use crate::notifications;
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Longest text typed with `PasteMethod::Type`; longer entries are pasted
+/// with Ctrl+V instead, as typing them would take too long.
+const MAX_TYPED_CHARS: usize = 2000;
+
+/// Set once synthetic input fails to start. It isn't retried for the rest of
+/// the session, so the user is told only once.
+static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+/// One step of typing a text entry.
+#[derive(Debug, PartialEq, Eq)]
+enum Keystroke<'a> {
+    Text(&'a str),
+    Key(Key),
+}
+
+/// Paste the restored entry. `text` is the entry's text, `None` for images,
+/// which are always pasted with Ctrl+V. `notify_secs` is how long to show
+/// the notice if auto-paste turns out to be unavailable.
+pub fn simulate_paste(method: PasteMethod, text: Option<&str>, notify_secs: u32) {
+    if UNAVAILABLE.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut enigo = match Enigo::new(&Settings::default()) {
+        Ok(enigo) => enigo,
+        Err(e) => {
+            log::warn!("Auto-paste unavailable: {}", e);
+            UNAVAILABLE.store(true, Ordering::Relaxed);
+            // The entry is on the clipboard already
+            notifications::notify_paste_unavailable(notify_secs);
+            return;
+        }
+    };
+    let keystrokes = match (method, text) {
+        (PasteMethod::Type, Some(text)) => typing_plan(text),
+        _ => None,
+    };
+    let result = match keystrokes {
+        Some(keystrokes) => type_keystrokes(&mut enigo, &keystrokes),
+        None => press_ctrl_v(&mut enigo),
+    };
+    if let Err(e) = result {
+        log::warn!("Auto-paste failed: {}", e);
+    }
+}
+
+fn press_ctrl_v(enigo: &mut Enigo) -> enigo::InputResult<()> {
+    enigo.key(Key::Control, Direction::Press)?;
+    let clicked = enigo.key(Key::Unicode('v'), Direction::Click);
+    // Never leave Control held down
+    enigo.key(Key::Control, Direction::Release)?;
+    clicked
+}
+
+fn type_keystrokes(enigo: &mut Enigo, keystrokes: &[Keystroke]) -> enigo::InputResult<()> {
+    for keystroke in keystrokes {
+        match keystroke {
+            Keystroke::Text(text) => enigo.text(text)?,
+            Keystroke::Key(key) => enigo.key(*key, Direction::Click)?,
+        }
+    }
+    Ok(())
+}
+
+/// Split `text` into runs typed as-is and the keys for line breaks and
+/// tabs, which not every backend types from text. `None` if the text is too
+/// long to type.
+fn typing_plan(text: &str) -> Option<Vec<Keystroke<'_>>> {
+    let len = text.chars().count();
+    if len > MAX_TYPED_CHARS {
+        log::warn!(
+            "Entry is {} characters, more than the {} typed by ui.paste_method = \"type\"; pasting with Ctrl+V",
+            len,
+            MAX_TYPED_CHARS
+        );
+        return None;
+    }
+    let mut keystrokes = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find(['\r', '\n', '\t']) {
+        if pos > 0 {
+            keystrokes.push(Keystroke::Text(&rest[..pos]));
+        }
+        let tail = &rest[pos..];
+        let (key, skip) = if tail.starts_with("\r\n") {
+            (Key::Return, 2)
+        } else if tail.starts_with('\t') {
+            (Key::Tab, 1)
+        } else {
+            (Key::Return, 1)
+        };
+        keystrokes.push(Keystroke::Key(key));
+        rest = &tail[skip..];
+    }
+    if !rest.is_empty() {
+        keystrokes.push(Keystroke::Text(rest));
+    }
+    Some(keystrokes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typing_plan_breaks_out_control_keys() {
+        assert_eq!(
+            typing_plan("ls -la\r\ncd ~/€\tx\n").unwrap(),
+            vec![
+                Keystroke::Text("ls -la"),
+                Keystroke::Key(Key::Return),
+                Keystroke::Text("cd ~/€"),
+                Keystroke::Key(Key::Tab),
+                Keystroke::Text("x"),
+                Keystroke::Key(Key::Return),
+            ]
+        );
+        assert_eq!(typing_plan("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_typing_plan_caps_length() {
+        assert!(typing_plan(&"é".repeat(MAX_TYPED_CHARS)).is_some());
+        assert!(typing_plan(&"a".repeat(MAX_TYPED_CHARS + 1)).is_none());
+    }
+}
@@ -0,0 +1,220 @@
+//! Screenshots through the xdg-desktop-portal Screenshot interface, for
+//! Wayland sessions where X11 (even through XWayland) can't read other
+//! clients' windows.
+//!
+//! The portal asks the desktop for the `screenshot` permission before
+//! handing out the screen. GNOME asks once ("Allow ClipSnap to take
+//! screenshots?") and keeps the answer in the permission store; a denial can
+//! be undone with `flatpak permission-reset` or in Settings → Apps. KDE
+//! grants it without asking. Either way the desktop needs a portal backend
+//! (`xdg-desktop-portal-gnome`, `-kde` or `-wlr`) that implements
+//! Screenshot.
+//!
+//! The Settings interface of the same portal gives the desktop's light/dark
+//! preference, which `ui.theme = "auto"` follows.
+
+use gio::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const PORTAL_BUS_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SCREENSHOT_INTERFACE: &str = "org.freedesktop.portal.Screenshot";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+const SETTINGS_INTERFACE: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// How long to wait for the portal, which may be waiting on a permission
+/// prompt.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Makes each request's handle token unique within the process.
+static NEXT_TOKEN: AtomicU32 = AtomicU32::new(0);
+
+/// Errors from a portal screenshot.
+#[derive(Debug, thiserror::Error)]
+pub enum PortalError {
+    #[error("Can't reach the screenshot portal; is xdg-desktop-portal running? ({0})")]
+    Unavailable(#[source] glib::Error),
+    #[error("Couldn't wait for the screenshot portal")]
+    Context(#[source] glib::BoolError),
+    #[error("Screenshot permission denied; allow ClipSnap to take screenshots in your desktop's privacy settings")]
+    Denied,
+    #[error("The screenshot portal refused the request; check that ClipSnap may take screenshots in your desktop's privacy settings")]
+    Refused,
+    #[error("The screenshot portal didn't answer within {} seconds", RESPONSE_TIMEOUT.as_secs())]
+    TimedOut,
+    #[error("The screenshot portal returned no readable file ({0})")]
+    NoFile(String),
+}
+
+/// Take a screenshot of the whole desktop without asking the user to pick
+/// an area, and return the image file's bytes (PNG on every known backend).
+///
+/// Blocks until the portal answers, so call it off the main loop. The portal
+/// saves the screenshot to a file for us; it is removed once read.
+pub fn screenshot() -> Result<Vec<u8>, PortalError> {
+    // The answer arrives as a signal, dispatched on the thread-default
+    // context at the time of subscribing; a private one can be iterated here
+    // without running the application's main loop.
+    let context = glib::MainContext::new();
+    context
+        .with_thread_default(|| request_screenshot(&context))
+        .map_err(PortalError::Context)?
+}
+
+fn request_screenshot(context: &glib::MainContext) -> Result<Vec<u8>, PortalError> {
+    let connection =
+        gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE).map_err(PortalError::Unavailable)?;
+
+    // Subscribe before calling, to the handle the portal derives from our
+    // bus name and token, so a fast answer isn't missed
+    let token = format!("clipsnap{}_{}", std::process::id(), NEXT_TOKEN.fetch_add(1, Ordering::Relaxed));
+    let sender = connection.unique_name().map(|name| name.trim_start_matches(':').replace('.', "_"));
+    let handle = format!("{}/request/{}/{}", PORTAL_PATH, sender.unwrap_or_default(), token);
+
+    let response: Arc<Mutex<Option<glib::Variant>>> = Arc::new(Mutex::new(None));
+    let response_ref = response.clone();
+    let subscription = connection.signal_subscribe(
+        Some(PORTAL_BUS_NAME),
+        Some(REQUEST_INTERFACE),
+        Some("Response"),
+        Some(&handle),
+        None,
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            if let Ok(mut response) = response_ref.lock() {
+                *response = Some(params.clone());
+            }
+        },
+    );
+
+    let options = glib::VariantDict::new(None);
+    options.insert("handle_token", token.as_str());
+    options.insert("interactive", false);
+    let args = glib::Variant::tuple_from_iter(["".to_variant(), options.end()]);
+    let called = connection.call_sync(
+        Some(PORTAL_BUS_NAME),
+        PORTAL_PATH,
+        SCREENSHOT_INTERFACE,
+        "Screenshot",
+        Some(&args),
+        None,
+        gio::DBusCallFlags::NONE,
+        -1,
+        gio::Cancellable::NONE,
+    );
+
+    let params = called.map_err(PortalError::Unavailable).and_then(|_| {
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let timer = {
+            let timed_out = timed_out.clone();
+            glib::timeout_source_new(RESPONSE_TIMEOUT, None, glib::Priority::DEFAULT, move || {
+                timed_out.store(true, Ordering::Relaxed);
+                glib::ControlFlow::Break
+            })
+        };
+        timer.attach(Some(context));
+        let params = loop {
+            if let Some(params) = response.lock().ok().and_then(|mut r| r.take()) {
+                break Ok(params);
+            }
+            if timed_out.load(Ordering::Relaxed) {
+                break Err(PortalError::TimedOut);
+            }
+            context.iteration(true);
+        };
+        timer.destroy();
+        params
+    });
+    connection.signal_unsubscribe(subscription);
+
+    // Response(u response, a{sv} results): 0 is success, 1 means the user
+    // said no, anything else that the request failed or was not allowed
+    let params = params?;
+    match params.child_value(0).get::<u32>() {
+        Some(0) => {}
+        Some(1) => return Err(PortalError::Denied),
+        _ => return Err(PortalError::Refused),
+    }
+    let results = glib::VariantDict::new(Some(&params.child_value(1)));
+    let uri = match results.lookup::<String>("uri") {
+        Ok(Some(uri)) => uri,
+        _ => return Err(PortalError::NoFile("no uri in the response".to_string())),
+    };
+    let path = gio::File::for_uri(&uri).path().ok_or_else(|| PortalError::NoFile(uri.clone()))?;
+    let bytes = std::fs::read(&path).map_err(|e| PortalError::NoFile(format!("{}: {}", path.display(), e)))?;
+    if let Err(e) = std::fs::remove_file(&path) {
+        log::warn!("Failed to remove portal screenshot {:?}: {}", path, e);
+    }
+    Ok(bytes)
+}
+
+/// Whether the desktop prefers dark windows: `Some(true)` for dark,
+/// `Some(false)` for light, `None` if it has no preference or there is no
+/// portal to ask.
+pub fn prefers_dark_scheme() -> Option<bool> {
+    let connection = gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE).ok()?;
+    let reply = connection.call_sync(
+        Some(PORTAL_BUS_NAME),
+        PORTAL_PATH,
+        SETTINGS_INTERFACE,
+        "Read",
+        Some(&(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY).to_variant()),
+        None,
+        gio::DBusCallFlags::NONE,
+        1000,
+        gio::Cancellable::NONE,
+    );
+    match reply {
+        // Read wraps the value in a variant once more than SettingChanged
+        Ok(reply) => color_scheme_prefers_dark(&reply.child_value(0)),
+        Err(e) => {
+            log::debug!("Could not read the desktop color scheme: {}", e);
+            None
+        }
+    }
+}
+
+/// Call `on_change` with the new preference, as for `prefers_dark_scheme`,
+/// whenever the desktop switches between light and dark. Dispatched on the
+/// thread-default main context; the subscription lasts for the process.
+pub fn watch_color_scheme(on_change: impl Fn(Option<bool>) + 'static) {
+    let connection = match gio::bus_get_sync(gio::BusType::Session, gio::Cancellable::NONE) {
+        Ok(connection) => connection,
+        Err(e) => {
+            log::debug!("Not following the desktop color scheme: {}", e);
+            return;
+        }
+    };
+    // SettingChanged(s namespace, s key, v value)
+    connection.signal_subscribe(
+        Some(PORTAL_BUS_NAME),
+        Some(SETTINGS_INTERFACE),
+        Some("SettingChanged"),
+        Some(PORTAL_PATH),
+        Some(APPEARANCE_NAMESPACE),
+        gio::DBusSignalFlags::NONE,
+        move |_, _, _, _, _, params| {
+            if params.child_value(1).str() == Some(COLOR_SCHEME_KEY) {
+                on_change(color_scheme_prefers_dark(&params.child_value(2)));
+            }
+        },
+    );
+}
+
+/// Map an `org.freedesktop.appearance` `color-scheme` value, which may be
+/// boxed in variants: 1 prefers dark, 2 prefers light, 0 has no preference.
+fn color_scheme_prefers_dark(value: &glib::Variant) -> Option<bool> {
+    let mut value = value.clone();
+    while let Some(inner) = value.as_variant() {
+        value = inner;
+    }
+    match value.get::<u32>()? {
+        1 => Some(true),
+        2 => Some(false),
+        _ => None,
+    }
+}
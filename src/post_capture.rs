@@ -0,0 +1,153 @@
+use crate::clipboard;
+use crate::temp_files;
+use crate::config::CaptureConfig;
+use anyhow::{Context, Result};
+use arboard::Clipboard;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Run `capture.post_capture_command` for a finished capture on a background thread.
+/// Does nothing when no command is configured.
+pub fn spawn(config: &CaptureConfig, capture_path: &Path, clipboard: Arc<Mutex<Clipboard>>) {
+    let template = config.post_capture_command.trim().to_string();
+    if template.is_empty() {
+        return;
+    }
+    // The capture file is removed after the next capture, so give the command its own copy.
+    let ext = capture_path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+    let own_path = match temp_files::create("capture_cmd", ext) {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("Failed to create file for post-capture command: {:#}", e);
+            return;
+        }
+    };
+    if let Err(e) = std::fs::copy(capture_path, &own_path) {
+        log::error!("Failed to copy capture for post-capture command: {}", e);
+        return;
+    }
+    let use_stdin = config.post_capture_stdin;
+    let copy_url = config.post_capture_copy_url;
+
+    let spawned = std::thread::Builder::new()
+        .name("post-capture".into())
+        .spawn(move || match run(&template, &own_path, use_stdin) {
+            Ok(stdout) => {
+                if !copy_url {
+                    return;
+                }
+                if let Some(url) = extract_url(&stdout) {
+                    if let Ok(mut cb) = clipboard.lock() {
+                        match clipboard::set_clipboard_text(&mut cb, url) {
+                            Ok(()) => log::info!("Copied post-capture URL to clipboard: {}", url),
+                            Err(e) => log::error!("Failed to copy post-capture URL: {}", e),
+                        }
+                    }
+                }
+            }
+            Err(e) => log::error!("Post-capture command failed: {:#}", e),
+        });
+    if let Err(e) = spawned {
+        log::error!("Failed to spawn post-capture thread: {}", e);
+    }
+}
+
+/// Execute the command and return its stdout on a zero exit status.
+fn run(template: &str, png_path: &Path, use_stdin: bool) -> Result<String> {
+    let command = render_command(template, png_path);
+
+    log::info!("Running post-capture command: {}", command);
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .stdin(if use_stdin { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start: {}", command))?;
+
+    if use_stdin {
+        if let Some(mut stdin) = child.stdin.take() {
+            let mut file = std::fs::File::open(png_path)
+                .with_context(|| format!("Failed to open capture: {:?}", png_path))?;
+            // A command that exits without reading stdin closes the pipe; that's not an error.
+            if let Err(e) = std::io::copy(&mut file, &mut stdin) {
+                log::warn!("Post-capture command did not read all of stdin: {}", e);
+            }
+        }
+    }
+
+    let output = child.wait_with_output().context("Failed to wait for command")?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("`{}` exited with {}", command, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Substitute `{path}` in the template with a shell-quoted file path.
+fn render_command(template: &str, path: &Path) -> String {
+    template.replace("{path}", &shell_quote(&path.to_string_lossy()))
+}
+
+/// Quote a string for safe use as a single `sh` word.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Return the first line of command output that looks like a URL.
+fn extract_url(stdout: &str) -> Option<&str> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("https://") || line.starts_with("http://"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_command_quotes_path() {
+        let cmd = render_command("gimp {path}", Path::new("/tmp/it's here.png"));
+        assert_eq!(cmd, "gimp '/tmp/it'\\''s here.png'");
+    }
+
+    #[test]
+    fn test_extract_url() {
+        assert_eq!(
+            extract_url("Uploading…\nhttps://example.com/abc.png\n"),
+            Some("https://example.com/abc.png")
+        );
+        assert_eq!(extract_url("done\n"), None);
+    }
+
+    fn fixture(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("clipsnap_{}_{}.png", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_run_reads_stdin() {
+        let path = fixture("stdin", b"12345");
+        let out = run("wc -c", &path, true).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(out.trim(), "5");
+    }
+
+    #[test]
+    fn test_run_substitutes_path() {
+        let path = fixture("path", b"abc");
+        let out = run("cat {path}", &path, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(out, "abc");
+    }
+
+    #[test]
+    fn test_run_reports_failure() {
+        let path = fixture("fail", b"");
+        let result = run("exit 3", &path, false);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
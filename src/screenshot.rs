@@ -1,36 +1,125 @@
-use anyhow::{Context, Result};
-use image::{ImageBuffer, ImageFormat, RgbaImage};
-use std::io::Cursor;
-use x11rb::connection::Connection;
+use crate::models::{Rectangle, RedactStyle};
+use crate::portal::{self, PortalError};
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ColorType, ImageBuffer, ImageEncoder, ImageError, ImageFormat, Rgba};
+use std::borrow::Cow;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError};
+use x11rb::protocol::screensaver::{self, ConnectionExt as _};
 use x11rb::protocol::xproto::{self, ConnectionExt as _};
+use x11rb::protocol::ErrorKind;
 use x11rb::rust_connection::RustConnection;
 
-/// Capture a specific region of the screen via X11 and return (BGRA data, width, height).
-/// Enhanced with better error handling and coordinate validation.
+/// Errors from screen capture and image encoding.
+#[derive(Debug, thiserror::Error)]
+pub enum ScreenshotError {
+    #[error("Invalid capture dimensions: {width}x{height}")]
+    InvalidDimensions { width: u32, height: u32 },
+    #[error("Capture coordinates ({x}, {y}) are outside screen bounds ({screen_width}x{screen_height})")]
+    OutOfBounds {
+        x: i32,
+        y: i32,
+        screen_width: i32,
+        screen_height: i32,
+    },
+    #[error("Failed to connect to X11 display")]
+    X11Unavailable(#[from] ConnectError),
+    #[error("Lost connection to the X11 display")]
+    X11Connection(#[from] ConnectionError),
+    /// The X server refused to hand out the screen contents.
+    #[error("{}", describe_x11_error(.0))]
+    Capture(#[source] ReplyError),
+    #[error("X11 request failed")]
+    X11Request(#[from] ReplyError),
+    #[error(transparent)]
+    Portal(#[from] PortalError),
+    #[error("Failed to create image buffer – size mismatch ({len} bytes for {width}x{height})")]
+    SizeMismatch { len: usize, width: u32, height: u32 },
+    #[error("Unrecognised image format")]
+    UnknownFormat,
+    #[error("Can't encode images as {0:?}; use \"png\", \"jpeg\" or \"webp\"")]
+    UnsupportedFormat(String),
+    #[error("Failed to decode image")]
+    Decode(#[source] ImageError),
+    #[error("Failed to encode image")]
+    Encode(#[source] ImageError),
+    #[error("Failed to write PNG file: {path:?}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+type Result<T, E = ScreenshotError> = std::result::Result<T, E>;
+
+/// Capture a specific region of the screen and return (BGRA data, width, height).
+/// A region partly off the screen (e.g. dragged from a corner, or on a
+/// monitor with negative offsets) is clamped to the visible part; only a
+/// region entirely off the screen is an error.
+///
+/// Wayland sessions go through the screenshot portal (see
+/// [`capture_region_wayland`]), everything else through X11.
 pub fn capture_region(x: i32, y: i32, w: u32, h: u32) -> Result<(Vec<u8>, u32, u32)> {
+    if is_wayland_session() {
+        capture_region_wayland(x, y, w, h)
+    } else {
+        capture_region_x11(x, y, w, h)
+    }
+}
+
+/// Whether this is a Wayland session, where X11 (even through XWayland)
+/// can't read other clients' windows.
+pub fn is_wayland_session() -> bool {
+    let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+    var("XDG_SESSION_TYPE").as_deref() == Some("wayland") || var("WAYLAND_DISPLAY").is_some()
+}
+
+/// Capture a region through the xdg-desktop-portal Screenshot interface.
+/// The portal hands out the whole desktop, whose top-left corner Wayland
+/// compositors put at (0, 0); the region is cut out of it.
+pub fn capture_region_wayland(x: i32, y: i32, w: u32, h: u32) -> Result<(Vec<u8>, u32, u32)> {
+    if w == 0 || h == 0 {
+        return Err(ScreenshotError::InvalidDimensions { width: w, height: h });
+    }
+
+    let file = portal::screenshot()?;
+    let desktop = image::load_from_memory(&file).map_err(ScreenshotError::Decode)?.to_rgba8();
+    crop_to_bgra(&desktop, x, y, w, h)
+}
+
+/// Cut a region out of a desktop screenshot, clamped like an X11 capture,
+/// as BGRA like X11 hands it out.
+fn crop_to_bgra(desktop: &image::RgbaImage, x: i32, y: i32, w: u32, h: u32) -> Result<(Vec<u8>, u32, u32)> {
+    let visible = visible_region(x, y, w, h, desktop.width() as i32, desktop.height() as i32)?;
+    let region = image::imageops::crop_imm(desktop, visible.x as u32, visible.y as u32, visible.width, visible.height);
+    let mut data = region.to_image().into_raw();
+    for px in data.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+    Ok((data, visible.width, visible.height))
+}
+
+/// Capture a region of the X11 root window.
+fn capture_region_x11(x: i32, y: i32, w: u32, h: u32) -> Result<(Vec<u8>, u32, u32)> {
     // Validate input parameters
     if w == 0 || h == 0 {
-        return Err(anyhow::anyhow!("Invalid capture dimensions: {}x{}", w, h));
+        return Err(ScreenshotError::InvalidDimensions { width: w, height: h });
     }
     
-    let (conn, screen_num) =
-        RustConnection::connect(None).context("Failed to connect to X11 display")?;
+    let (conn, screen_num) = RustConnection::connect(None)?;
     let screen = &conn.setup().roots[screen_num];
 
-    // Validate coordinates are within screen bounds
-    let screen_width = screen.width_in_pixels as i32;
-    let screen_height = screen.height_in_pixels as i32;
-    
-    if x < 0 || y < 0 || x >= screen_width || y >= screen_height {
-        return Err(anyhow::anyhow!(
-            "Capture coordinates ({}, {}) are outside screen bounds ({}x{})", 
-            x, y, screen_width, screen_height
-        ));
-    }
-
-    // Clamp dimensions to screen bounds to prevent X11 errors
-    let actual_w = std::cmp::min(w, (screen_width - x) as u32);
-    let actual_h = std::cmp::min(h, (screen_height - y) as u32);
+    // Clamp to the screen to prevent X11 errors
+    let visible = visible_region(x, y, w, h, screen.width_in_pixels as i32, screen.height_in_pixels as i32)?;
+    let (actual_w, actual_h) = (visible.width, visible.height);
 
     // Use a small delay to ensure any compositor effects are settled
     std::thread::sleep(std::time::Duration::from_millis(50));
@@ -39,15 +128,14 @@ pub fn capture_region(x: i32, y: i32, w: u32, h: u32) -> Result<(Vec<u8>, u32, u
         .get_image(
             xproto::ImageFormat::Z_PIXMAP,
             screen.root,
-            x as i16,
-            y as i16,
+            visible.x as i16,
+            visible.y as i16,
             actual_w as u16,
             actual_h as u16,
             u32::MAX,
-        )
-        .context("get_image request failed")?
+        )?
         .reply()
-        .context("get_image reply failed")?;
+        .map_err(ScreenshotError::Capture)?;
 
     let mut data = reply.data;
     
@@ -65,6 +153,89 @@ pub fn capture_region(x: i32, y: i32, w: u32, h: u32) -> Result<(Vec<u8>, u32, u
     Ok((data, actual_w, actual_h))
 }
 
+/// How long another client may hold the keyboard before the screen counts
+/// as locked. A hotkey's own grab ends when the keys are released.
+const GRAB_GRACE: Duration = Duration::from_millis(300);
+
+/// Whether the screen is probably locked or held by a secure prompt, in
+/// which case X11 hands out black pixels instead of the screen contents:
+/// the screen saver is active, or another client keeps the keyboard grabbed.
+/// When this can't be checked, the answer is no.
+pub fn is_secure_context() -> bool {
+    match secure_context() {
+        Ok(Some(reason)) => {
+            log::info!("Screen looks locked: {}", reason);
+            true
+        }
+        Ok(None) => false,
+        Err(e) => {
+            log::debug!("Could not check for a lock screen: {}", e);
+            false
+        }
+    }
+}
+
+fn secure_context() -> Result<Option<&'static str>> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+
+    if conn.extension_information(screensaver::X11_EXTENSION_NAME)?.is_some() {
+        let state = conn.screensaver_query_info(root)?.reply()?.state;
+        if state == u8::from(screensaver::State::ON) || state == u8::from(screensaver::State::CYCLE) {
+            return Ok(Some("the screen saver is active"));
+        }
+    }
+
+    let deadline = Instant::now() + GRAB_GRACE;
+    while keyboard_grabbed(&conn, root)? {
+        if Instant::now() >= deadline {
+            return Ok(Some("another client has grabbed the keyboard"));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(None)
+}
+
+/// Whether another client holds the keyboard, found by trying to grab it.
+fn keyboard_grabbed(conn: &RustConnection, root: xproto::Window) -> Result<bool> {
+    let status = conn
+        .grab_keyboard(false, root, x11rb::CURRENT_TIME, xproto::GrabMode::ASYNC, xproto::GrabMode::ASYNC)?
+        .reply()?
+        .status;
+    if status == xproto::GrabStatus::SUCCESS {
+        conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        conn.flush()?;
+    }
+    Ok(status == xproto::GrabStatus::ALREADY_GRABBED || status == xproto::GrabStatus::FROZEN)
+}
+
+/// The part of a capture region that is on a `screen_width`×`screen_height`
+/// screen, or `OutOfBounds` if none of it is.
+fn visible_region(x: i32, y: i32, w: u32, h: u32, screen_width: i32, screen_height: i32) -> Result<Rectangle> {
+    let rect = Rectangle { x, y, width: w, height: h };
+    clip_to_screen(rect, screen_width.max(0) as u32, screen_height.max(0) as u32).ok_or(ScreenshotError::OutOfBounds {
+        x,
+        y,
+        screen_width,
+        screen_height,
+    })
+}
+
+/// Turn a failed `get_image` reply into a message the user can act on.
+fn describe_x11_error(err: &ReplyError) -> String {
+    match err {
+        ReplyError::X11Error(e) => match e.error_kind {
+            ErrorKind::Match => {
+                "Region includes an area X11 can't read; try a smaller selection".to_string()
+            }
+            ErrorKind::Value => "Region is outside the screen; try selecting again".to_string(),
+            ErrorKind::Drawable => "The screen could not be read; the display may have changed".to_string(),
+            kind => format!("X11 refused to read the screen ({:?})", kind),
+        },
+        ReplyError::ConnectionError(e) => format!("Lost connection to the X11 display: {}", e),
+    }
+}
+
 /// Convert BGRA pixel data to RGBA.
 pub fn bgra_to_rgba(bgra: &[u8]) -> Vec<u8> {
     let mut rgba = Vec::with_capacity(bgra.len());
@@ -79,20 +250,227 @@ pub fn bgra_to_rgba(bgra: &[u8]) -> Vec<u8> {
 
 /// Encode RGBA pixel data to PNG bytes.
 pub fn encode_png(rgba_pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
-    let img: RgbaImage = ImageBuffer::from_raw(width, height, rgba_pixels.to_vec())
-        .context("Failed to create image buffer – size mismatch")?;
-
     let mut png_bytes: Vec<u8> = Vec::new();
-    img.write_to(&mut Cursor::new(&mut png_bytes), ImageFormat::Png)
-        .context("Failed to encode PNG")?;
-
+    encode_png_to(rgba_pixels, width, height, &mut png_bytes)?;
     Ok(png_bytes)
 }
 
+/// Encode RGBA pixel data in `format` ("png", "jpeg" or "webp"), as set by
+/// `capture.format`.
+///
+/// `quality` (1–100) applies to JPEG, which has no alpha channel, so the
+/// pixels are composited over white first. WebP is always written
+/// losslessly and ignores `quality`: the `image` crate only has a lossy
+/// encoder through libwebp.
+pub fn encode_image(rgba_pixels: &[u8], width: u32, height: u32, format: &str, quality: u8) -> Result<Vec<u8>> {
+    let expected = width as usize * height as usize * 4;
+    if rgba_pixels.len() != expected {
+        return Err(ScreenshotError::SizeMismatch {
+            len: rgba_pixels.len(),
+            width,
+            height,
+        });
+    }
+    let mut bytes = Vec::new();
+    match format {
+        "png" => encode_png_to(rgba_pixels, width, height, &mut bytes)?,
+        "jpeg" | "jpg" => JpegEncoder::new_with_quality(&mut bytes, quality.clamp(1, 100))
+            .write_image(&flatten_over_white(rgba_pixels), width, height, ColorType::Rgb8)
+            .map_err(ScreenshotError::Encode)?,
+        "webp" => WebPEncoder::new_lossless(&mut bytes)
+            .write_image(rgba_pixels, width, height, ColorType::Rgba8)
+            .map_err(ScreenshotError::Encode)?,
+        other => return Err(ScreenshotError::UnsupportedFormat(other.to_string())),
+    }
+    Ok(bytes)
+}
+
+/// File extension for images encoded by `encode_image` in `format`.
+pub fn image_extension(format: &str) -> &'static str {
+    match format {
+        "jpeg" | "jpg" => "jpg",
+        "webp" => "webp",
+        _ => "png",
+    }
+}
+
+/// MIME type of images encoded by `encode_image` in `format`.
+pub fn image_mime_type(format: &str) -> &'static str {
+    match format {
+        "jpeg" | "jpg" => "image/jpeg",
+        "webp" => "image/webp",
+        _ => "image/png",
+    }
+}
+
+/// Encode RGBA pixel data as a 24-bit BMP, composited over white. Apps that
+/// only take BMP pastes rarely handle an alpha channel.
+pub fn encode_bmp(rgba_pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let expected = width as usize * height as usize * 4;
+    if rgba_pixels.len() != expected {
+        return Err(ScreenshotError::SizeMismatch {
+            len: rgba_pixels.len(),
+            width,
+            height,
+        });
+    }
+    let mut bmp_bytes = Vec::new();
+    BmpEncoder::new(&mut bmp_bytes)
+        .write_image(&flatten_over_white(rgba_pixels), width, height, ColorType::Rgb8)
+        .map_err(ScreenshotError::Encode)?;
+    Ok(bmp_bytes)
+}
+
+/// Composite RGBA pixels over a white background, giving RGB pixels.
+pub fn flatten_over_white(rgba_pixels: &[u8]) -> Vec<u8> {
+    rgba_pixels
+        .chunks_exact(4)
+        .flat_map(|px| {
+            let a = px[3] as u32;
+            let over_white = |c: u8| ((c as u32 * a + 255 * (255 - a) + 127) / 255) as u8;
+            [over_white(px[0]), over_white(px[1]), over_white(px[2])]
+        })
+        .collect()
+}
+
+/// Encode RGBA pixel data as PNG directly into `writer`.
+///
+/// The encoder compresses row by row from the borrowed pixels, so peak memory
+/// is the RGBA buffer plus whatever the writer buffers – no copy of the pixels
+/// and no full in-memory PNG as with `encode_png`.
+pub fn encode_png_to<W: Write>(rgba_pixels: &[u8], width: u32, height: u32, writer: W) -> Result<()> {
+    let expected = width as usize * height as usize * 4;
+    if rgba_pixels.len() != expected {
+        return Err(ScreenshotError::SizeMismatch {
+            len: rgba_pixels.len(),
+            width,
+            height,
+        });
+    }
+
+    PngEncoder::new(writer)
+        .write_image(rgba_pixels, width, height, ColorType::Rgba8)
+        .map_err(ScreenshotError::Encode)
+}
+
+/// Encode RGBA pixel data as PNG straight to a file. Returns the file size in bytes.
+pub fn encode_png_to_file(rgba_pixels: &[u8], width: u32, height: u32, path: &Path) -> Result<u64> {
+    let io_error = |source| ScreenshotError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = std::fs::File::create(path).map_err(io_error)?;
+    let mut writer = BufWriter::new(file);
+    encode_png_to(rgba_pixels, width, height, &mut writer)?;
+    writer.flush().map_err(io_error)?;
+    let size = std::fs::metadata(path).map_err(io_error)?.len();
+    Ok(size)
+}
+
+/// Smallest and largest `ui.thumbnail_size` honoured.
+const THUMBNAIL_SIZE_RANGE: std::ops::RangeInclusive<u32> = 48..=512;
+
+/// Logical size (longest side) at which history cards show thumbnails;
+/// `ui.thumbnail_size`, set once at startup.
+static THUMBNAIL_SIZE: AtomicU32 = AtomicU32::new(150);
+
+/// Generate and show thumbnails at this logical size from now on. Entries
+/// already stored keep their thumbnails; the cards scale them to fit.
+pub fn set_thumbnail_size(size: u32) {
+    let clamped = size.clamp(*THUMBNAIL_SIZE_RANGE.start(), *THUMBNAIL_SIZE_RANGE.end());
+    if clamped != size {
+        log::warn!("ui.thumbnail_size {} is out of range, using {}", size, clamped);
+    }
+    THUMBNAIL_SIZE.store(clamped, Ordering::Relaxed);
+}
+
+/// Logical size (longest side) of history card thumbnails.
+pub fn thumbnail_size() -> u32 {
+    THUMBNAIL_SIZE.load(Ordering::Relaxed)
+}
+
+/// Display scale factor thumbnails are generated for; set once the display
+/// is known, from the main thread.
+static THUMBNAIL_SCALE: AtomicU32 = AtomicU32::new(1);
+
+/// Generate thumbnails for a display with this scale factor from now on.
+pub fn set_thumbnail_scale(scale: u32) {
+    THUMBNAIL_SCALE.store(scale.max(1), Ordering::Relaxed);
+}
+
+/// Thumbnail size in pixels for the current display, so HiDPI cards stay
+/// sharp: GTK downscales the larger image instead of upscaling a small one.
+pub fn thumbnail_px() -> u32 {
+    scaled_thumbnail_px(thumbnail_size(), THUMBNAIL_SCALE.load(Ordering::Relaxed))
+}
+
+fn scaled_thumbnail_px(size: u32, scale: u32) -> u32 {
+    size * scale.max(1)
+}
+
+/// Size of a `width`×`height` image scaled down so neither side exceeds
+/// `max`, keeping the aspect ratio. `None` if it already fits.
+fn fit_within(width: u32, height: u32, max: u32) -> Option<(u32, u32)> {
+    let max = max.max(1);
+    if width <= max && height <= max {
+        return None;
+    }
+    let scale = f64::min(max as f64 / width as f64, max as f64 / height as f64);
+    let fit = |side: u32| ((side as f64 * scale).round() as u32).clamp(1, max);
+    Some((fit(width), fit(height)))
+}
+
+/// RGBA pixels downscaled so neither side exceeds `max`
+/// (`history.max_image_dimension`), or borrowed unchanged if they fit.
+/// Returns the pixels with their new width and height.
+pub fn downscale_to_fit(rgba_pixels: &[u8], width: u32, height: u32, max: u32) -> Result<(Cow<'_, [u8]>, u32, u32)> {
+    let Some((new_w, new_h)) = fit_within(width, height, max) else {
+        return Ok((Cow::Borrowed(rgba_pixels), width, height));
+    };
+    let view: ImageBuffer<Rgba<u8>, &[u8]> =
+        ImageBuffer::from_raw(width, height, rgba_pixels).ok_or(ScreenshotError::SizeMismatch {
+            len: rgba_pixels.len(),
+            width,
+            height,
+        })?;
+    let resized = image::imageops::resize(&view, new_w, new_h, image::imageops::FilterType::Triangle);
+    Ok((Cow::Owned(resized.into_raw()), new_w, new_h))
+}
+
+/// Hide `area` of an RGBA image in place, by blurring it or painting it
+/// black. The part of `area` outside the image is ignored.
+pub fn redact(rgba_pixels: &mut [u8], width: u32, height: u32, area: Rectangle, style: RedactStyle) -> Result<()> {
+    let len = rgba_pixels.len();
+    let mut image: ImageBuffer<Rgba<u8>, &mut [u8]> =
+        ImageBuffer::from_raw(width, height, rgba_pixels).ok_or(ScreenshotError::SizeMismatch { len, width, height })?;
+    let Some(area) = clip_to_screen(area, width, height) else {
+        return Ok(());
+    };
+    let (x, y) = (area.x as u32, area.y as u32);
+    match style {
+        RedactStyle::Black => {
+            for py in y..y + area.height {
+                for px in x..x + area.width {
+                    image.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        RedactStyle::Blur => {
+            let region: ImageBuffer<Rgba<u8>, Vec<u8>> =
+                ImageBuffer::from_fn(area.width, area.height, |px, py| *image.get_pixel(x + px, y + py));
+            // Wide enough to smear a line of text into its neighbours
+            let sigma = (area.width.min(area.height) as f32 / 4.0).clamp(6.0, 30.0);
+            let blurred = image::imageops::blur(&region, sigma);
+            image::imageops::replace(&mut image, &blurred, x as i64, y as i64);
+        }
+    }
+    Ok(())
+}
+
 /// Create a thumbnail from PNG bytes. Returns PNG thumbnail bytes.
 /// Enhanced with better quality settings and error handling.
 pub fn create_thumbnail(png_bytes: &[u8], max_size: u32) -> Result<Vec<u8>> {
-    let img = image::load_from_memory(png_bytes).context("Failed to decode PNG for thumbnail")?;
+    let img = image::load_from_memory(png_bytes).map_err(ScreenshotError::Decode)?;
     
     // Use high-quality Lanczos3 filter for better thumbnails
     let thumbnail = img.resize(
@@ -104,16 +482,237 @@ pub fn create_thumbnail(png_bytes: &[u8], max_size: u32) -> Result<Vec<u8>> {
     let mut thumb_bytes: Vec<u8> = Vec::new();
     thumbnail
         .write_to(&mut Cursor::new(&mut thumb_bytes), ImageFormat::Png)
-        .context("Failed to encode thumbnail PNG")?;
+        .map_err(ScreenshotError::Encode)?;
 
     Ok(thumb_bytes)
 }
 
+/// Create a PNG thumbnail straight from RGBA pixels, avoiding a PNG decode.
+pub fn create_thumbnail_from_rgba(rgba_pixels: &[u8], width: u32, height: u32, max_size: u32) -> Result<Vec<u8>> {
+    let view: ImageBuffer<Rgba<u8>, &[u8]> =
+        ImageBuffer::from_raw(width, height, rgba_pixels).ok_or(ScreenshotError::SizeMismatch {
+            len: rgba_pixels.len(),
+            width,
+            height,
+        })?;
+
+    let scale = f64::min(max_size as f64 / width as f64, max_size as f64 / height as f64);
+    let thumb_w = ((width as f64 * scale).round() as u32).max(1);
+    let thumb_h = ((height as f64 * scale).round() as u32).max(1);
+    let thumbnail = image::imageops::resize(&view, thumb_w, thumb_h, image::imageops::FilterType::Lanczos3);
+
+    let mut thumb_bytes: Vec<u8> = Vec::new();
+    thumbnail
+        .write_to(&mut Cursor::new(&mut thumb_bytes), ImageFormat::Png)
+        .map_err(ScreenshotError::Encode)?;
+
+    Ok(thumb_bytes)
+}
+
+/// Read an encoded image's pixel dimensions from its header, without decoding it.
+pub fn image_dimensions(bytes: &[u8]) -> Result<(u32, u32)> {
+    image::io::Reader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| ScreenshotError::Decode(ImageError::IoError(e)))?
+        .into_dimensions()
+        .map_err(ScreenshotError::Decode)
+}
+
+/// Identify an encoded image from its magic bytes. Only the formats other
+/// applications commonly put on the clipboard are recognised.
+pub fn detect_format(bytes: &[u8]) -> Option<ImageFormat> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(ImageFormat::Gif)
+    } else if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+        Some(ImageFormat::WebP)
+    } else if bytes.len() >= 14 && bytes.starts_with(b"BM") {
+        // "BM" alone is too weak a signature; require a whole file header
+        Some(ImageFormat::Bmp)
+    } else {
+        None
+    }
+}
+
+/// An encoded image prepared for the history.
+pub struct ConvertedImage {
+    /// PNG bytes – the original ones if the image already was a PNG.
+    pub png: Vec<u8>,
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub source_format: ImageFormat,
+}
+
+/// Decode an encoded image of any format `detect_format` knows and re-encode
+/// it as PNG, the format history entries are stored in. Formats the `image`
+/// crate was built without fail with `Decode`.
+pub fn convert_to_png(bytes: &[u8]) -> Result<ConvertedImage> {
+    let source_format = detect_format(bytes).ok_or(ScreenshotError::UnknownFormat)?;
+    let rgba = image::load_from_memory_with_format(bytes, source_format)
+        .map_err(ScreenshotError::Decode)?
+        .to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let png = match source_format {
+        ImageFormat::Png => bytes.to_vec(),
+        _ => encode_png(rgba.as_raw(), width, height)?,
+    };
+    Ok(ConvertedImage {
+        png,
+        rgba: rgba.into_raw(),
+        width,
+        height,
+        source_format,
+    })
+}
+
+/// Position of the mouse pointer in root coordinates.
+pub fn pointer_position() -> Result<(i32, i32)> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let screen = &conn.setup().roots[screen_num];
+    let pointer = conn.query_pointer(screen.root)?.reply()?;
+    Ok((pointer.root_x as i32, pointer.root_y as i32))
+}
+
+/// Bounds of the top-level window under the mouse pointer, in root
+/// coordinates and clipped to the screen. `None` means the pointer is over
+/// the root window (the bare desktop).
+///
+/// With a reparenting window manager the top-level window is the frame, so
+/// the title bar and borders are included.
+pub fn window_under_pointer() -> Result<Option<Rectangle>> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let screen = &conn.setup().roots[screen_num];
+
+    let pointer = conn.query_pointer(screen.root)?.reply()?;
+    if pointer.child == x11rb::NONE {
+        return Ok(None);
+    }
+
+    let geometry = conn.get_geometry(pointer.child)?.reply()?;
+    let origin = conn.translate_coordinates(pointer.child, screen.root, 0, 0)?.reply()?;
+    let border = geometry.border_width as i32;
+    let window = Rectangle {
+        x: origin.dst_x as i32 - border,
+        y: origin.dst_y as i32 - border,
+        width: geometry.width as u32 + 2 * border as u32,
+        height: geometry.height as u32 + 2 * border as u32,
+    };
+    Ok(clip_to_screen(window, screen.width_in_pixels as u32, screen.height_in_pixels as u32))
+}
+
+/// Bounds of the visible top-level windows, frames included, topmost first,
+/// in root coordinates. Read from the window manager's `_NET_CLIENT_LIST`
+/// (its stacking variant when available) and `_NET_FRAME_EXTENTS`; empty
+/// if the window manager publishes neither list.
+pub fn client_windows() -> Result<Vec<Rectangle>> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atom = |name: &str| -> Result<xproto::Atom> { Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom) };
+    let frame_extents = atom("_NET_FRAME_EXTENTS")?;
+
+    let cardinals = |window: xproto::Window, property: xproto::Atom, kind: xproto::AtomEnum| -> Result<Vec<u32>> {
+        let reply = conn.get_property(false, window, property, kind, 0, u32::MAX)?.reply()?;
+        Ok(reply.value32().map(|values| values.collect()).unwrap_or_default())
+    };
+    // Stacking order is bottom to top; the plain list may be in mapping order
+    let mut clients = cardinals(root, atom("_NET_CLIENT_LIST_STACKING")?, xproto::AtomEnum::WINDOW)?;
+    if clients.is_empty() {
+        clients = cardinals(root, atom("_NET_CLIENT_LIST")?, xproto::AtomEnum::WINDOW)?;
+    }
+
+    let mut windows = Vec::new();
+    for &client in clients.iter().rev() {
+        windows.extend(frame_bounds(&conn, root, client, frame_extents)?);
+    }
+    Ok(windows)
+}
+
+/// Bounds of the focused window (`_NET_ACTIVE_WINDOW`), frame included, in
+/// root coordinates and clipped to the screen. `None` when no window has
+/// focus, the desktop has it, or the window manager doesn't say.
+pub fn active_window() -> Result<Option<Rectangle>> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let screen = &conn.setup().roots[screen_num];
+    let atom = |name: &str| -> Result<xproto::Atom> { Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom) };
+    let first_value = |window: xproto::Window, property: xproto::Atom, kind: xproto::AtomEnum| -> Result<Option<u32>> {
+        let reply = conn.get_property(false, window, property, kind, 0, 1)?.reply()?;
+        Ok(reply.value32().and_then(|mut values| values.next()))
+    };
+
+    let active = first_value(screen.root, atom("_NET_ACTIVE_WINDOW")?, xproto::AtomEnum::WINDOW)?;
+    let Some(active) = active.filter(|&w| w != x11rb::NONE && w != screen.root) else {
+        return Ok(None);
+    };
+    // File managers draw the desktop as a window, which can hold the focus
+    let window_type = first_value(active, atom("_NET_WM_WINDOW_TYPE")?, xproto::AtomEnum::ATOM)?;
+    if window_type.is_some() && window_type == Some(atom("_NET_WM_WINDOW_TYPE_DESKTOP")?) {
+        return Ok(None);
+    }
+
+    let bounds = frame_bounds(&conn, screen.root, active, atom("_NET_FRAME_EXTENTS")?)?;
+    Ok(bounds.and_then(|rect| clip_to_screen(rect, screen.width_in_pixels as u32, screen.height_in_pixels as u32)))
+}
+
+/// Bounds of a mapped client window plus the frame the window manager
+/// reports in `_NET_FRAME_EXTENTS`, in root coordinates. `None` if it is
+/// unmapped (minimized, on another desktop) or gone.
+fn frame_bounds(
+    conn: &RustConnection,
+    root: xproto::Window,
+    client: xproto::Window,
+    frame_extents: xproto::Atom,
+) -> Result<Option<Rectangle>> {
+    // Windows can disappear between listing and querying them
+    let Ok(attributes) = conn.get_window_attributes(client)?.reply() else {
+        return Ok(None);
+    };
+    if attributes.map_state != xproto::MapState::VIEWABLE {
+        return Ok(None);
+    }
+    let (Ok(geometry), Ok(origin)) = (
+        conn.get_geometry(client)?.reply(),
+        conn.translate_coordinates(client, root, 0, 0)?.reply(),
+    ) else {
+        return Ok(None);
+    };
+    let extents = conn
+        .get_property(false, client, frame_extents, xproto::AtomEnum::CARDINAL, 0, 4)?
+        .reply()?;
+    let [left, right, top, bottom] = match extents.value32().map(|values| values.collect::<Vec<u32>>()).as_deref() {
+        Some(&[left, right, top, bottom]) => [left, right, top, bottom],
+        _ => [0; 4],
+    };
+    let border = geometry.border_width as u32;
+    Ok(Some(Rectangle {
+        x: origin.dst_x as i32 - (border + left) as i32,
+        y: origin.dst_y as i32 - (border + top) as i32,
+        width: geometry.width as u32 + 2 * border + left + right,
+        height: geometry.height as u32 + 2 * border + top + bottom,
+    }))
+}
+
+/// The part of `rect` that lies on a `screen_w`×`screen_h` screen, if any.
+fn clip_to_screen(rect: Rectangle, screen_w: u32, screen_h: u32) -> Option<Rectangle> {
+    let x0 = rect.x.clamp(0, screen_w as i32);
+    let y0 = rect.y.clamp(0, screen_h as i32);
+    let x1 = (rect.x as i64 + rect.width as i64).clamp(0, screen_w as i64) as i32;
+    let y1 = (rect.y as i64 + rect.height as i64).clamp(0, screen_h as i64) as i32;
+    (x1 > x0 && y1 > y0).then(|| Rectangle {
+        x: x0,
+        y: y0,
+        width: (x1 - x0) as u32,
+        height: (y1 - y0) as u32,
+    })
+}
+
 /// Get screen information for better coordinate mapping
 #[allow(dead_code)]
 pub fn get_screen_info() -> Result<(i32, i32, u32, u32)> {
-    let (conn, screen_num) =
-        RustConnection::connect(None).context("Failed to connect to X11 display")?;
+    let (conn, screen_num) = RustConnection::connect(None)?;
     let screen = &conn.setup().roots[screen_num];
     
     Ok((0, 0, screen.width_in_pixels as u32, screen.height_in_pixels as u32))
@@ -123,6 +722,212 @@ pub fn get_screen_info() -> Result<(i32, i32, u32, u32)> {
 mod tests {
     use super::*;
 
+    fn x11_error(error_kind: ErrorKind) -> ReplyError {
+        ReplyError::X11Error(x11rb::x11_utils::X11Error {
+            error_kind,
+            error_code: 0,
+            sequence: 0,
+            bad_value: 0,
+            minor_opcode: 0,
+            major_opcode: 73,
+            extension_name: None,
+            request_name: Some("GetImage"),
+        })
+    }
+
+    #[test]
+    fn test_thumbnail_scales_with_display() {
+        let rgba = vec![0u8; 400 * 200 * 4];
+        let thumb = create_thumbnail_from_rgba(&rgba, 400, 200, scaled_thumbnail_px(150, 2)).unwrap();
+        assert_eq!(image_dimensions(&thumb).unwrap(), (300, 150));
+        assert_eq!(scaled_thumbnail_px(150, 0), 150);
+    }
+
+    #[test]
+    fn test_create_thumbnail_tracks_max_size() {
+        let png = encode_png(&vec![0u8; 400 * 200 * 4], 400, 200).unwrap();
+        for (max_size, expected) in [(100, (100, 50)), (150, (150, 75)), (300, (300, 150))] {
+            let thumb = create_thumbnail(&png, max_size).unwrap();
+            assert_eq!(image_dimensions(&thumb).unwrap(), expected, "max_size {}", max_size);
+        }
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        assert!(matches!(
+            encode_png(&[0u8; 15], 2, 2),
+            Err(ScreenshotError::SizeMismatch { len: 15, width: 2, height: 2 })
+        ));
+        assert!(matches!(create_thumbnail(b"not an image", 150), Err(ScreenshotError::Decode(_))));
+        assert!(matches!(image_dimensions(b"junk"), Err(ScreenshotError::Decode(_))));
+
+        let err = ScreenshotError::Capture(x11_error(ErrorKind::Match));
+        assert!(err.to_string().contains("try a smaller selection"));
+    }
+
+    #[test]
+    fn test_detect_format_png() {
+        let png = encode_png(&[0u8; 4], 1, 1).unwrap();
+        assert_eq!(detect_format(&png), Some(ImageFormat::Png));
+        assert_eq!(detect_format(b"\x89PNG"), None);
+    }
+
+    #[test]
+    fn test_detect_format_jpeg() {
+        assert_eq!(detect_format(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, b'J', b'F']), Some(ImageFormat::Jpeg));
+        assert_eq!(detect_format(&[0xFF, 0xD8]), None);
+    }
+
+    #[test]
+    fn test_detect_format_gif() {
+        assert_eq!(detect_format(b"GIF87a\x01\x00\x01\x00"), Some(ImageFormat::Gif));
+        assert_eq!(detect_format(b"GIF89a\x01\x00\x01\x00"), Some(ImageFormat::Gif));
+        assert_eq!(detect_format(b"GIF90a"), None);
+    }
+
+    #[test]
+    fn test_detect_format_bmp() {
+        let mut bmp = b"BM".to_vec();
+        bmp.extend_from_slice(&[0u8; 12]);
+        assert_eq!(detect_format(&bmp), Some(ImageFormat::Bmp));
+        assert_eq!(detect_format(b"BMW is text"), None);
+        assert_eq!(detect_format(b"plain text"), None);
+        assert_eq!(detect_format(b""), None);
+    }
+
+    #[test]
+    fn test_detect_format_webp() {
+        assert_eq!(detect_format(b"RIFF\x24\x00\x00\x00WEBPVP8L"), Some(ImageFormat::WebP));
+        // Other RIFF files, such as WAV audio
+        assert_eq!(detect_format(b"RIFF\x24\x00\x00\x00WAVEfmt "), None);
+        assert_eq!(detect_format(b"RIFF"), None);
+    }
+
+    #[test]
+    fn test_encode_bmp_flattens_alpha() {
+        // One opaque red pixel, one fully transparent one
+        let bmp = encode_bmp(&[255, 0, 0, 255, 0, 0, 0, 0], 2, 1).unwrap();
+        assert_eq!(detect_format(&bmp), Some(ImageFormat::Bmp));
+        let decoded = image::load_from_memory_with_format(&bmp, ImageFormat::Bmp).unwrap().to_rgb8();
+        assert_eq!(decoded.as_raw(), &[255, 0, 0, 255, 255, 255]);
+        assert!(matches!(encode_bmp(&[0u8; 7], 2, 1), Err(ScreenshotError::SizeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_downscale_to_fit_keeps_aspect_ratio() {
+        let (w, h) = (400u32, 100u32);
+        let rgba = vec![128u8; (w * h * 4) as usize];
+        let (pixels, new_w, new_h) = downscale_to_fit(&rgba, w, h, 200).unwrap();
+        assert_eq!((new_w, new_h), (200, 50));
+        assert_eq!(pixels.len(), (200 * 50 * 4) as usize);
+
+        // Portrait: the height is the side that hits the cap
+        assert_eq!(fit_within(300, 900, 600), Some((200, 600)));
+        // Thin strips keep at least one pixel
+        assert_eq!(fit_within(10_000, 3, 100), Some((100, 1)));
+    }
+
+    #[test]
+    fn test_downscale_to_fit_leaves_small_images() {
+        let rgba = vec![0u8; 4 * 3 * 4];
+        let (pixels, w, h) = downscale_to_fit(&rgba, 4, 3, 4).unwrap();
+        assert!(matches!(pixels, Cow::Borrowed(_)));
+        assert_eq!((w, h), (4, 3));
+    }
+
+    #[test]
+    fn test_redact_changes_only_the_area() {
+        let (w, h) = (40u32, 30u32);
+        // A checkerboard, so blurring visibly changes every pixel
+        let original: Vec<u8> = (0..w * h)
+            .flat_map(|i| {
+                let v = if (i % w + i / w) % 2 == 0 { 255 } else { 128 };
+                [v, v, v, 255]
+            })
+            .collect();
+        let area = Rectangle { x: 10, y: 5, width: 12, height: 8 };
+        let inside = |i: u32| (10..22).contains(&(i % w)) && (5..13).contains(&(i / w));
+
+        for style in [RedactStyle::Blur, RedactStyle::Black] {
+            let mut pixels = original.clone();
+            redact(&mut pixels, w, h, area, style).unwrap();
+            for i in 0..w * h {
+                let range = (i * 4) as usize..(i * 4 + 4) as usize;
+                if inside(i) {
+                    assert_ne!(pixels[range.clone()], original[range], "{:?} left pixel {} unchanged", style, i);
+                } else {
+                    assert_eq!(pixels[range.clone()], original[range], "{:?} changed pixel {}", style, i);
+                }
+            }
+        }
+
+        // Areas hanging off the image are clipped, not an error
+        let mut pixels = original.clone();
+        redact(&mut pixels, w, h, Rectangle { x: -5, y: -5, width: 10, height: 10 }, RedactStyle::Black).unwrap();
+        assert_eq!(pixels[..4], [0, 0, 0, 255]);
+        assert!(redact(&mut pixels, w + 1, h, area, RedactStyle::Black).is_err());
+    }
+
+    #[test]
+    fn test_convert_to_png() {
+        let png = encode_png(&[255u8; 2 * 3 * 4], 2, 3).unwrap();
+        let converted = convert_to_png(&png).unwrap();
+        assert_eq!(converted.png, png);
+        assert_eq!((converted.width, converted.height), (2, 3));
+        assert_eq!(converted.source_format, ImageFormat::Png);
+        assert!(matches!(convert_to_png(b"plain text"), Err(ScreenshotError::UnknownFormat)));
+    }
+
+    #[test]
+    fn test_clip_to_screen() {
+        let rect = |x, y, width, height| Rectangle { x, y, width, height };
+        let clipped = clip_to_screen(rect(-10, 5, 100, 50), 1920, 1080).unwrap();
+        assert_eq!((clipped.x, clipped.y, clipped.width, clipped.height), (0, 5, 90, 50));
+        let clipped = clip_to_screen(rect(1900, 1000, 100, 100), 1920, 1080).unwrap();
+        assert_eq!((clipped.width, clipped.height), (20, 80));
+        assert!(clip_to_screen(rect(2000, 0, 100, 100), 1920, 1080).is_none());
+    }
+
+    #[test]
+    fn test_crop_to_bgra() {
+        // 3×2 desktop whose pixels are (column, row, 9, 255) in RGBA
+        let desktop = image::RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8, y as u8, 9, 255]));
+        let (data, w, h) = crop_to_bgra(&desktop, 1, 1, 5, 5).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(data, [9, 1, 1, 255, 9, 1, 2, 255]);
+        assert!(matches!(crop_to_bgra(&desktop, 3, 0, 1, 1), Err(ScreenshotError::OutOfBounds { .. })));
+    }
+
+    #[test]
+    fn test_visible_region_clamps_partly_off_screen() {
+        let bounds = |r: Rectangle| (r.x, r.y, r.width, r.height);
+        // Dragged from just past the top-left corner
+        assert_eq!(bounds(visible_region(-5, -8, 100, 50, 1920, 1080).unwrap()), (0, 0, 95, 42));
+        // A monitor left of the primary one starts at negative x
+        assert_eq!(bounds(visible_region(-1920, 100, 2000, 200, 1920, 1080).unwrap()), (0, 100, 80, 200));
+        // Running past the bottom-right corner
+        assert_eq!(bounds(visible_region(1900, 1000, 100, 100, 1920, 1080).unwrap()), (1900, 1000, 20, 80));
+        assert_eq!(bounds(visible_region(10, 10, 30, 40, 1920, 1080).unwrap()), (10, 10, 30, 40));
+    }
+
+    #[test]
+    fn test_visible_region_rejects_fully_off_screen() {
+        for (x, y) in [(-200, 0), (0, -200), (1920, 0), (0, 1080), (-500, -500)] {
+            assert!(matches!(
+                visible_region(x, y, 100, 100, 1920, 1080),
+                Err(ScreenshotError::OutOfBounds { screen_width: 1920, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_describe_x11_error() {
+        assert!(describe_x11_error(&x11_error(ErrorKind::Match)).contains("try a smaller selection"));
+        assert!(describe_x11_error(&x11_error(ErrorKind::Value)).contains("outside the screen"));
+        assert!(describe_x11_error(&x11_error(ErrorKind::Drawable)).contains("could not be read"));
+        assert!(describe_x11_error(&x11_error(ErrorKind::Alloc)).contains("Alloc"));
+    }
+
     #[test]
     fn test_bgra_to_rgba() {
         let bgra = vec![10, 20, 30, 255, 40, 50, 60, 255];
@@ -142,6 +947,77 @@ mod tests {
         assert_eq!(&png[0..4], &[0x89, 0x50, 0x4E, 0x47]);
     }
 
+    #[test]
+    fn test_encode_png_to_matches_encode_png() {
+        let pixels = vec![
+            255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+        ];
+        let mut streamed = Vec::new();
+        encode_png_to(&pixels, 2, 2, &mut streamed).unwrap();
+        assert_eq!(streamed, encode_png(&pixels, 2, 2).unwrap());
+    }
+
+    #[test]
+    fn test_encode_png_to_rejects_size_mismatch() {
+        let mut out = Vec::new();
+        assert!(encode_png_to(&[0u8; 15], 2, 2, &mut out).is_err());
+    }
+
+    #[test]
+    fn test_encode_png_to_file() {
+        let pixels = vec![128u8; 4 * 4 * 4];
+        let path = std::env::temp_dir().join(format!("clipsnap_test_{}.png", std::process::id()));
+        let size = encode_png_to_file(&pixels, 4, 4, &path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(size, written.len() as u64);
+        assert_eq!(&written[0..4], &[0x89, 0x50, 0x4E, 0x47]);
+    }
+
+    #[test]
+    fn test_encode_image_formats() {
+        let pixels = vec![
+            255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 0,
+        ];
+        let png = encode_image(&pixels, 2, 2, "png", 90).unwrap();
+        assert_eq!(png, encode_png(&pixels, 2, 2).unwrap());
+        let jpeg = encode_image(&pixels, 2, 2, "jpeg", 90).unwrap();
+        assert_eq!(detect_format(&jpeg), Some(ImageFormat::Jpeg));
+        let webp = encode_image(&pixels, 2, 2, "webp", 90).unwrap();
+        assert_eq!(detect_format(&webp), Some(ImageFormat::WebP));
+        // Lossless whatever the quality, so the pixels come back exactly
+        let decoded = image::load_from_memory_with_format(&webp, ImageFormat::WebP).unwrap().to_rgba8();
+        assert_eq!(decoded.as_raw(), &pixels);
+        assert_eq!(encode_image(&pixels, 2, 2, "webp", 1).unwrap(), webp);
+        assert_eq!(
+            ["png", "jpg", "webp"].map(image_mime_type),
+            ["image/png", "image/jpeg", "image/webp"]
+        );
+
+        assert!(matches!(
+            encode_image(&pixels, 2, 2, "tiff", 90),
+            Err(ScreenshotError::UnsupportedFormat(f)) if f == "tiff"
+        ));
+        assert!(matches!(encode_image(&pixels[..15], 2, 2, "jpeg", 90), Err(ScreenshotError::SizeMismatch { .. })));
+    }
+
+    #[test]
+    fn test_jpeg_quality_trades_size() {
+        // A noisy image, which compresses badly at high quality
+        let pixels: Vec<u8> = (0..64 * 64 * 4).map(|i: u32| (i.wrapping_mul(2654435761) >> 13) as u8 | 3).collect();
+        let low = encode_image(&pixels, 64, 64, "jpeg", 20).unwrap();
+        let high = encode_image(&pixels, 64, 64, "jpeg", 95).unwrap();
+        assert!(low.len() < high.len());
+    }
+
+    #[test]
+    fn test_create_thumbnail_from_rgba() {
+        let pixels = vec![255u8; 20 * 10 * 4];
+        let thumb = create_thumbnail_from_rgba(&pixels, 20, 10, 5).unwrap();
+        let img = image::load_from_memory(&thumb).unwrap();
+        assert_eq!((img.width(), img.height()), (5, 3));
+    }
+
     #[test]
     fn test_create_thumbnail() {
         // Create a small valid PNG first
@@ -0,0 +1,321 @@
+//! Serve copied images on the X11 CLIPBOARD selection in several formats.
+//!
+//! arboard only offers `image/png`, which some editors (LibreOffice, older
+//! GIMP builds) don't accept for pasting. `offer_image` takes over the
+//! selection with a window of its own and converts the image on request to
+//! each configured target (`capture.clipboard_targets`).
+
+use crate::screenshot;
+use anyhow::{anyhow, Result};
+use std::sync::Mutex;
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::xproto::{self, Atom, ConnectionExt as _, PropMode, Window};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+
+/// Largest chunk sent per property change in an INCR transfer.
+const INCR_CHUNK: usize = 256 * 1024;
+
+static IMAGE_TARGETS: Mutex<Vec<ImageTarget>> = Mutex::new(Vec::new());
+
+/// A format a copied image is offered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageTarget {
+    Png,
+    Bmp,
+}
+
+impl ImageTarget {
+    /// Parse a target (MIME type) from the config.
+    pub fn from_mime(s: &str) -> Option<Self> {
+        match s {
+            "image/png" => Some(ImageTarget::Png),
+            "image/bmp" => Some(ImageTarget::Bmp),
+            _ => None,
+        }
+    }
+
+    /// The selection target name.
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageTarget::Png => "image/png",
+            ImageTarget::Bmp => "image/bmp",
+        }
+    }
+
+    fn encode(&self, rgba: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+        Ok(match self {
+            ImageTarget::Png => screenshot::encode_png(rgba, width, height)?,
+            ImageTarget::Bmp => screenshot::encode_bmp(rgba, width, height)?,
+        })
+    }
+}
+
+/// Parse the configured targets, skipping unknown and repeated ones. Falls
+/// back to PNG alone when nothing usable is left.
+pub fn parse_targets(names: &[String]) -> Vec<ImageTarget> {
+    let mut targets = Vec::new();
+    for name in names {
+        match ImageTarget::from_mime(name) {
+            Some(target) if !targets.contains(&target) => targets.push(target),
+            Some(_) => {}
+            None => log::warn!("Ignoring unsupported clipboard target {:?}", name),
+        }
+    }
+    if targets.is_empty() {
+        targets.push(ImageTarget::Png);
+    }
+    targets
+}
+
+/// Offer copied images on these targets from now on.
+pub fn set_image_targets(targets: Vec<ImageTarget>) {
+    *IMAGE_TARGETS.lock().unwrap() = targets;
+}
+
+/// The targets copied images are offered on; PNG alone until configured.
+pub fn image_targets() -> Vec<ImageTarget> {
+    let targets = IMAGE_TARGETS.lock().unwrap();
+    if targets.is_empty() {
+        vec![ImageTarget::Png]
+    } else {
+        targets.clone()
+    }
+}
+
+struct Atoms {
+    clipboard: Atom,
+    targets: Atom,
+    incr: Atom,
+}
+
+/// The image and its encodings, made on first request.
+struct Offer {
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+    /// Target atom, format and encoding, in the configured order.
+    encoded: Vec<(Atom, ImageTarget, Option<Vec<u8>>)>,
+}
+
+impl Offer {
+    fn data(&mut self, target: Atom) -> Option<&[u8]> {
+        let (_, image_target, data) = self.encoded.iter_mut().find(|(atom, _, _)| *atom == target)?;
+        if data.is_none() {
+            match image_target.encode(&self.rgba, self.width, self.height) {
+                Ok(bytes) => *data = Some(bytes),
+                Err(e) => {
+                    log::warn!("Failed to convert clipboard image to {}: {:#}", image_target.mime(), e);
+                    return None;
+                }
+            }
+        }
+        data.as_deref()
+    }
+}
+
+/// An INCR transfer waiting for the requestor to delete each chunk.
+struct Transfer {
+    requestor: Window,
+    property: Atom,
+    target: Atom,
+    data: Vec<u8>,
+    offset: usize,
+}
+
+/// Take over the CLIPBOARD selection and serve `rgba` on `targets` from a
+/// background thread, until another client takes the selection.
+pub fn offer_image(rgba: &[u8], width: u32, height: u32, targets: &[ImageTarget]) -> Result<()> {
+    let (conn, screen_num) = RustConnection::connect(None)?;
+    let screen = &conn.setup().roots[screen_num];
+    let window = conn.generate_id()?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        window,
+        screen.root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        xproto::WindowClass::INPUT_OUTPUT,
+        screen.root_visual,
+        &xproto::CreateWindowAux::new(),
+    )?
+    .check()?;
+
+    let atom = |name: &str| -> Result<Atom> { Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom) };
+    let atoms = Atoms {
+        clipboard: atom("CLIPBOARD")?,
+        targets: atom("TARGETS")?,
+        incr: atom("INCR")?,
+    };
+    let mut encoded = Vec::new();
+    for target in targets {
+        encoded.push((atom(target.mime())?, *target, None));
+    }
+
+    conn.set_selection_owner(window, atoms.clipboard, x11rb::CURRENT_TIME)?.check()?;
+    if conn.get_selection_owner(atoms.clipboard)?.reply()?.owner != window {
+        return Err(anyhow!("Could not take ownership of the clipboard"));
+    }
+
+    let offer = Offer {
+        rgba: rgba.to_vec(),
+        width,
+        height,
+        encoded,
+    };
+    std::thread::Builder::new()
+        .name("clipboard-owner".into())
+        .spawn(move || {
+            if let Err(e) = serve(&conn, window, &atoms, offer) {
+                log::warn!("Clipboard owner stopped: {:#}", e);
+            }
+            let _ = conn.destroy_window(window);
+            let _ = conn.flush();
+        })?;
+    Ok(())
+}
+
+/// Answer selection requests until the selection is taken over.
+fn serve(conn: &RustConnection, window: Window, atoms: &Atoms, mut offer: Offer) -> Result<()> {
+    // Leave room for the ChangeProperty request header
+    let max_property = conn.maximum_request_bytes().saturating_sub(64);
+    let mut transfers: Vec<Transfer> = Vec::new();
+
+    loop {
+        match conn.wait_for_event()? {
+            Event::SelectionClear(ev) if ev.owner == window && ev.selection == atoms.clipboard => {
+                log::debug!("Clipboard image replaced by another owner");
+                return Ok(());
+            }
+            Event::SelectionRequest(req) => {
+                // Obsolete clients leave the property unset and expect the target
+                let property = if req.property == x11rb::NONE { req.target } else { req.property };
+                let answered = if req.target == atoms.targets {
+                    let mut list = vec![atoms.targets];
+                    list.extend(offer.encoded.iter().map(|(atom, _, _)| *atom));
+                    conn.change_property32(PropMode::REPLACE, req.requestor, property, xproto::AtomEnum::ATOM, &list)?;
+                    true
+                } else if let Some(data) = offer.data(req.target) {
+                    if data.len() <= max_property {
+                        conn.change_property8(PropMode::REPLACE, req.requestor, property, req.target, data)?;
+                    } else {
+                        conn.change_window_attributes(
+                            req.requestor,
+                            &xproto::ChangeWindowAttributesAux::new().event_mask(xproto::EventMask::PROPERTY_CHANGE),
+                        )?;
+                        conn.change_property32(
+                            PropMode::REPLACE,
+                            req.requestor,
+                            property,
+                            atoms.incr,
+                            &[data.len() as u32],
+                        )?;
+                        transfers.push(Transfer {
+                            requestor: req.requestor,
+                            property,
+                            target: req.target,
+                            data: data.to_vec(),
+                            offset: 0,
+                        });
+                    }
+                    true
+                } else {
+                    false
+                };
+                conn.send_event(
+                    false,
+                    req.requestor,
+                    xproto::EventMask::NO_EVENT,
+                    xproto::SelectionNotifyEvent {
+                        response_type: xproto::SELECTION_NOTIFY_EVENT,
+                        sequence: 0,
+                        time: req.time,
+                        requestor: req.requestor,
+                        selection: req.selection,
+                        target: req.target,
+                        property: if answered { property } else { x11rb::NONE },
+                    },
+                )?;
+                conn.flush()?;
+            }
+            // The requestor read the last chunk; send the next one
+            Event::PropertyNotify(ev) if ev.state == xproto::Property::DELETE => {
+                let Some(index) = transfers
+                    .iter()
+                    .position(|t| t.requestor == ev.window && t.property == ev.atom)
+                else {
+                    continue;
+                };
+                let transfer = &mut transfers[index];
+                let (start, end) = next_chunk(transfer.offset, transfer.data.len(), INCR_CHUNK.min(max_property));
+                conn.change_property8(
+                    PropMode::REPLACE,
+                    transfer.requestor,
+                    transfer.property,
+                    transfer.target,
+                    &transfer.data[start..end],
+                )?;
+                transfer.offset = end;
+                // The zero-length chunk just written ends the transfer
+                if start == end {
+                    let done = transfers.swap_remove(index);
+                    if !transfers.iter().any(|t| t.requestor == done.requestor) {
+                        conn.change_window_attributes(
+                            done.requestor,
+                            &xproto::ChangeWindowAttributesAux::new().event_mask(xproto::EventMask::NO_EVENT),
+                        )?;
+                    }
+                }
+                conn.flush()?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Byte range of the next INCR chunk; an empty range once all is sent.
+fn next_chunk(offset: usize, len: usize, chunk: usize) -> (usize, usize) {
+    let start = offset.min(len);
+    (start, (start + chunk).min(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_targets() {
+        let names = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parse_targets(&names(&["image/bmp", "image/png", "image/bmp"])),
+            vec![ImageTarget::Bmp, ImageTarget::Png]
+        );
+        assert_eq!(parse_targets(&names(&["image/tiff"])), vec![ImageTarget::Png]);
+        assert_eq!(parse_targets(&[]), vec![ImageTarget::Png]);
+    }
+
+    #[test]
+    fn test_offer_encodes_lazily() {
+        let mut offer = Offer {
+            rgba: vec![0, 0, 255, 255],
+            width: 1,
+            height: 1,
+            encoded: vec![(1, ImageTarget::Png, None), (2, ImageTarget::Bmp, None)],
+        };
+        assert_eq!(screenshot::detect_format(offer.data(2).unwrap()), Some(image::ImageFormat::Bmp));
+        assert!(offer.encoded[0].2.is_none());
+        assert_eq!(screenshot::detect_format(offer.data(1).unwrap()), Some(image::ImageFormat::Png));
+        assert!(offer.data(3).is_none());
+    }
+
+    #[test]
+    fn test_next_chunk() {
+        assert_eq!(next_chunk(0, 10, 4), (0, 4));
+        assert_eq!(next_chunk(8, 10, 4), (8, 10));
+        assert_eq!(next_chunk(10, 10, 4), (10, 10));
+    }
+}
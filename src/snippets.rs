@@ -0,0 +1,90 @@
+//! Predefined text snippets, loaded from `snippets.toml` next to the config:
+//!
+//! ```toml
+//! [[snippet]]
+//! name = "Signature"
+//! text = "Regards,\nSam"
+//! ```
+
+use crate::config::Config;
+use crate::database::Database;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+struct SnippetFile {
+    #[serde(default)]
+    snippet: Vec<SnippetDef>,
+}
+
+/// One `[[snippet]]` table.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SnippetDef {
+    pub name: String,
+    pub text: String,
+}
+
+/// Where snippets are read from.
+pub fn snippets_path() -> PathBuf {
+    Config::config_dir().join("snippets.toml")
+}
+
+/// Parse the contents of a snippets file.
+pub fn parse(content: &str) -> Result<Vec<SnippetDef>> {
+    let file: SnippetFile = toml::from_str(content).context("Failed to parse snippets TOML")?;
+    Ok(file.snippet)
+}
+
+/// Add the snippets from `path` whose names aren't in the database yet.
+/// A missing file is not an error. Returns how many were added.
+pub fn sync_from_file(db: &Database, path: &Path) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let existing: Vec<String> = db.get_snippets()?.into_iter().map(|s| s.name).collect();
+
+    let mut added = 0;
+    for def in parse(&content)? {
+        if existing.contains(&def.name) {
+            continue;
+        }
+        db.add_snippet(&def.name, &def.text)?;
+        added += 1;
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_adds_only_new_names() {
+        let path = std::env::temp_dir().join(format!("clipsnap_snippets_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[[snippet]]\nname = \"sig\"\ntext = \"Regards\"\n\n[[snippet]]\nname = \"addr\"\ntext = \"1 Main St\"\n",
+        )
+        .unwrap();
+
+        let db = Database::new_in_memory().unwrap();
+        db.add_snippet("sig", "edited in the UI").unwrap();
+        assert_eq!(sync_from_file(&db, &path).unwrap(), 1);
+        assert_eq!(sync_from_file(&db, &path).unwrap(), 0);
+
+        let snippets = db.get_snippets().unwrap();
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(snippets.iter().find(|s| s.name == "sig").unwrap().text, "edited in the UI");
+
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(sync_from_file(&db, &path).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_parse_empty_file() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("[[snippet]]\nname = \"no text\"\n").is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! Which app a clipboard change came from, so changes from apps listed in
+//! `privacy.blocked_source_apps` can be left out of the history.
+//!
+//! The app is identified by the WM_CLASS of the X11 window owning the
+//! CLIPBOARD selection. Without X11 nothing is ever blocked.
+
+use anyhow::Result;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, Window};
+use x11rb::rust_connection::RustConnection;
+
+/// Whether the app owning the clipboard is in `blocked`. Logs the app, never
+/// the content. An owner that can't be identified is not blocked.
+pub fn owner_is_blocked(blocked: &[String]) -> bool {
+    if blocked.is_empty() {
+        return false;
+    }
+    match clipboard_owner_class() {
+        Ok(names) if is_blocked(&names, blocked) => {
+            log::info!("Not storing clipboard change from blocked app {:?}", names.join("/"));
+            true
+        }
+        Ok(_) => false,
+        Err(e) => {
+            log::debug!("Could not identify the clipboard owner: {:#}", e);
+            false
+        }
+    }
+}
+
+/// Whether any WM_CLASS name (instance or class) matches a blocked app,
+/// ignoring case.
+pub fn is_blocked(names: &[String], blocked: &[String]) -> bool {
+    names
+        .iter()
+        .any(|name| blocked.iter().any(|app| name.eq_ignore_ascii_case(app.trim())))
+}
+
+/// WM_CLASS names of the window owning the CLIPBOARD selection; empty when
+/// nobody owns it or the owner has no class.
+fn clipboard_owner_class() -> Result<Vec<String>> {
+    let (conn, _) = RustConnection::connect(None)?;
+    let clipboard = conn.intern_atom(false, b"CLIPBOARD")?.reply()?.atom;
+    let owner = conn.get_selection_owner(clipboard)?.reply()?.owner;
+    if owner == x11rb::NONE {
+        return Ok(Vec::new());
+    }
+    let names = wm_class(&conn, owner)?;
+    if !names.is_empty() {
+        return Ok(names);
+    }
+    // Toolkits often own the selection with a hidden window; the app's class
+    // is on its client leader
+    let leader_atom = conn.intern_atom(false, b"WM_CLIENT_LEADER")?.reply()?.atom;
+    let leader = conn
+        .get_property(false, owner, leader_atom, AtomEnum::WINDOW, 0, 1)?
+        .reply()?
+        .value32()
+        .and_then(|mut values| values.next());
+    match leader {
+        Some(leader) if leader != owner && leader != x11rb::NONE => wm_class(&conn, leader),
+        _ => Ok(Vec::new()),
+    }
+}
+
+fn wm_class(conn: &RustConnection, window: Window) -> Result<Vec<String>> {
+    let reply = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)?
+        .reply()?;
+    Ok(parse_wm_class(&reply.value))
+}
+
+/// WM_CLASS holds the instance and class names, each NUL-terminated.
+fn parse_wm_class(value: &[u8]) -> Vec<String> {
+    value
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocked_wm_classes() {
+        let blocked = vec!["KeePassXC".to_string(), " bank-app ".to_string()];
+        let check = |wm_class: &[u8]| is_blocked(&parse_wm_class(wm_class), &blocked);
+
+        assert!(check(b"keepassxc\0KeePassXC\0"));
+        // Either name matches, whatever the case
+        assert!(check(b"bank-app\0Bank\0"));
+        assert!(check(b"main\0BANK-APP\0"));
+        assert!(!check(b"firefox\0Firefox\0"));
+        // Whole names only
+        assert!(!check(b"keepassxc-proxy\0KeePassXC-Proxy\0"));
+        assert!(!check(b""));
+        assert!(!is_blocked(&parse_wm_class(b"keepassxc\0KeePassXC\0"), &[]));
+    }
+}
@@ -0,0 +1,252 @@
+//! Sync text entries between machines through a shared directory (`[sync]`,
+//! built with the `sync` feature).
+//!
+//! Each synced entry is one small TOML file named after a hash of its text:
+//!
+//! ```toml
+//! created_at = 1700000000
+//! text = "copied text"
+//! ```
+//!
+//! Newly stored text entries are pushed shortly after they are stored; text
+//! that looks like a secret and images are never pushed. Entries pushed by
+//! other machines are pulled on a timer and added with their original
+//! timestamps, unless the same text is already in the history. There is no
+//! conflict resolution beyond that.
+
+use crate::clipboard::content_hash;
+use crate::config::{Config, SyncConfig};
+use crate::database::Database;
+use crate::models::{ContentType, SortOrder};
+use crate::sensitive;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often newly stored entries are pushed.
+const PUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Entries pushed per round.
+const PUSH_BATCH: usize = 100;
+
+/// Local entries whose text counts as already present when pulling.
+const KNOWN_ENTRIES: usize = 10_000;
+
+/// Setting holding the id of the newest entry already pushed.
+const LAST_PUSHED_SETTING: &str = "sync_last_pushed_id";
+
+/// One synced entry, as stored in the shared directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncRecord {
+    pub created_at: i64,
+    pub text: String,
+}
+
+impl SyncRecord {
+    /// File the record is stored in; the same text always maps to the same
+    /// file, so pushing it twice is harmless.
+    fn file_name(&self) -> String {
+        format!("{}.toml", content_hash(self.text.as_bytes()))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).context("Failed to serialize sync record")
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        toml::from_str(content).context("Failed to parse sync record")
+    }
+}
+
+/// Write `record` into `dir` unless it is there already. The file appears
+/// whole or not at all. Returns whether it was written.
+pub fn push(dir: &Path, record: &SyncRecord) -> Result<bool> {
+    let path = dir.join(record.file_name());
+    if path.exists() {
+        return Ok(false);
+    }
+    let partial = dir.join(format!(".{}.part", record.file_name()));
+    fs::write(&partial, record.to_toml()?).with_context(|| format!("Failed to write {:?}", partial))?;
+    fs::rename(&partial, &path).with_context(|| format!("Failed to move {:?} into place", partial))?;
+    Ok(true)
+}
+
+/// Every record in `dir`. Files that can't be read or parsed are skipped
+/// with a warning, so one bad file doesn't stop the sync.
+pub fn read_records(dir: &Path) -> Result<Vec<SyncRecord>> {
+    let mut records = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to list {:?}", dir))? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+            continue;
+        }
+        match fs::read_to_string(&path).map_err(anyhow::Error::from).and_then(|c| SyncRecord::from_toml(&c)) {
+            Ok(record) => records.push(record),
+            Err(e) => log::warn!("Skipping sync file {:?}: {:#}", path, e),
+        }
+    }
+    Ok(records)
+}
+
+/// The records whose text isn't in `known` yet, as `insert_batch` rows.
+/// Adds their hashes to `known`, so each text is taken once.
+pub fn select_new(records: Vec<SyncRecord>, known: &mut HashSet<String>) -> Vec<(String, i64)> {
+    records
+        .into_iter()
+        .filter(|record| known.insert(content_hash(record.text.as_bytes())))
+        .map(|record| (record.text, record.created_at))
+        .collect()
+}
+
+/// Start syncing in a background thread if `[sync]` is enabled.
+pub fn spawn(db: Arc<Mutex<Database>>, config: &SyncConfig) {
+    if !config.enabled {
+        return;
+    }
+    if config.directory.trim().is_empty() {
+        log::warn!("sync.enabled is set but sync.directory is empty; not syncing");
+        return;
+    }
+    let dir = Config::expand_path(&config.directory);
+    let pull_interval = Duration::from_secs(config.pull_interval_secs);
+    let spawned = std::thread::Builder::new()
+        .name("clipboard-sync".into())
+        .spawn(move || {
+            if let Err(e) = run(&db, &dir, pull_interval) {
+                log::error!("Clipboard sync stopped: {:#}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        log::warn!("Failed to spawn sync thread: {}", e);
+    }
+}
+
+fn run(db: &Mutex<Database>, dir: &Path, pull_interval: Duration) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    log::info!("Syncing text entries through {:?}", dir);
+
+    let (mut last_pushed, mut known) = {
+        let db = db.lock().map_err(|_| anyhow::anyhow!("Database lock poisoned"))?;
+        // Start with what is stored from now on, not the whole history
+        let last_pushed = match db.get_setting(LAST_PUSHED_SETTING)?.and_then(|v| v.parse().ok()) {
+            Some(id) => id,
+            None => db.max_entry_id()?,
+        };
+        let known: HashSet<String> = db
+            .get_recent_entries_by_type(KNOWN_ENTRIES, ContentType::Text, SortOrder::Newest)?
+            .iter()
+            .filter_map(|e| e.text_content.as_deref().map(|text| content_hash(text.as_bytes())))
+            .collect();
+        (last_pushed, known)
+    };
+
+    let mut next_pull = Instant::now();
+    loop {
+        if let Err(e) = push_new(db, dir, &mut last_pushed, &mut known) {
+            log::warn!("Sync push failed: {:#}", e);
+        }
+        if !pull_interval.is_zero() && Instant::now() >= next_pull {
+            next_pull = Instant::now() + pull_interval;
+            match pull(db, dir, &mut known) {
+                Ok(0) => {}
+                Ok(added) => log::info!("Pulled {} synced entries", added),
+                Err(e) => log::warn!("Sync pull failed: {:#}", e),
+            }
+        }
+        std::thread::sleep(PUSH_INTERVAL);
+    }
+}
+
+/// Push the text entries stored since `last_pushed`.
+fn push_new(db: &Mutex<Database>, dir: &Path, last_pushed: &mut i64, known: &mut HashSet<String>) -> Result<()> {
+    let entries = {
+        let db = db.lock().map_err(|_| anyhow::anyhow!("Database lock poisoned"))?;
+        db.text_entries_after(*last_pushed, PUSH_BATCH)?
+    };
+    let Some(newest) = entries.last().map(|e| e.id) else {
+        return Ok(());
+    };
+    for entry in entries {
+        let Some(text) = entry.text_content else {
+            continue;
+        };
+        known.insert(content_hash(text.as_bytes()));
+        if sensitive::looks_sensitive(&text) {
+            continue;
+        }
+        push(dir, &SyncRecord { created_at: entry.created_at, text })?;
+    }
+    *last_pushed = newest;
+    if let Ok(db) = db.lock() {
+        db.set_setting(LAST_PUSHED_SETTING, &newest.to_string())?;
+    }
+    Ok(())
+}
+
+/// Add the records in `dir` whose text isn't in the history yet. Returns
+/// how many were added.
+fn pull(db: &Mutex<Database>, dir: &Path, known: &mut HashSet<String>) -> Result<usize> {
+    let rows = select_new(read_records(dir)?, known);
+    if rows.is_empty() {
+        return Ok(0);
+    }
+    let db = db.lock().map_err(|_| anyhow::anyhow!("Database lock poisoned"))?;
+    Ok(db.insert_batch(&rows)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(created_at: i64, text: &str) -> SyncRecord {
+        SyncRecord {
+            created_at,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let original = record(1_700_000_000, "line one\n\"quoted\"\ttab ✓");
+        let toml = original.to_toml().unwrap();
+        assert_eq!(SyncRecord::from_toml(&toml).unwrap(), original);
+        assert!(SyncRecord::from_toml("text = \"no timestamp\"").is_err());
+
+        // File names depend only on the text, and never change between builds
+        assert_eq!(record(1, "a").file_name(), record(2, "a").file_name());
+        assert_eq!(record(1, "a").file_name(), "af63dc4c8601ec8c.toml");
+    }
+
+    #[test]
+    fn test_select_new_dedups() {
+        let mut known: HashSet<String> = [content_hash(b"already here")].into();
+        let rows = select_new(
+            vec![record(1, "already here"), record(2, "new"), record(3, "new"), record(4, "other")],
+            &mut known,
+        );
+        assert_eq!(rows, [("new".to_string(), 2), ("other".to_string(), 4)]);
+        // A second pull of the same records adds nothing
+        assert!(select_new(vec![record(2, "new"), record(4, "other")], &mut known).is_empty());
+    }
+
+    #[test]
+    fn test_push_and_read_directory() {
+        let dir = std::env::temp_dir().join(format!("clipsnap_sync_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert!(push(&dir, &record(10, "hello")).unwrap());
+        assert!(!push(&dir, &record(20, "hello")).unwrap());
+        assert!(push(&dir, &record(30, "world")).unwrap());
+        fs::write(dir.join("broken.toml"), "not = [valid").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let mut records = read_records(&dir).unwrap();
+        records.sort_by_key(|r| r.created_at);
+        assert_eq!(records, [record(10, "hello"), record(30, "world")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
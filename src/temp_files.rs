@@ -0,0 +1,131 @@
+//! Private temporary files for captures (notification icons, post-capture
+//! commands). They live in a per-user directory only the user can read:
+//! `$XDG_RUNTIME_DIR/clipsnap`, or `/tmp/clipsnap-<uid>` without one.
+
+use anyhow::{anyhow, Context, Result};
+use std::fs::{DirBuilder, OpenOptions};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The current user's id, from the owner of our own /proc entry.
+fn current_uid() -> Result<u32> {
+    Ok(std::fs::metadata("/proc/self").context("Failed to determine user id")?.uid())
+}
+
+/// The per-user temp directory, created with mode 0700 if needed.
+pub fn dir() -> Result<PathBuf> {
+    let uid = current_uid()?;
+    let dir = match dirs::runtime_dir() {
+        Some(runtime) => runtime.join("clipsnap"),
+        None => std::env::temp_dir().join(format!("clipsnap-{}", uid)),
+    };
+    ensure_private_dir(&dir, uid)?;
+    Ok(dir)
+}
+
+/// Create `dir` as 0700, or check that an existing one belongs to `uid` and
+/// is not accessible to anyone else (a shared /tmp could hold a planted one).
+fn ensure_private_dir(dir: &Path, uid: u32) -> Result<()> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to create {:?}", dir)),
+    }
+    let meta = std::fs::symlink_metadata(dir).with_context(|| format!("Failed to stat {:?}", dir))?;
+    if !meta.is_dir() || meta.uid() != uid {
+        return Err(anyhow!("{:?} is not a directory owned by the current user", dir));
+    }
+    if meta.permissions().mode() & 0o077 != 0 {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+            .with_context(|| format!("Failed to restrict permissions of {:?}", dir))?;
+    }
+    Ok(())
+}
+
+/// Create a new, empty file readable only by the user, named
+/// `<name>_<unique>.<ext>`. Writing to the returned path keeps its mode.
+pub fn create(name: &str, ext: &str) -> Result<PathBuf> {
+    create_in(&dir()?, name, ext)
+}
+
+fn create_in(dir: &Path, name: &str, ext: &str) -> Result<PathBuf> {
+    loop {
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("{}_{}_{}.{}", name, std::process::id(), n, ext));
+        match OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+            Ok(_) => return Ok(path),
+            // Left over from an earlier process with the same pid
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to create {:?}", path)),
+        }
+    }
+}
+
+/// Remove temp files left by earlier runs, including the shared
+/// `clipsnap_*.png` files older versions wrote straight into /tmp.
+/// Returns how many were removed.
+pub fn cleanup() -> usize {
+    let mut removed = match dir() {
+        Ok(dir) => remove_files(&dir, |_| true),
+        Err(e) => {
+            log::warn!("Skipping temp file cleanup: {:#}", e);
+            0
+        }
+    };
+    if let Ok(uid) = current_uid() {
+        removed += remove_files(&std::env::temp_dir(), |path| {
+            let legacy = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n == "clipsnap_last.png" || (n.starts_with("clipsnap_capture_") && n.ends_with(".png")));
+            legacy && std::fs::symlink_metadata(path).is_ok_and(|m| m.uid() == uid)
+        });
+    }
+    removed
+}
+
+/// Remove the regular files in `dir` that `select` accepts.
+fn remove_files(dir: &Path, select: impl Fn(&Path) -> bool) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .map(|e| e.path())
+        .filter(|path| select(path) && std::fs::remove_file(path).is_ok())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_dir_and_files() {
+        let base = std::env::temp_dir().join(format!("clipsnap_tempfiles_{}", std::process::id()));
+        let uid = current_uid().unwrap();
+        ensure_private_dir(&base, uid).unwrap();
+        assert_eq!(std::fs::metadata(&base).unwrap().permissions().mode() & 0o777, 0o700);
+
+        // A pre-existing, too-open directory is tightened
+        std::fs::set_permissions(&base, std::fs::Permissions::from_mode(0o755)).unwrap();
+        ensure_private_dir(&base, uid).unwrap();
+        assert_eq!(std::fs::metadata(&base).unwrap().permissions().mode() & 0o777, 0o700);
+
+        let a = create_in(&base, "capture", "png").unwrap();
+        let b = create_in(&base, "capture", "png").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(std::fs::metadata(&a).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // Rewriting the file keeps it private
+        std::fs::write(&a, b"data").unwrap();
+        assert_eq!(std::fs::metadata(&a).unwrap().permissions().mode() & 0o777, 0o600);
+
+        assert_eq!(remove_files(&base, |_| true), 2);
+        std::fs::remove_dir(&base).unwrap();
+    }
+}
@@ -1,15 +1,32 @@
-use crate::models::{ContentType, HistoryEntry};
-use crate::{clipboard, database::Database};
+use crate::config::Config;
+use crate::export::{self, ExportFormat};
+use crate::models::{CaptureMethod, ContentType, HistoryEntry, PasteMethod, Snippet, SortOrder, TimeFormat};
+use crate::ui::image_preview;
+use crate::ui::preview_cache::SharedPreviewCache;
+use crate::database::{self, Database};
+use crate::{clipboard, notifications, paste, screenshot};
 use arboard::Clipboard;
-use enigo::{Enigo, Key};
 use gdk4;
 use gdk_pixbuf;
 use glib;
 use gtk4::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-/// Show the clipboard history dialog.
-pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard: Arc<Mutex<Clipboard>>) {
+/// Recent text entries scanned by a fuzzy search (`ui.fuzzy_search`).
+const FUZZY_CANDIDATES: usize = 1000;
+
+/// Show the clipboard history dialog and return its window, which
+/// [`present_history`] brings back while it is open.
+pub fn show_history(
+    app: &gtk4::Application,
+    db: Arc<Mutex<Database>>,
+    clipboard: Arc<Mutex<Clipboard>>,
+    config: Arc<Config>,
+    hashes: clipboard::SharedHashes,
+    previews: SharedPreviewCache,
+) -> gtk4::Window {
     let window = gtk4::Window::builder()
         .application(app)
         .title("ClipSnap History")
@@ -50,6 +67,13 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
         .css_classes(["flat"])
         .build();
     header_box.append(&clear_button);
+
+    let export_button = gtk4::Button::builder()
+        .icon_name("document-save-as-symbolic")
+        .tooltip_text("Export visible results")
+        .css_classes(["flat"])
+        .build();
+    header_box.append(&export_button);
     
     vbox.append(&header_box);
 
@@ -75,7 +99,8 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
     
     let text_tab_label = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
     text_tab_label.append(&gtk4::Image::from_icon_name("edit-copy-symbolic"));
-    text_tab_label.append(&gtk4::Label::new(Some("Text")));
+    let text_tab_text = gtk4::Label::new(Some("Text"));
+    text_tab_label.append(&text_tab_text);
     notebook.append_page(&text_scrolled, Some(&text_tab_label));
 
     // 2. Images Tab
@@ -98,9 +123,55 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
     
     let img_tab_label = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
     img_tab_label.append(&gtk4::Image::from_icon_name("image-x-generic-symbolic"));
-    img_tab_label.append(&gtk4::Label::new(Some("Images")));
+    let img_tab_text = gtk4::Label::new(Some("Images"));
+    img_tab_label.append(&img_tab_text);
     notebook.append_page(&img_scrolled, Some(&img_tab_label));
 
+    // 3. Snippets Tab
+    let snippets_page = gtk4::Box::new(gtk4::Orientation::Vertical, 8);
+    let add_row = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
+    add_row.set_margin_start(8);
+    add_row.set_margin_end(8);
+    add_row.set_margin_top(8);
+    let snippet_name = gtk4::Entry::builder().placeholder_text("Name").width_chars(12).build();
+    let snippet_text = gtk4::Entry::builder().placeholder_text("Snippet text").hexpand(true).build();
+    let add_snippet_button = gtk4::Button::builder()
+        .icon_name("list-add-symbolic")
+        .tooltip_text("Add snippet")
+        .build();
+    add_row.append(&snippet_name);
+    add_row.append(&snippet_text);
+    add_row.append(&add_snippet_button);
+    snippets_page.append(&add_row);
+
+    let snippets_scrolled = gtk4::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .vscrollbar_policy(gtk4::PolicyType::Automatic)
+        .vexpand(true)
+        .build();
+    let snippet_flow = gtk4::FlowBox::builder()
+        .max_children_per_line(1)
+        .selection_mode(gtk4::SelectionMode::None)
+        .row_spacing(8)
+        .margin_start(8)
+        .margin_end(8)
+        .margin_bottom(8)
+        .build();
+    snippets_scrolled.set_child(Some(&snippet_flow));
+    snippets_page.append(&snippets_scrolled);
+
+    let snippets_tab_label = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+    snippets_tab_label.append(&gtk4::Image::from_icon_name("insert-text-symbolic"));
+    let snippets_tab_text = gtk4::Label::new(Some("Snippets"));
+    snippets_tab_label.append(&snippets_tab_text);
+    notebook.append_page(&snippets_page, Some(&snippets_tab_label));
+
+    match config.ui.default_tab.as_str() {
+        "images" => notebook.set_current_page(Some(1)),
+        "snippets" => notebook.set_current_page(Some(2)),
+        _ => {}
+    }
+
     vbox.append(&notebook);
 
     // Footer Info
@@ -115,8 +186,9 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
     spacer.set_hexpand(true);
     footer_box.append(&spacer);
     
+    let pick = if config.ui.auto_paste { "Copy & Paste (Shift: Copy only)" } else { "Copy" };
     let hint = gtk4::Label::builder()
-        .label("Click to Copy & Paste   •   Esc to Close")
+        .label(format!("Click or ↑↓ Enter to {}   •   Right-click image to Preview   •   Del to Delete   •   Ctrl+Tab to Switch   •   Esc to Close", pick))
         .xalign(1.0)
         .css_classes(["dim-label"])
         .build();
@@ -127,61 +199,173 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
 
     // ── Load entries ────────────
     let db_load = db.clone();
-    let win_ref = window.clone();
-    let cb_ref = clipboard.clone();
+    let shown = ShownEntries {
+        status_label: status_label.clone(),
+        tab_labels: [text_tab_text.clone(), img_tab_text.clone()],
+        entries: Rc::new(RefCell::new(Vec::new())),
+    };
+    let cards = CardContext {
+        db: db.clone(),
+        window: window.clone(),
+        clipboard: clipboard.clone(),
+        auto_paste: config.ui.auto_paste,
+        paste_delay_ms: config.ui.auto_paste_delay_ms,
+        paste_method: config.paste_method(),
+        notification_duration: config.ui.notification_duration,
+        time_format: config.time_format(),
+        time_pattern: config.absolute_time_pattern().into(),
+        developer_actions: config.ui.developer_actions,
+        bump_on_restore: config.history.bump_on_restore.then_some(hashes),
+        previews: previews.clone(),
+        shown: shown.clone(),
+    };
+    let sort_order = config.sort_order();
+    let fuzzy_search = config.ui.fuzzy_search;
+    let selection = CardSelection::default();
 
     let populate = {
+        let selection = selection.clone();
         let text_flow = text_flow.clone();
         let img_flow = img_flow.clone();
-        let status_label = status_label.clone();
+        let snippet_flow = snippet_flow.clone();
+        let shown = shown.clone();
+        let snippets_tab_text = snippets_tab_text.clone();
         
         move |query: &str| {
-            // Clear both
+            let started = std::time::Instant::now();
+            selection.clear();
+            // Clear all tabs
             while let Some(child) = text_flow.first_child() { text_flow.remove(&child); }
             while let Some(child) = img_flow.first_child() { img_flow.remove(&child); }
+            while let Some(child) = snippet_flow.first_child() { snippet_flow.remove(&child); }
 
             if let Ok(db) = db_load.lock() {
+                // "method:window" lists the images captured that way, and no text
+                let method_filter = query.trim().starts_with("method:").then(|| CaptureMethod::from_metadata(query.trim()));
+
                 // Populate Text
-                let text_entries = if query.is_empty() {
-                    db.get_recent_entries_by_type(50, ContentType::Text).unwrap_or_default()
+                let text_entries = if method_filter.is_some() {
+                    Vec::new()
+                } else if query.is_empty() {
+                    db.get_recent_metadata_by_type(50, ContentType::Text, sort_order).unwrap_or_default()
+                } else if fuzzy_search {
+                    db.fuzzy_search_text(query, FUZZY_CANDIDATES).unwrap_or_default()
                 } else {
                     db.search_text(query).unwrap_or_default()
                 };
-                for (i, entry) in text_entries.iter().enumerate() {
-                    if i > 0 {
+                let grouped = groups_by_date(query, sort_order);
+                let mut last_bucket = None;
+                for entry in &text_entries {
+                    let group = bucket(entry.created_at);
+                    if grouped && last_bucket != Some(group) {
+                        text_flow.insert(&build_date_header(group), -1);
+                    } else if last_bucket.is_some() {
                         let sep = gtk4::Separator::new(gtk4::Orientation::Horizontal);
                         sep.add_css_class("divider");
                         text_flow.insert(&sep, -1);
                     }
-                    text_flow.insert(&build_entry_widget(entry, &db_load, &win_ref, &cb_ref), -1);
+                    last_bucket = Some(group);
+                    text_flow.insert(&build_entry_widget(entry, CardKind::History, &cards), -1);
                 }
 
                 // Populate Images
-                let img_entries = if query.is_empty() {
-                    db.get_recent_entries_by_type(50, ContentType::Image).unwrap_or_default()
-                } else {
-                    Vec::new()
+                let img_entries = match method_filter {
+                    Some(Some(method)) => db.get_images_by_method(method, 50).unwrap_or_default(),
+                    Some(None) => Vec::new(),
+                    None if query.is_empty() => {
+                        db.get_recent_metadata_by_type(50, ContentType::Image, sort_order).unwrap_or_default()
+                    }
+                    None => Vec::new(),
                 };
+                let mut last_bucket = None;
                 for entry in &img_entries {
-                    img_flow.insert(&build_entry_widget(entry, &db_load, &win_ref, &cb_ref), -1);
+                    let group = bucket(entry.created_at);
+                    if grouped && last_bucket != Some(group) {
+                        img_flow.insert(&build_date_header(group), -1);
+                    }
+                    last_bucket = Some(group);
+                    img_flow.insert(&build_entry_widget(entry, CardKind::History, &cards), -1);
                 }
 
-                status_label.set_text(&format!("{} text, {} images", text_entries.len(), img_entries.len()));
+                shown.set(text_entries.iter().chain(img_entries.iter()));
+                shown.set_totals(tab_totals(&db));
+
+                // Populate Snippets; searching matches names and text
+                let snippets = db.get_snippets().unwrap_or_default();
+                let needle = query.to_lowercase();
+                for snippet in snippets.iter().filter(|s| snippet_matches(s, &needle)) {
+                    let card = build_entry_widget(&snippet.to_entry(), CardKind::Snippet(&snippet.name), &cards);
+                    snippet_flow.insert(&card, -1);
+                }
+                snippets_tab_text.set_text(&format!("Snippets ({})", snippets.len()));
             }
+            log::debug!("Built history cards in {:?}", started.elapsed());
         }
     };
 
+    // --- Add Snippet ---
+    {
+        let db = db.clone();
+        let populate = populate.clone();
+        let (snippet_name, snippet_text) = (snippet_name.clone(), snippet_text.clone());
+        let search_entry = search_entry.clone();
+        let status_label = status_label.clone();
+        let add = move || {
+            let name = snippet_name.text().trim().to_string();
+            let text = snippet_text.text().to_string();
+            if name.is_empty() || text.is_empty() {
+                status_label.set_text("A snippet needs a name and text");
+                return;
+            }
+            let added = match db.lock() {
+                Ok(db) => db.add_snippet(&name, &text),
+                Err(_) => return,
+            };
+            match added {
+                Ok(_) => {
+                    snippet_name.set_text("");
+                    snippet_text.set_text("");
+                    populate(&search_entry.text());
+                }
+                Err(e) => {
+                    log::warn!("{:#}", e);
+                    status_label.set_text(&format!("A snippet named \"{}\" already exists", name));
+                }
+            }
+        };
+        let add_enter = add.clone();
+        snippet_text.connect_activate(move |_| add_enter());
+        add_snippet_button.connect_clicked(move |_| add());
+    }
+
     // --- Clear All Logic ---
     let db_clear = db.clone();
     let notebook_clear = notebook.clone();
     let text_flow_clear = text_flow.clone();
     let img_flow_clear = img_flow.clone();
+    let text_tab_clear = text_tab_text.clone();
+    let img_tab_clear = img_tab_text.clone();
     let _win_clear = window.clone();
+    let previews_clear = previews.clone();
     
+    // Snippets are never cleared in bulk
+    let clear_sensitive = clear_button.clone();
+    notebook.connect_switch_page(move |_, _, page| clear_sensitive.set_sensitive(page < 2));
+    clear_button.set_sensitive(notebook.current_page().map_or(true, |page| page < 2));
+
     clear_button.connect_clicked(move |_| {
         let current_page = notebook_clear.current_page();
+        if current_page == Some(2) {
+            return;
+        }
         let content_type = if current_page == Some(0) { Some(ContentType::Text) } else { Some(ContentType::Image) };
         let active_flow = if current_page == Some(0) { text_flow_clear.clone() } else { img_flow_clear.clone() };
+        let active_tab_text = if current_page == Some(0) { text_tab_clear.clone() } else { img_tab_clear.clone() };
+        let tab_name = if current_page == Some(0) { "Text" } else { "Images" };
+
+        if let Some(content_type) = &content_type {
+            previews_clear.borrow_mut().clear(content_type.clone());
+        }
 
         // 1. Start smooth visual removal (fade out)
         active_flow.add_css_class("fade-out");
@@ -198,6 +382,7 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
             while let Some(child) = active_flow.first_child() {
                 active_flow.remove(&child);
             }
+            active_tab_text.set_text(&format!("{} (0)", tab_name));
 
             // Background thread handles DB work
             std::thread::spawn(move || {
@@ -208,10 +393,24 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
         });
     });
 
+    // --- Export Visible Results ---
+    let db_export = db.clone();
+    let win_export = window.clone();
+    let status_export = status_label.clone();
+    export_button.connect_clicked(move |_| {
+        let ids = shown.ids();
+        if ids.is_empty() {
+            status_export.set_text("Nothing to export");
+            return;
+        }
+        choose_export_file(&win_export, ids, db_export.clone(), status_export.clone());
+    });
+
     // CSS for divider and animations
     let provider_extra = gtk4::CssProvider::new();
     provider_extra.load_from_data("
         .divider { margin: 4px 0; opacity: 0.1; }
+        .date-header { font-size: 11px; font-weight: 700; opacity: 0.5; margin-top: 8px; }
         .fade-out { 
             opacity: 0; 
             transition: opacity 300ms ease-out;
@@ -230,6 +429,18 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
         populate_search(&entry.text());
     });
 
+    // `history.refresh`: drop the search and show the latest entries
+    let actions = gio::SimpleActionGroup::new();
+    let refresh = gio::SimpleAction::new("refresh", None);
+    let search_refresh = search_entry.clone();
+    let populate_refresh = populate.clone();
+    refresh.connect_activate(move |_, _| {
+        search_refresh.set_text("");
+        populate_refresh("");
+    });
+    actions.add_action(&refresh);
+    window.insert_action_group("history", Some(&actions));
+
     // ── Styles ──────────────────
     let provider = gtk4::CssProvider::new();
     provider.load_from_data("
@@ -247,11 +458,19 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
         card.text-card {
             min-width: 580px;
         }
+        .entry-note {
+            font-style: italic;
+            font-size: 13px;
+        }
         card:hover {
             background-color: alpha(@theme_fg_color, 0.05);
             border-color: alpha(@theme_fg_color, 0.2);
             box-shadow: 0 4px 12px alpha(black, 0.1);
         }
+        card.selected {
+            background-color: alpha(@theme_selected_bg_color, 0.15);
+            border-color: @theme_selected_bg_color;
+        }
 
         notebook header {
             background-color: transparent;
@@ -275,57 +494,327 @@ pub fn show_history(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
         );
     }
 
-    // ── Keyboard: Esc closes ───
+    // ── Keyboard: Esc closes, Ctrl+Tab / Ctrl+Shift+Tab / Ctrl+1..3 switch tabs ───
+    // On the Text and Images tabs, Up/Down highlight a card, Enter restores it
+    // and Tab flips between the two.
+    // Runs in the capture phase: the notebook itself binds Ctrl+Tab to moving
+    // focus out of it, and the search entry keeps focus while cards are picked.
+    let selection_page = selection.clone();
+    notebook.connect_switch_page(move |_, _, _| selection_page.clear());
     let win_key = window.clone();
+    let notebook_key = notebook.clone();
+    let lists = [(text_flow.clone(), text_scrolled.clone()), (img_flow.clone(), img_scrolled.clone())];
     let key_ctl = gtk4::EventControllerKey::new();
-    key_ctl.connect_key_pressed(move |_, key, _code, _mods| {
+    key_ctl.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    key_ctl.connect_key_pressed(move |_, key, _code, mods| {
         if key == gdk4::Key::Escape {
             win_key.close();
             return glib::Propagation::Stop;
         }
-        glib::Propagation::Proceed
+        if !mods.contains(gdk4::ModifierType::CONTROL_MASK) {
+            let page = notebook_key.current_page().unwrap_or(0);
+            let Some((flow, scrolled)) = lists.get(page as usize) else {
+                return glib::Propagation::Proceed;
+            };
+            match key {
+                gdk4::Key::Up | gdk4::Key::KP_Up => selection.step(flow, scrolled, false),
+                gdk4::Key::Down | gdk4::Key::KP_Down => selection.step(flow, scrolled, true),
+                gdk4::Key::Return | gdk4::Key::KP_Enter | gdk4::Key::ISO_Enter => {
+                    let Some(card) = selection.current_in(flow) else {
+                        return glib::Propagation::Proceed;
+                    };
+                    let copy_only = mods.contains(gdk4::ModifierType::SHIFT_MASK);
+                    if let Err(e) = card.activate_action("card.restore", Some(&copy_only.to_variant())) {
+                        log::warn!("Failed to restore the selected entry: {}", e);
+                    }
+                }
+                gdk4::Key::Tab | gdk4::Key::ISO_Left_Tab => notebook_key.set_current_page(Some(1 - page)),
+                _ => return glib::Propagation::Proceed,
+            }
+            return glib::Propagation::Stop;
+        }
+        let n_pages = notebook_key.n_pages();
+        let current = notebook_key.current_page().unwrap_or(0);
+        let target = match key {
+            gdk4::Key::Tab if !mods.contains(gdk4::ModifierType::SHIFT_MASK) => (current + 1) % n_pages,
+            gdk4::Key::ISO_Left_Tab | gdk4::Key::Tab => (current + n_pages - 1) % n_pages,
+            gdk4::Key::_1 | gdk4::Key::KP_1 => 0,
+            gdk4::Key::_2 | gdk4::Key::KP_2 => 1,
+            gdk4::Key::_3 | gdk4::Key::KP_3 => 2,
+            _ => return glib::Propagation::Proceed,
+        };
+        notebook_key.set_current_page(Some(target));
+        glib::Propagation::Stop
     });
     window.add_controller(key_ctl);
 
+    // Delete removes the entry of the focused card
+    for flow in [&text_flow, &img_flow] {
+        let flow_key = flow.clone();
+        let key_ctl = gtk4::EventControllerKey::new();
+        key_ctl.connect_key_pressed(move |_, key, _code, _mods| {
+            if !matches!(key, gdk4::Key::Delete | gdk4::Key::KP_Delete) {
+                return glib::Propagation::Proceed;
+            }
+            let card = flow_key.focus_child().and_then(|row| row.first_child());
+            match card.map(|card| card.activate_action("card.delete", None)) {
+                Some(Ok(())) => glib::Propagation::Stop,
+                _ => glib::Propagation::Proceed,
+            }
+        });
+        flow.add_controller(key_ctl);
+    }
+
+    // Left/Right past the edge of a tab's entries moves to the adjacent tab
+    for (flow, page) in [(&text_flow, 0u32), (&img_flow, 1u32), (&snippet_flow, 2u32)] {
+        let notebook_nav = notebook.clone();
+        let flows = [text_flow.clone(), img_flow.clone(), snippet_flow.clone()];
+        flow.connect_keynav_failed(move |_, direction| {
+            let target = match direction {
+                gtk4::DirectionType::Left if page > 0 => page - 1,
+                gtk4::DirectionType::Right if page + 1 < notebook_nav.n_pages() => page + 1,
+                _ => return glib::Propagation::Proceed,
+            };
+            notebook_nav.set_current_page(Some(target));
+            flows[target as usize].child_focus(gtk4::DirectionType::TabForward);
+            glib::Propagation::Stop
+        });
+    }
+
     window.present();
+    window
 }
 
-/// Build a GTK widget for a single history entry.
-fn build_entry_widget(
-    entry: &HistoryEntry,
-    db: &Arc<Mutex<Database>>,
+/// Raise a dialog opened by [`show_history`], refreshed to show the latest
+/// entries.
+pub fn present_history(window: &gtk4::Window) {
+    if let Err(e) = window.activate_action("history.refresh", None) {
+        log::warn!("Failed to refresh the history dialog: {}", e);
+    }
+    window.present();
+}
+
+/// A non-interactive "TODAY" / "YESTERDAY" / … row for the history lists.
+fn build_date_header(group: &str) -> gtk4::FlowBoxChild {
+    let label = gtk4::Label::builder()
+        .label(group.to_uppercase())
+        .xalign(0.0)
+        .css_classes(["date-header"])
+        .build();
+    let child = gtk4::FlowBoxChild::new();
+    child.set_child(Some(&label));
+    child.set_can_focus(false);
+    child.set_can_target(false);
+    child
+}
+
+/// Ask for a destination file and export the given entries to it. The format
+/// follows the chosen extension (`.json`, `.txt` or `.csv`).
+fn choose_export_file(
     window: &gtk4::Window,
-    clipboard: &Arc<Mutex<Clipboard>>,
-) -> gtk4::Widget {
+    ids: Vec<i64>,
+    db: Arc<Mutex<Database>>,
+    status_label: gtk4::Label,
+) {
+    let dialog = gtk4::FileChooserNative::new(
+        Some("Export Results"),
+        Some(window),
+        gtk4::FileChooserAction::Save,
+        Some("Export"),
+        Some("Cancel"),
+    );
+    dialog.set_current_name("clipsnap-export.json");
+    for (name, pattern) in [("JSON", "*.json"), ("Text", "*.txt"), ("CSV (text entries)", "*.csv")] {
+        let filter = gtk4::FileFilter::new();
+        filter.set_name(Some(name));
+        filter.add_pattern(pattern);
+        dialog.add_filter(&filter);
+    }
+
+    // The native dialog must outlive this function; drop our handle once it answers.
+    let keep_alive = RefCell::new(Some(dialog.clone()));
+    dialog.connect_response(move |dialog, response| {
+        if response == gtk4::ResponseType::Accept {
+            if let Some(path) = dialog.file().and_then(|f| f.path()) {
+                let entries: Vec<HistoryEntry> = match db.lock() {
+                    Ok(db) => ids.iter().filter_map(|id| db.get_entry(*id).ok().flatten()).collect(),
+                    Err(_) => Vec::new(),
+                };
+                match export::export_entries(&entries, ExportFormat::from_path(&path), &path) {
+                    Ok(count) => status_label.set_text(&format!("Exported {} entries", count)),
+                    Err(e) => {
+                        log::error!("Export failed: {:#}", e);
+                        status_label.set_text("Export failed");
+                    }
+                }
+            }
+        }
+        dialog.destroy();
+        keep_alive.borrow_mut().take();
+    });
+    dialog.show();
+}
+
+/// What a card shows: a history entry, or a snippet with its name.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CardKind<'a> {
+    History,
+    Snippet(&'a str),
+}
+
+/// Whether a snippet matches a lowercased search string.
+fn snippet_matches(snippet: &Snippet, needle: &str) -> bool {
+    needle.is_empty()
+        || snippet.name.to_lowercase().contains(needle)
+        || snippet.text.to_lowercase().contains(needle)
+}
+
+/// What every card needs to restore and act on its entry.
+#[derive(Clone)]
+struct CardContext {
+    db: Arc<Mutex<Database>>,
+    window: gtk4::Window,
+    clipboard: Arc<Mutex<Clipboard>>,
+    /// `ui.auto_paste`: paste restored entries, not just copy them.
+    auto_paste: bool,
+    paste_delay_ms: u64,
+    paste_method: PasteMethod,
+    /// `ui.notification_duration`, in seconds.
+    notification_duration: u32,
+    time_format: TimeFormat,
+    /// strftime pattern for absolute times, already validated.
+    time_pattern: Rc<str>,
+    developer_actions: bool,
+    /// Set with `history.bump_on_restore`: restored entries move to the top,
+    /// and the monitor is told about them so it doesn't store a second copy.
+    bump_on_restore: Option<clipboard::SharedHashes>,
+    /// Rendered texts and thumbnails, kept across dialog openings.
+    previews: SharedPreviewCache,
+    /// What the lists show, updated as cards are deleted.
+    shown: ShownEntries,
+}
+
+/// The history entries listed in the dialog, for "export visible results",
+/// the "N text, M images" status line and the tab totals.
+#[derive(Clone)]
+struct ShownEntries {
+    status_label: gtk4::Label,
+    /// The Text and Images tab labels.
+    tab_labels: [gtk4::Label; 2],
+    /// Ids and types of the listed entries, in order.
+    entries: Rc<RefCell<Vec<(i64, ContentType)>>>,
+}
+
+impl ShownEntries {
+    fn set<'a>(&self, entries: impl Iterator<Item = &'a HistoryEntry>) {
+        *self.entries.borrow_mut() = entries.map(|e| (e.id, e.content_type.clone())).collect();
+        self.update_status();
+    }
+
+    fn remove(&self, id: i64) {
+        self.entries.borrow_mut().retain(|(shown, _)| *shown != id);
+        self.update_status();
+    }
+
+    fn ids(&self) -> Vec<i64> {
+        self.entries.borrow().iter().map(|(id, _)| *id).collect()
+    }
+
+    /// Tab labels show totals, not just what is listed.
+    fn set_totals(&self, [text, images]: [usize; 2]) {
+        self.tab_labels[0].set_text(&format!("Text ({})", text));
+        self.tab_labels[1].set_text(&format!("Images ({})", images));
+    }
+
+    fn update_status(&self) {
+        let entries = self.entries.borrow();
+        let text = entries.iter().filter(|(_, ct)| *ct == ContentType::Text).count();
+        self.status_label.set_text(&format!("{} text, {} images", text, entries.len() - text));
+    }
+}
+
+/// The card highlighted with Up/Down, which Enter restores.
+#[derive(Clone, Default)]
+struct CardSelection(Rc<RefCell<Option<gtk4::Widget>>>);
+
+impl CardSelection {
+    fn clear(&self) {
+        if let Some(card) = self.0.borrow_mut().take() {
+            card.remove_css_class("selected");
+        }
+    }
+
+    /// The highlighted card, if it is in `flow`.
+    fn current_in(&self, flow: &gtk4::FlowBox) -> Option<gtk4::Widget> {
+        self.0.borrow().clone().filter(|card| card.is_ancestor(flow) && card.is_sensitive())
+    }
+
+    /// Highlight the next or previous card in `flow`, or its first or last
+    /// one, and scroll it into view.
+    fn step(&self, flow: &gtk4::FlowBox, scrolled: &gtk4::ScrolledWindow, forward: bool) {
+        // Date headers and dividers share the list; cards being deleted are skipped
+        let cards: Vec<gtk4::Widget> = std::iter::successors(flow.first_child(), |row| row.next_sibling())
+            .filter_map(|row| row.first_child())
+            .filter(|child| child.has_css_class("card") && child.is_sensitive())
+            .collect();
+        let current = self.current_in(flow).and_then(|card| cards.iter().position(|c| *c == card));
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1).min(cards.len() - 1),
+            (Some(i), false) => i.saturating_sub(1),
+            (None, true) => 0,
+            (None, false) => cards.len().saturating_sub(1),
+        };
+        let Some(card) = cards.get(next) else { return };
+
+        self.clear();
+        card.add_css_class("selected");
+        if let Some(bounds) = card.compute_bounds(flow) {
+            let top = bounds.y() as f64;
+            scrolled.vadjustment().clamp_page(top, top + bounds.height() as f64);
+        }
+        *self.0.borrow_mut() = Some(card.clone());
+    }
+}
+
+/// Number of text and image entries in the history.
+fn tab_totals(db: &Database) -> [usize; 2] {
+    [
+        db.count(Some(ContentType::Text)).unwrap_or(0),
+        db.count(Some(ContentType::Image)).unwrap_or(0),
+    ]
+}
+
+/// Build a GTK widget for a single history entry or snippet.
+fn build_entry_widget(entry: &HistoryEntry, kind: CardKind, ctx: &CardContext) -> gtk4::Widget {
+    let CardContext { db, window, clipboard, auto_paste, paste_delay_ms, paste_method, notification_duration, previews, .. } = ctx;
+    let (auto_paste, paste_delay_ms, paste_method, notification_duration) =
+        (*auto_paste, *paste_delay_ms, *paste_method, *notification_duration);
     let card = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
     card.add_css_class("card");
     card.set_cursor(Some(&gdk4::Cursor::from_name("pointer", None).unwrap()));
 
     match entry.content_type {
         ContentType::Image => {
-            card.set_size_request(150, -1);
-            // Thumbnail
-            if let Some(thumb_bytes) = &entry.thumbnail {
-                if let Some(pixbuf) = load_pixbuf_from_png(thumb_bytes) {
-                    let texture = gdk4::Texture::for_pixbuf(&pixbuf);
-                    let picture = gtk4::Picture::for_paintable(&texture);
-                    picture.set_size_request(150, 110);
-                    picture.set_can_shrink(false);
-                    card.append(&picture);
+            card.set_size_request(screenshot::thumbnail_size() as i32, -1);
+            let thumbnail = entry.thumbnail.as_deref().filter(|t| !t.is_empty()).and_then(|bytes| {
+                let texture = previews.borrow_mut().texture(entry.content_hash.as_deref(), bytes, thumbnail_texture);
+                if texture.is_none() {
+                    log::warn!("Failed to decode thumbnail of entry {}", entry.id);
                 }
+                texture
+            });
+            match thumbnail {
+                Some(texture) => card.append(&thumbnail_image(&texture)),
+                None => card.append(&build_thumbnail_placeholder(&card, entry, db, previews)),
             }
         }
         ContentType::Text => {
             card.add_css_class("text-card");
             card.set_hexpand(true);
-            let text = entry.text_content.as_deref().unwrap_or("");
-            let preview = if text.len() > 150 {
-                format!("{}…", &text[..text.char_indices().nth(150).map(|(i, _)| i).unwrap_or(text.len())])
-            } else {
-                text.to_string()
-            };
+            let preview =
+                previews.borrow_mut().text_preview(entry.content_hash.as_deref(), entry.text_content.as_deref().unwrap_or(""));
 
-            let label = gtk4::Label::new(Some(&preview));
+            let label = gtk4::Label::new(Some(&*preview));
             label.set_wrap(true);
             label.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
             label.set_xalign(0.0);
@@ -336,20 +825,61 @@ fn build_entry_widget(
         }
     }
 
+    // The entry's note, kept hidden while there is none so editing can fill it in
+    let note_label = gtk4::Label::new(entry.note.as_deref());
+    note_label.add_css_class("entry-note");
+    note_label.set_wrap(true);
+    note_label.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
+    note_label.set_xalign(0.0);
+    note_label.set_visible(entry.note.is_some());
+    card.append(&note_label);
+
     // Card Footer (Type + Time)
     let footer = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
     footer.add_css_class("dim-label");
 
-    let ago = format_timestamp(entry.created_at);
-    let ts_label = gtk4::Label::new(Some(&ago));
-    footer.append(&ts_label);
+    let caption = match kind {
+        CardKind::History => format_timestamp(entry.created_at, ctx.time_format, &ctx.time_pattern),
+        CardKind::Snippet(name) => name.to_string(),
+    };
+    let caption_label = gtk4::Label::new(Some(&caption));
+    if kind == CardKind::History {
+        // The other format on hover
+        let other = match ctx.time_format {
+            TimeFormat::Relative => TimeFormat::Absolute,
+            TimeFormat::Absolute => TimeFormat::Relative,
+        };
+        caption_label.set_tooltip_text(Some(&format_timestamp(entry.created_at, other, &ctx.time_pattern)));
+    }
+    caption_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    footer.append(&caption_label);
 
     let spacer = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
     spacer.set_hexpand(true);
     footer.append(&spacer);
 
+    if entry.pinned {
+        let pin_icon = gtk4::Image::from_icon_name("view-pin-symbolic");
+        pin_icon.set_tooltip_text(Some("Pinned: history cleanup keeps this entry"));
+        footer.append(&pin_icon);
+    }
+
+    if entry.use_count > 0 {
+        let uses_label = gtk4::Label::new(Some(&format!("{}×", entry.use_count)));
+        uses_label.set_tooltip_text(Some(&format!("Restored {} time(s)", entry.use_count)));
+        footer.append(&uses_label);
+    }
+
     if entry.content_type == ContentType::Image {
-        let size_str = format_size(entry.file_size);
+        let size_str = match (entry.width, entry.height) {
+            (Some(w), Some(h)) if w > 0 && h > 0 => format!("{}×{} · {}", w, h, format_size(entry.file_size)),
+            _ => format_size(entry.file_size),
+        };
+        if let Some(method) = entry.capture_method {
+            let method_label = gtk4::Label::new(Some(method.label()));
+            method_label.set_tooltip_text(Some(&format!("Search method:{} to list these", method.to_str())));
+            footer.append(&method_label);
+        }
         let size_label = gtk4::Label::new(Some(&size_str));
         footer.append(&size_label);
     } else {
@@ -357,83 +887,398 @@ fn build_entry_widget(
         footer.append(&icon);
     }
 
+    // `card.restore` for clicks and Enter, with a "copy only" flag set by
+    // holding Shift; `card.delete` for the Delete key
+    let actions = gio::SimpleActionGroup::new();
+    card.insert_action_group("card", Some(&actions));
+
+    // Delete button
+    if kind == CardKind::History {
+        let delete = gio::SimpleAction::new("delete", None);
+        // Weak, as the card owns the action
+        let (card_delete, entry_delete, ctx_delete) = (card.downgrade(), entry.clone(), ctx.clone());
+        delete.connect_activate(move |_, _| {
+            if let Some(card) = card_delete.upgrade() {
+                delete_card(&card, &entry_delete, &ctx_delete);
+            }
+        });
+        actions.add_action(&delete);
+
+        let delete_btn = gtk4::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Delete (Del)")
+            .action_name("card.delete")
+            .css_classes(["flat"])
+            .build();
+        footer.append(&delete_btn);
+    }
+
     card.append(&footer);
 
     // Click handler – restore to clipboard
-    let entry_id = entry.id;
-    let content_type = entry.content_type.clone();
+    let entry_click = entry.clone();
     let db_click = db.clone();
     let win_click = window.clone();
-
-    // We store text/image data needed for the click handler
-    let text_content = entry.text_content.clone();
-    let image_data = entry.image_data.clone();
     let cb_click = clipboard.clone();
+    let is_history = kind == CardKind::History;
+    // Snippets stay where they are
+    let bump = ctx.bump_on_restore.clone().filter(|_| is_history);
 
-    let gesture = gtk4::GestureClick::new();
-    gesture.connect_released(move |_gesture, _n, _x, _y| {
-        restore_entry_to_clipboard(
-            entry_id,
-            &content_type,
-            text_content.as_deref(),
-            image_data.as_deref(),
-            &db_click,
-            &cb_click,
-        );
+    let restore = gio::SimpleAction::new("restore", Some(glib::VariantTy::BOOLEAN));
+    restore.connect_activate(move |_, copy_only| {
+        let restored = restore_entry_to_clipboard(&entry_click, &db_click, &cb_click, bump.as_ref(), notification_duration);
+        // Snippet ids are not history ids
+        if restored && is_history {
+            record_use(entry_click.id, &db_click, bump.is_some());
+        }
         win_click.close();
+        if !auto_paste || copy_only.and_then(|v| v.get::<bool>()).unwrap_or(false) {
+            return;
+        }
 
         // --- Auto-Paste Simulation ---
-        // Wait a short duration for the window to hide and focus to return to previous app.
-        glib::timeout_add_local_once(std::time::Duration::from_millis(150), move || {
-            use enigo::{Direction, Keyboard, Settings};
-            if let Ok(mut enigo) = Enigo::new(&Settings::default()) {
-                let _ = enigo.key(Key::Control, Direction::Press);
-                let _ = enigo.key(Key::Unicode('v'), Direction::Click);
-                let _ = enigo.key(Key::Control, Direction::Release);
-            }
+        // Wait for the window to hide and focus to return to the previous app
+        // (`ui.auto_paste_delay_ms`).
+        let typed_text = entry_click.text_content.clone().filter(|_| entry_click.content_type == ContentType::Text);
+        glib::timeout_add_local_once(std::time::Duration::from_millis(paste_delay_ms), move || {
+            paste::simulate_paste(paste_method, typed_text.as_deref(), notification_duration);
         });
     });
+    actions.add_action(&restore);
+
+    let gesture = gtk4::GestureClick::new();
+    gesture.connect_released(move |gesture, _n, _x, _y| {
+        let copy_only = gesture.current_event_state().contains(gdk4::ModifierType::SHIFT_MASK);
+        restore.activate(Some(&copy_only.to_variant()));
+    });
     card.add_controller(gesture);
 
+    // Right-click for the card's menu
+    let menu = build_entry_menu(&card, &note_label, entry, kind, ctx);
+    let menu_gesture = gtk4::GestureClick::new();
+    menu_gesture.set_button(3);
+    menu_gesture.connect_released(move |_gesture, _n, x, y| {
+        menu.set_pointing_to(Some(&gdk4::Rectangle::new(x as i32, y as i32, 1, 1)));
+        menu.popup();
+    });
+    card.add_controller(menu_gesture);
+
     card.upcast()
 }
 
-/// Restore a history entry to the clipboard.
+/// Show a thumbnail at a fixed logical size; it may be stored at a multiple of
+/// that for HiDPI, and GTK downscales it.
+fn thumbnail_image(texture: &gdk4::Texture) -> gtk4::Image {
+    let image = gtk4::Image::from_paintable(Some(texture));
+    image.set_pixel_size(screenshot::thumbnail_size() as i32);
+    image
+}
+
+/// Stand-in for a thumbnail that is missing or can't be decoded, with a button
+/// to re-derive it from the full image.
+fn build_thumbnail_placeholder(
+    card: &gtk4::Box,
+    entry: &HistoryEntry,
+    db: &Arc<Mutex<Database>>,
+    previews: &SharedPreviewCache,
+) -> gtk4::Box {
+    let placeholder = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+    let size = screenshot::thumbnail_size() as i32;
+    placeholder.set_size_request(size, size * 11 / 15);
+    placeholder.set_valign(gtk4::Align::Center);
+
+    let icon = gtk4::Image::from_icon_name("image-missing-symbolic");
+    icon.set_pixel_size(48);
+    icon.add_css_class("dim-label");
+    placeholder.append(&icon);
+
+    let size_label = gtk4::Label::new(Some(&format_size(entry.file_size)));
+    size_label.add_css_class("dim-label");
+    placeholder.append(&size_label);
+
+    let regenerate = gtk4::Button::with_label("Regenerate thumbnail");
+    regenerate.add_css_class("flat");
+    placeholder.append(&regenerate);
+
+    let (entry_id, hash) = (entry.id, entry.content_hash.clone());
+    let (db, card, placeholder_ref, previews) = (db.clone(), card.clone(), placeholder.clone(), previews.clone());
+    regenerate.connect_clicked(move |button| {
+        button.set_sensitive(false);
+        button.set_label("Regenerating…");
+        let (db, card, placeholder, button) = (db.clone(), card.clone(), placeholder_ref.clone(), button.clone());
+        let (hash, previews) = (hash.clone(), previews.clone());
+        glib::MainContext::default().spawn_local(async move {
+            // Thumbnailing a large image takes a while; keep it off the main loop
+            let result = gio::spawn_blocking(move || database::regenerate_thumbnail(&db, entry_id)).await;
+            // The old thumbnail's failed decode is cached under the same hash
+            previews.borrow_mut().forget(hash.as_deref());
+            match result {
+                Ok(Ok(bytes)) => match thumbnail_texture(&bytes) {
+                    Some(texture) => {
+                        card.remove(&placeholder);
+                        card.prepend(&thumbnail_image(&texture));
+                    }
+                    None => button.set_label("Thumbnail still unreadable"),
+                },
+                Ok(Err(e)) => {
+                    log::error!("Failed to regenerate thumbnail of entry {}: {:#}", entry_id, e);
+                    button.set_label("Image can't be read");
+                }
+                Err(_) => button.set_label("Image can't be read"),
+            }
+        });
+    });
+    placeholder
+}
+
+/// Right-click menu for a card: "Preview" for images, "Edit note" for history
+/// entries, "Delete" for snippets, plus the developer "Copy raw bytes" action.
+fn build_entry_menu(
+    card: &gtk4::Box,
+    note_label: &gtk4::Label,
+    entry: &HistoryEntry,
+    kind: CardKind,
+    ctx: &CardContext,
+) -> gtk4::Popover {
+    let CardContext { db, window, clipboard, developer_actions, .. } = ctx;
+    let popover = gtk4::Popover::new();
+    popover.set_has_arrow(false);
+    popover.set_parent(card);
+    // Popovers must be unparented explicitly before their parent goes away
+    let popover_ref = popover.clone();
+    card.connect_destroy(move |_| popover_ref.unparent());
+
+    let items = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    let entry_id = entry.id;
+
+    if entry.content_type == ContentType::Image {
+        let preview_btn = gtk4::Button::with_label("Preview");
+        preview_btn.add_css_class("flat");
+        let (db, window, popover) = (db.clone(), window.clone(), popover.clone());
+        preview_btn.connect_clicked(move |_| {
+            popover.popdown();
+            image_preview::show_image_preview(&window, db.clone(), entry_id);
+        });
+        items.append(&preview_btn);
+    }
+
+    if kind == CardKind::History {
+        let note_btn = gtk4::Button::with_label("Edit note…");
+        note_btn.add_css_class("flat");
+        let editor = build_note_editor(card, note_label, entry_id, db);
+        let popover = popover.clone();
+        note_btn.connect_clicked(move |_| {
+            popover.popdown();
+            editor.popup();
+        });
+        items.append(&note_btn);
+    }
+
+    if entry.pinned {
+        let unpin_btn = gtk4::Button::with_label("Unpin");
+        unpin_btn.add_css_class("flat");
+        let (db, popover) = (db.clone(), popover.clone());
+        unpin_btn.connect_clicked(move |button| {
+            popover.popdown();
+            if let Ok(db) = db.lock() {
+                match db.set_pinned(entry_id, false) {
+                    Ok(()) => button.set_visible(false),
+                    Err(e) => log::error!("Failed to unpin entry {}: {}", entry_id, e),
+                }
+            }
+        });
+        items.append(&unpin_btn);
+    }
+
+    if let CardKind::Snippet(_) = kind {
+        let delete_btn = gtk4::Button::with_label("Delete snippet");
+        delete_btn.add_css_class("flat");
+        let (db, card, popover) = (db.clone(), card.clone(), popover.clone());
+        delete_btn.connect_clicked(move |_| {
+            popover.popdown();
+            if let Ok(db) = db.lock() {
+                if let Err(e) = db.delete_snippet(entry_id) {
+                    log::error!("Failed to delete snippet {}: {}", entry_id, e);
+                    return;
+                }
+            }
+            remove_card(&card);
+        });
+        items.append(&delete_btn);
+    }
+
+    if *developer_actions && kind == CardKind::History {
+        let raw_btn = gtk4::Button::with_label(match entry.content_type {
+            ContentType::Text => "Copy raw bytes (base64)",
+            ContentType::Image => "Copy raw bytes (hex)",
+        });
+        raw_btn.add_css_class("flat");
+        let (db, clipboard, popover) = (db.clone(), clipboard.clone(), popover.clone());
+        raw_btn.connect_clicked(move |_| {
+            popover.popdown();
+            copy_raw_bytes(entry_id, &db, &clipboard);
+        });
+        items.append(&raw_btn);
+    }
+
+    popover.set_child(Some(&items));
+    popover
+}
+
+/// Delete a history card's entry in the background, then fade the card out
+/// of its list.
+fn delete_card(card: &gtk4::Box, entry: &HistoryEntry, ctx: &CardContext) {
+    let id = entry.id;
+    let hash = entry.content_hash.clone();
+    let (db, card, shown, previews) = (ctx.db.clone(), card.clone(), ctx.shown.clone(), ctx.previews.clone());
+    card.set_sensitive(false);
+    card.add_css_class("fade-out");
+    glib::MainContext::default().spawn_local(async move {
+        let result = gio::spawn_blocking(move || {
+            let db = db.lock().map_err(|_| anyhow::anyhow!("Database lock poisoned"))?;
+            db.delete_entry(id)?;
+            Ok::<_, anyhow::Error>(tab_totals(&db))
+        })
+        .await;
+        let totals = match result {
+            Ok(Ok(totals)) => totals,
+            Ok(Err(e)) => {
+                log::error!("Failed to delete entry {}: {:#}", id, e);
+                card.remove_css_class("fade-out");
+                card.set_sensitive(true);
+                return;
+            }
+            Err(_) => return,
+        };
+        previews.borrow_mut().forget(hash.as_deref());
+        shown.remove(id);
+        shown.set_totals(totals);
+        // Let the fade finish before the list closes the gap
+        glib::timeout_add_local_once(std::time::Duration::from_millis(320), move || remove_card(&card));
+    });
+}
+
+/// Drop a card's FlowBoxChild from its list, along with the divider or date
+/// header that only it needed.
+fn remove_card(card: &gtk4::Box) {
+    let Some(row) = card.parent() else { return };
+    let Some(flow) = row.parent().and_downcast::<gtk4::FlowBox>() else { return };
+    let styled = |row: Option<gtk4::Widget>, class: &str| {
+        row.filter(|row| row.first_child().is_some_and(|child| child.has_css_class(class)))
+    };
+    let (prev, next) = (row.prev_sibling(), row.next_sibling());
+    let orphan = styled(prev.clone(), "divider")
+        .or_else(|| styled(next.clone(), "divider"))
+        .or_else(|| {
+            // The last card under its date header
+            let next_is_header = next.is_none() || styled(next, "date-header").is_some();
+            styled(prev, "date-header").filter(|_| next_is_header)
+        });
+    if let Some(orphan) = orphan {
+        flow.remove(&orphan);
+    }
+    flow.remove(&row);
+}
+
+/// Popover with an entry for editing a card's note. Enter saves it; an empty
+/// note removes it.
+fn build_note_editor(card: &gtk4::Box, note_label: &gtk4::Label, id: i64, db: &Arc<Mutex<Database>>) -> gtk4::Popover {
+    let popover = gtk4::Popover::new();
+    popover.set_parent(card);
+    let popover_ref = popover.clone();
+    card.connect_destroy(move |_| popover_ref.unparent());
+
+    let note_entry = gtk4::Entry::builder().placeholder_text("Note").width_chars(30).build();
+    popover.set_child(Some(&note_entry));
+    let entry_ref = note_entry.clone();
+    let label = note_label.clone();
+    popover.connect_show(move |_| {
+        entry_ref.set_text(if label.is_visible() { label.text().as_str() } else { "" });
+        entry_ref.grab_focus();
+    });
+
+    let (db, label, popover_ref) = (db.clone(), note_label.clone(), popover.clone());
+    note_entry.connect_activate(move |note_entry| {
+        let text = note_entry.text();
+        let note = text.trim();
+        if let Ok(db) = db.lock() {
+            if let Err(e) = db.set_note(id, Some(note)) {
+                log::error!("Failed to save note of entry {}: {}", id, e);
+                return;
+            }
+        }
+        label.set_text(note);
+        label.set_visible(!note.is_empty());
+        popover_ref.popdown();
+    });
+    popover
+}
+
+/// Copy an entry's stored bytes to the clipboard as text (developer action).
+fn copy_raw_bytes(id: i64, db: &Arc<Mutex<Database>>, clipboard: &Arc<Mutex<Clipboard>>) {
+    // The list only carries thumbnails; fetch the full row
+    let entry = match db.lock().map(|db| db.get_entry(id)) {
+        Ok(Ok(Some(entry))) => entry,
+        Ok(Ok(None)) => return,
+        Ok(Err(e)) => {
+            log::error!("Failed to load entry {}: {}", id, e);
+            return;
+        }
+        Err(_) => return,
+    };
+    let dump = export::raw_bytes_dump(&entry);
+    if let Ok(mut cb) = clipboard.lock() {
+        if let Err(e) = clipboard::set_clipboard_text(&mut cb, &dump) {
+            log::error!("Failed to copy raw bytes: {}", e);
+            return;
+        }
+    }
+    log::info!("Copied raw bytes of entry {} ({} characters)", id, dump.len());
+}
+
+/// Restore a history entry to the clipboard. Returns whether it was restored.
+/// With `hashes`, the content is recorded as already seen first, so the
+/// monitor won't store it again.
 fn restore_entry_to_clipboard(
-    id: i64,
-    content_type: &ContentType,
-    text_content: Option<&str>,
-    image_data: Option<&[u8]>,
+    entry: &HistoryEntry,
     db: &Arc<Mutex<Database>>,
     clipboard: &Arc<Mutex<Clipboard>>,
-) {
-    match content_type {
+    hashes: Option<&clipboard::SharedHashes>,
+    notification_duration: u32,
+) -> bool {
+    match entry.content_type {
         ContentType::Text => {
-            if let Some(text) = text_content {
+            if let Some(text) = entry.text_content.as_deref() {
+                if let Some(hashes) = hashes {
+                    hashes.remember_text(text);
+                }
                 if let Ok(mut cb) = clipboard.lock() {
                     if let Err(e) = clipboard::set_clipboard_text(&mut cb, text) {
                         log::error!("Failed to restore text: {}", e);
-                        return;
+                        return false;
                     }
                 }
+                notifications::notify_clipboard_restored(Some(text), entry.sensitive, notification_duration);
+                return true;
             }
+            false
         }
         ContentType::Image => {
             // image_data might be None in the list (we don't always load full data)
-            let img_bytes = if let Some(data) = image_data {
+            let img_bytes = if let Some(data) = entry.image_data.as_deref() {
                 data.to_vec()
             } else {
                 // Fetch full data from DB
                 let db = match db.lock() {
                     Ok(db) => db,
-                    Err(_) => return,
+                    Err(_) => return false,
                 };
-                match db.get_entry(id) {
+                match db.get_entry(entry.id) {
                     Ok(Some(entry)) => match entry.image_data {
                         Some(d) => d,
-                        None => return,
+                        None => return false,
                     },
-                    _ => return,
+                    _ => return false,
                 }
             };
 
@@ -443,22 +1288,47 @@ fn restore_entry_to_clipboard(
                     let rgba = img.to_rgba8();
                     let w = rgba.width() as usize;
                     let h = rgba.height() as usize;
+                    if let Some(hashes) = hashes {
+                        hashes.remember_image(rgba.as_raw());
+                    }
                     if let Ok(mut cb) = clipboard.lock() {
                         if let Err(e) = clipboard::set_clipboard_image(&mut cb, rgba.as_raw(), w, h) {
                             log::error!("Failed to restore image: {}", e);
-                            return;
+                            return false;
                         }
                     }
+                    notifications::notify_clipboard_restored(None, false, notification_duration);
+                    true
                 }
                 Err(e) => {
                     log::error!("Failed to decode image: {}", e);
-                    return;
+                    false
                 }
             }
         }
     }
 }
 
+/// Bump an entry's restore count for the "most_used" ordering, and with
+/// `move_to_top` make it the newest entry.
+fn record_use(id: i64, db: &Arc<Mutex<Database>>, move_to_top: bool) {
+    if let Ok(db) = db.lock() {
+        if let Err(e) = db.increment_use_count(id) {
+            log::warn!("Failed to record use of entry {}: {}", id, e);
+        }
+        if move_to_top {
+            if let Err(e) = db.bump_entry(id) {
+                log::warn!("Failed to move entry {} to the top: {}", id, e);
+            }
+        }
+    }
+}
+
+/// Decode PNG thumbnail bytes into a texture.
+fn thumbnail_texture(png_bytes: &[u8]) -> Option<gdk4::Texture> {
+    load_pixbuf_from_png(png_bytes).map(|pixbuf| gdk4::Texture::for_pixbuf(&pixbuf))
+}
+
 /// Load a `gdk_pixbuf::Pixbuf` from PNG bytes.
 fn load_pixbuf_from_png(png_bytes: &[u8]) -> Option<gdk_pixbuf::Pixbuf> {
     let stream = gio::MemoryInputStream::from_bytes(&glib::Bytes::from(png_bytes));
@@ -476,9 +1346,39 @@ fn format_size(bytes: i64) -> String {
     }
 }
 
+/// Whether the history lists get date headers. They only make sense when the
+/// list is in time order: searches rank by score, and the "largest" and
+/// "most used" orders ignore time.
+fn groups_by_date(query: &str, sort_order: SortOrder) -> bool {
+    query.is_empty() && !matches!(sort_order, SortOrder::Largest | SortOrder::MostUsed)
+}
+
+/// Date group heading for a Unix timestamp, using the same day-sized steps as
+/// [`format_relative`].
+fn bucket(ts: i64) -> &'static str {
+    bucket_at(ts, chrono::Utc::now().timestamp())
+}
+
+fn bucket_at(ts: i64, now: i64) -> &'static str {
+    let days = (now - ts) / 86400;
+    match days {
+        i64::MIN..=0 => "Today",
+        1 => "Yesterday",
+        2..=6 => "This week",
+        _ => "Older",
+    }
+}
+
+/// Format a Unix timestamp for an entry card, per `ui.time_format`.
+fn format_timestamp(ts: i64, format: TimeFormat, pattern: &str) -> String {
+    match format {
+        TimeFormat::Relative => format_relative(ts, chrono::Utc::now().timestamp()),
+        TimeFormat::Absolute => format_absolute(ts, pattern, &chrono::Local),
+    }
+}
+
 /// Format a Unix timestamp as a human-friendly "time ago" string.
-fn format_timestamp(ts: i64) -> String {
-    let now = chrono::Utc::now().timestamp();
+fn format_relative(ts: i64, now: i64) -> String {
     let diff = now - ts;
 
     if diff < 60 {
@@ -491,3 +1391,53 @@ fn format_timestamp(ts: i64) -> String {
         format!("{}d ago", diff / 86400)
     }
 }
+
+/// Format a Unix timestamp with a strftime `pattern` in the time zone `tz`.
+fn format_absolute<Tz: chrono::TimeZone>(ts: i64, pattern: &str, tz: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    match tz.timestamp_opt(ts, 0).single() {
+        Some(time) => time.format(pattern).to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_at() {
+        let now = 1_700_000_000;
+        assert_eq!(bucket_at(now - 30, now), "Today");
+        assert_eq!(bucket_at(now - 86_399, now), "Today");
+        assert_eq!(bucket_at(now - 86_400, now), "Yesterday");
+        assert_eq!(bucket_at(now - 3 * 86_400, now), "This week");
+        assert_eq!(bucket_at(now - 7 * 86_400, now), "Older");
+        assert_eq!(bucket_at(now + 60, now), "Today");
+    }
+
+    #[test]
+    fn test_groups_by_date_only_in_time_order() {
+        assert!(groups_by_date("", SortOrder::Newest));
+        assert!(groups_by_date("", SortOrder::Oldest));
+        assert!(!groups_by_date("", SortOrder::Largest));
+        assert!(!groups_by_date("", SortOrder::MostUsed));
+        assert!(!groups_by_date("invoice", SortOrder::Newest));
+        assert!(!groups_by_date("method:window", SortOrder::Oldest));
+    }
+
+    #[test]
+    fn test_relative_and_absolute_times() {
+        let now = 1_700_000_000;
+        assert_eq!(format_relative(now - 30, now), "just now");
+        assert_eq!(format_relative(now - 5 * 60, now), "5m ago");
+        assert_eq!(format_relative(now - 3 * 3600, now), "3h ago");
+        assert_eq!(format_relative(now - 2 * 86_400, now), "2d ago");
+
+        assert_eq!(format_absolute(now, crate::config::DEFAULT_TIME_PATTERN, &chrono::Utc), "2023-11-14 22:13");
+        assert_eq!(format_absolute(now, "%Y-%m-%dT%H:%M:%S", &chrono::Utc), "2023-11-14T22:13:20");
+        assert_eq!(format_absolute(now, "%I:%M %p", &chrono::Utc), "10:13 PM");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::database::Database;
+use gtk4::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Decoded RGBA pixels ready to become a texture.
+struct DecodedImage {
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+/// Open a full-resolution preview of an image entry.
+///
+/// The blob is fetched and decoded on a background thread while a spinner is
+/// shown; only the texture is created on the main loop. If the window is
+/// closed first, the decode is skipped or its result dropped.
+pub fn show_image_preview(parent: &gtk4::Window, db: Arc<Mutex<Database>>, id: i64) {
+    let window = gtk4::Window::builder()
+        .transient_for(parent)
+        .title("Image Preview")
+        .default_width(800)
+        .default_height(600)
+        .build();
+
+    let spinner = gtk4::Spinner::builder()
+        .spinning(true)
+        .width_request(48)
+        .height_request(48)
+        .halign(gtk4::Align::Center)
+        .valign(gtk4::Align::Center)
+        .build();
+    window.set_child(Some(&spinner));
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let cancel_on_close = cancelled.clone();
+    window.connect_close_request(move |_| {
+        cancel_on_close.store(true, Ordering::Relaxed);
+        glib::Propagation::Proceed
+    });
+
+    // Esc closes the preview
+    let win_key = window.clone();
+    let key_ctl = gtk4::EventControllerKey::new();
+    key_ctl.connect_key_pressed(move |_, key, _code, _mods| {
+        if key == gdk4::Key::Escape {
+            win_key.close();
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    });
+    window.add_controller(key_ctl);
+
+    let window_ref = glib::SendWeakRef::from(window.downgrade());
+    std::thread::spawn(move || {
+        let result = load_image(&db, id, &cancelled);
+        if cancelled.load(Ordering::Relaxed) {
+            return;
+        }
+        glib::idle_add_once(move || {
+            let Some(window) = window_ref.upgrade() else {
+                return;
+            };
+            match result {
+                Ok(decoded) => {
+                    let bytes = glib::Bytes::from_owned(decoded.rgba);
+                    let texture = gdk4::MemoryTexture::new(
+                        decoded.width as i32,
+                        decoded.height as i32,
+                        gdk4::MemoryFormat::R8g8b8a8,
+                        &bytes,
+                        decoded.width as usize * 4,
+                    );
+                    let picture = gtk4::Picture::for_paintable(&texture);
+                    picture.set_can_shrink(true);
+                    window.set_child(Some(&picture));
+                }
+                Err(e) => {
+                    log::error!("Failed to load image preview: {:#}", e);
+                    window.set_child(Some(&gtk4::Label::new(Some("Could not load image"))));
+                }
+            }
+        });
+    });
+
+    window.present();
+}
+
+/// Fetch the image blob and decode it, checking for cancellation in between.
+fn load_image(db: &Mutex<Database>, id: i64, cancelled: &AtomicBool) -> anyhow::Result<DecodedImage> {
+    let png = {
+        let db = db.lock().map_err(|_| anyhow::anyhow!("Database lock poisoned"))?;
+        db.get_entry(id)?
+            .and_then(|entry| entry.image_data)
+            .ok_or_else(|| anyhow::anyhow!("Entry {} has no image data", id))?
+    };
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(anyhow::anyhow!("Preview closed"));
+    }
+
+    let rgba = image::load_from_memory(&png)?.to_rgba8();
+    Ok(DecodedImage {
+        width: rgba.width(),
+        height: rgba.height(),
+        rgba: rgba.into_raw(),
+    })
+}
@@ -0,0 +1,33 @@
+//! Overlays as layer-shell surfaces, on Wayland compositors that implement
+//! the protocol (wlroots-based ones such as Sway and Hyprland, and KDE).
+//! There a fullscreen window can't be put on a chosen monitor or above the
+//! panels, while an overlay-layer surface sits over everything and takes the
+//! keyboard. Without the `wayland` feature, on X11, or where the compositor
+//! lacks the protocol (GNOME), overlays stay fullscreen windows.
+
+use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+
+/// The monitor an overlay should cover as a layer surface, or `None` when
+/// GTK isn't running on Wayland or the compositor has no layer-shell.
+pub fn overlay_monitor() -> Option<gdk4::Monitor> {
+    if !gtk4_layer_shell::is_supported() {
+        return None;
+    }
+    // A layer surface covers a single monitor
+    super::overlay::primary_monitor()
+}
+
+/// Turn `window`, before it is shown, into an overlay-layer surface covering
+/// `monitor` and taking all keyboard input.
+pub fn init_overlay(window: &gtk4::Window, monitor: &gdk4::Monitor) {
+    window.init_layer_shell();
+    window.set_layer(Layer::Overlay);
+    window.set_namespace("clipsnap-overlay");
+    window.set_monitor(monitor);
+    for edge in [Edge::Left, Edge::Right, Edge::Top, Edge::Bottom] {
+        window.set_anchor(edge, true);
+    }
+    // Cover panels and docks too, not just the area they leave free
+    window.set_exclusive_zone(-1);
+    window.set_keyboard_mode(KeyboardMode::Exclusive);
+}
@@ -1,2 +1,7 @@
 pub mod history_dialog;
+pub mod image_preview;
+#[cfg(feature = "wayland")]
+pub mod layer_shell;
 pub mod overlay;
+pub mod preview_cache;
+pub mod theme;
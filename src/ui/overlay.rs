@@ -1,9 +1,15 @@
-use crate::{clipboard, database::Database, notifications, screenshot};
+use crate::models::{Rectangle, SelectionMode};
+use crate::capture::{self, BaseImage, CaptureSession, ClickSelection};
+use crate::clipboard::SharedHashes;
+use crate::color;
+use crate::config::{CaptureOutput, Config};
+use crate::layout::{self, DesktopLayout};
+use crate::{database::Database, notifications, screenshot};
 use arboard::Clipboard;
 use cairo;
 use gdk4;
 use gtk4::prelude::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
@@ -11,43 +17,112 @@ use std::sync::{Arc, Mutex};
 struct OverlayState {
     start: Option<(f64, f64)>,
     current: Option<(f64, f64)>,
+    /// Opacity of the instruction hint; fades to 0 once dragging begins.
+    hint_alpha: f64,
+    /// Areas marked with a right-drag, hidden in the capture
+    /// (`capture.redact_style`).
+    redactions: Vec<(f64, f64, f64, f64)>,
+    redact_start: Option<(f64, f64)>,
+    redact_current: Option<(f64, f64)>,
+    /// The first corner in click-click mode (`capture.selection_mode`).
+    clicks: ClickSelection,
 }
 
-/// Show a transparent overlay, let the user select an area, and capture it.
-pub fn show_overlay(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard: Arc<Mutex<Clipboard>>) {
-    // --- 1. Calculate total bounding box of all monitors with proper scaling ---
-    let mut min_x = 0;
-    let mut min_y = 0;
-    let mut max_x = 0;
-    let mut max_y = 0;
-    let mut scale_factor = 1.0;
+/// A base image shown in the overlay, with its top-left corner in overlay
+/// (logical) coordinates.
+struct PlacedBase {
+    image: BaseImage,
+    surface: cairo::ImageSurface,
+    origin: (f64, f64),
+}
 
+/// The monitors GDK knows, as one desktop.
+pub fn desktop_layout() -> DesktopLayout {
+    let mut monitors = Vec::new();
     if let Some(display) = gdk4::Display::default() {
-        let monitors = display.monitors();
-        for i in 0..monitors.n_items() {
-            if let Some(monitor) = monitors.item(i).and_then(|m| m.downcast::<gdk4::Monitor>().ok()) {
-                let geom = monitor.geometry();
-                scale_factor = monitor.scale_factor() as f64;
-                min_x = min_x.min(geom.x());
-                min_y = min_y.min(geom.y());
-                max_x = max_x.max(geom.x() + geom.width());
-                max_y = max_y.max(geom.y() + geom.height());
+        let list = display.monitors();
+        for i in 0..list.n_items() {
+            if let Some(monitor) = list.item(i).and_then(|m| m.downcast::<gdk4::Monitor>().ok()) {
+                monitors.push(layout_monitor(&monitor));
             }
         }
     }
+    DesktopLayout::new(&monitors)
+}
+
+/// The monitor to treat as the primary one. GTK4 has no notion of a primary
+/// monitor, so the first is the best guess, as in `desktop_layout`.
+pub fn primary_monitor() -> Option<gdk4::Monitor> {
+    gdk4::Display::default()?.monitors().item(0)?.downcast().ok()
+}
+
+/// A GDK monitor as `layout` describes it.
+fn layout_monitor(monitor: &gdk4::Monitor) -> layout::Monitor {
+    let geom = monitor.geometry();
+    layout::Monitor {
+        geometry: Rectangle {
+            x: geom.x(),
+            y: geom.y(),
+            width: geom.width().max(0) as u32,
+            height: geom.height().max(0) as u32,
+        },
+        scale: monitor.scale_factor(),
+    }
+}
+
+/// Make `window`, before it is shown, cover the screen. With the `wayland`
+/// feature on a compositor with layer-shell it becomes a layer surface over
+/// one monitor, which is returned; otherwise it is fullscreened across the
+/// desktop.
+fn cover_screen(window: &gtk4::Window) -> Option<gdk4::Monitor> {
+    #[cfg(feature = "wayland")]
+    if let Some(monitor) = super::layer_shell::overlay_monitor() {
+        super::layer_shell::init_overlay(window, &monitor);
+        return Some(monitor);
+    }
+    window.fullscreen();
+    None
+}
 
-    let total_width = max_x - min_x;
-    let total_height = max_y - min_y;
+/// Show a transparent overlay, let the user select an area, and capture it.
+///
+/// With a `base` image the overlay shows that image instead of the live
+/// screen, and the selection is cropped from it. `output` is the encoding
+/// chosen for the action that opened the overlay.
+///
+/// `session` is held until the overlay is gone and any capture it scheduled
+/// has been stored. Returns the overlay window.
+pub fn show_overlay(
+    app: &gtk4::Application,
+    db: Arc<Mutex<Database>>,
+    clipboard: Arc<Mutex<Clipboard>>,
+    config: Arc<Config>,
+    base: Option<BaseImage>,
+    output: CaptureOutput,
+    session: CaptureSession,
+) -> gtk4::Window {
+    let session = Rc::new(session);
 
     let window = gtk4::Window::builder()
         .application(app)
         .decorated(false)
         .title("ClipSnap Overlay")
-        .default_width(total_width)
-        .default_height(total_height)
-        .fullscreened(true)
         .build();
     window.add_css_class("clipsnap-overlay");
+    let layer_monitor = cover_screen(&window);
+
+    // --- 1. Bounding box of the covered monitors, and how it maps to the screen ---
+    // A layer surface covers just its monitor; the selection then maps into
+    // the portal's desktop image through that monitor's position
+    let layout = match &layer_monitor {
+        Some(monitor) => DesktopLayout::new(&[layout_monitor(monitor)]),
+        None => desktop_layout(),
+    };
+    let scale_factor = layout.scale;
+    window.set_default_size(layout.bounds.width as i32, layout.bounds.height as i32);
+
+    // Centre of the primary monitor in overlay coordinates
+    let hint_center = layout.primary.map(|_| layout.primary_center());
 
     // Enhanced CSS for premium look with smooth animations
     let provider = gtk4::CssProvider::new();
@@ -56,6 +131,15 @@ pub fn show_overlay(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
             background-color: transparent;
             transition: all 200ms ease-in-out;
         }
+        .region-entry {
+            background-color: rgba(0, 0, 0, 0.8);
+            color: white;
+            padding: 12px;
+            border-radius: 6px;
+        }
+        .region-entry-error {
+            color: #ff6b6b;
+        }
         .selection-area {
             border-radius: 3px;
             box-shadow: 0 0 20px rgba(0, 0, 0, 0.3);
@@ -75,10 +159,52 @@ pub fn show_overlay(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
         .or_else(|| gdk4::Cursor::from_name("cross", None));
     window.set_cursor(cursor.as_ref());
 
+    // Centre the base image (if any) on the primary monitor, one image pixel per device pixel
+    let base = base.and_then(|image| {
+        let surface = match image_surface(&image) {
+            Ok(surface) => surface,
+            Err(e) => {
+                log::error!("Failed to prepare base image: {}", e);
+                return None;
+            }
+        };
+        let origin = layout.place_image(image.width, image.height);
+        Some(Rc::new(PlacedBase { image, surface, origin }))
+    });
+
+    // Window edges to snap the selection to, in overlay coordinates. Queried
+    // once, before the overlay itself is mapped; a base image has no windows.
+    let snap_windows: Rc<Vec<(f64, f64, f64, f64)>> = Rc::new(if config.capture.snap_to_windows && base.is_none() {
+        match screenshot::client_windows() {
+            Ok(windows) => windows.iter().map(|&w| layout.overlay_rect(w)).collect(),
+            Err(e) => {
+                log::warn!("Window snapping unavailable: {}", e);
+                Vec::new()
+            }
+        }
+    } else {
+        Vec::new()
+    });
+    let snap_distance = config.capture.snap_distance_px as f64;
+    let snap = move |point: (f64, f64)| capture::snap_to_window_edges(point, &snap_windows, snap_distance);
+
+    let selection_mode = config.selection_mode();
+    let hint = match selection_mode {
+        SelectionMode::Drag => "Drag to select  •  Right-drag to redact  •  I to type a region  •  Esc or right-click to cancel",
+        SelectionMode::ClickClick => {
+            "Click two corners to select  •  Right-drag to redact  •  I to type a region  •  Esc or right-click to cancel"
+        }
+    };
+
     // --- 2. State shared between closures ---
     let state = Rc::new(RefCell::new(OverlayState {
         start: None,
         current: None,
+        hint_alpha: if config.ui.show_overlay_hint { 1.0 } else { 0.0 },
+        redactions: Vec::new(),
+        redact_start: None,
+        redact_current: None,
+        clicks: ClickSelection::default(),
     }));
 
     // --- 3. Drawing area ---
@@ -87,14 +213,54 @@ pub fn show_overlay(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
     drawing_area.set_vexpand(true);
 
     // Draw callback: Dim the screen and "cut out" the selection
+    //
+    // Every frame is drawn from scratch, so keep the per-frame work small: the
+    // dimmed base image is rendered once and reused, and in live mode the dim
+    // is a single fill around the selection rather than a paint plus a clear.
+    // Clipping to the changed rectangles wouldn't help: GTK4 can't invalidate
+    // part of a widget and hands each frame a fresh, empty surface, so
+    // whatever the clip left out would be blank rather than kept.
     let state_draw = state.clone();
-    drawing_area.set_draw_func(move |_da, cr, _w, _h| {
+    let base_draw = base.clone();
+    let dimmed_base: RefCell<Option<(i32, i32, cairo::ImageSurface)>> = RefCell::new(None);
+    drawing_area.set_draw_func(move |_da, cr, w, h| {
         let st = state_draw.borrow();
+        let selection = selection_rect(&st);
 
-        // 1. Premium overlay background with subtle gradient
-        cr.set_source_rgba(0.0, 0.0, 0.0, 0.5);
-        cr.set_operator(cairo::Operator::Source);
-        let _ = cr.paint();
+        // 1. Dimmed background
+        if let Some(base) = &base_draw {
+            let mut cache = dimmed_base.borrow_mut();
+            if !matches!(&*cache, Some((cw, ch, _)) if (*cw, *ch) == (w, h)) {
+                *cache = dimmed_base_surface(base, w, h, scale_factor).ok().map(|surface| (w, h, surface));
+            }
+            cr.set_operator(cairo::Operator::Source);
+            match &*cache {
+                Some((_, _, surface)) if cr.set_source_surface(surface, 0.0, 0.0).is_ok() => {
+                    let _ = cr.paint();
+                }
+                _ => {
+                    cr.set_source_rgb(0.0, 0.0, 0.0);
+                    let _ = cr.paint();
+                }
+            }
+        } else {
+            // Everything but the selection; the selection stays transparent
+            cr.set_source_rgba(0.0, 0.0, 0.0, 0.5);
+            cr.set_operator(cairo::Operator::Source);
+            cr.set_fill_rule(cairo::FillRule::EvenOdd);
+            cr.rectangle(0.0, 0.0, w as f64, h as f64);
+            if let Some((x, y, sw, sh)) = selection {
+                cr.rectangle(x, y, sw, sh);
+            }
+            let _ = cr.fill();
+            cr.set_fill_rule(cairo::FillRule::Winding);
+        }
+
+        // Instruction hint for first-time users
+        if st.hint_alpha > 0.0 {
+            let center = hint_center.unwrap_or((w as f64 / 2.0, h as f64 / 2.0));
+            draw_hint(cr, hint, center, st.hint_alpha);
+        }
 
         // 2. "Cut out" the selected region with enhanced visuals
         if let (Some(start), Some(cur)) = (st.start, st.current) {
@@ -104,10 +270,17 @@ pub fn show_overlay(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
             let sel_h = (start.1 - cur.1).abs();
 
             if sel_w > 1.0 && sel_h > 1.0 {
-                // Clear the selection area (make it fully transparent)
-                cr.set_operator(cairo::Operator::Clear);
-                cr.rectangle(sel_x, sel_y, sel_w, sel_h);
-                let _ = cr.fill();
+                if let Some(base) = &base_draw {
+                    // Show the undimmed base image inside the selection
+                    let _ = cr.save();
+                    cr.rectangle(sel_x, sel_y, sel_w, sel_h);
+                    cr.clip();
+                    cr.set_source_rgb(0.0, 0.0, 0.0);
+                    cr.set_operator(cairo::Operator::Source);
+                    let _ = cr.paint();
+                    paint_base(cr, base, scale_factor);
+                    let _ = cr.restore();
+                }
 
                 // Premium selection border with subtle glow effect
                 cr.set_operator(cairo::Operator::Over);
@@ -172,121 +345,676 @@ pub fn show_overlay(app: &gtk4::Application, db: Arc<Mutex<Database>>, clipboard
                 let _ = cr.show_text(&label);
             }
         }
+
+        // 3. Areas marked for redaction, on top of everything
+        let marking = match (st.redact_start, st.redact_current) {
+            (Some(start), Some(cur)) => Some(rect_between(start, cur)),
+            _ => None,
+        };
+        for &area in st.redactions.iter().chain(marking.as_ref()) {
+            draw_redaction(cr, area);
+        }
     });
 
-    // --- 4. Keyboard: ESC cancels ---
+    // Entry for typing the region as "x,y,w,h", shown with I
+    let region_entry = gtk4::Entry::builder()
+        .placeholder_text("x,y,w,h")
+        .width_chars(24)
+        .build();
+    let region_error = gtk4::Label::new(None);
+    region_error.add_css_class("region-entry-error");
+    region_error.set_visible(false);
+    let region_box = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
+    region_box.add_css_class("region-entry");
+    region_box.set_halign(gtk4::Align::Center);
+    region_box.set_valign(gtk4::Align::Start);
+    region_box.set_margin_top(48);
+    region_box.append(&gtk4::Label::new(Some("Region in screen pixels, Enter to capture")));
+    region_box.append(&region_entry);
+    region_box.append(&region_error);
+    region_box.set_visible(false);
+
+    // --- 4. Keyboard: ESC cancels, Backspace drops the last redaction, I
+    // opens the region entry ---
     let win_key = window.clone();
+    let state_key = state.clone();
+    let da_key = drawing_area.clone();
+    let box_key = region_box.clone();
+    let entry_key = region_entry.clone();
     let key_ctl = gtk4::EventControllerKey::new();
     key_ctl.connect_key_pressed(move |_, key, _code, _mods| {
         if key == gdk4::Key::Escape {
-            win_key.close();
+            let mut st = state_key.borrow_mut();
+            if box_key.is_visible() {
+                box_key.set_visible(false);
+                win_key.set_focus(None::<&gtk4::Widget>);
+            } else if st.clicks.cancel() {
+                // Drop the first corner rather than the whole overlay
+                st.start = None;
+                st.current = None;
+                da_key.queue_draw();
+            } else {
+                win_key.close();
+            }
+            return glib::Propagation::Stop;
+        }
+        if box_key.is_visible() {
+            return glib::Propagation::Proceed;
+        }
+        if matches!(key, gdk4::Key::i | gdk4::Key::I) {
+            box_key.set_visible(true);
+            entry_key.grab_focus();
+            return glib::Propagation::Stop;
+        }
+        if key == gdk4::Key::BackSpace && state_key.borrow_mut().redactions.pop().is_some() {
+            da_key.queue_draw();
             return glib::Propagation::Stop;
         }
         glib::Propagation::Proceed
     });
     window.add_controller(key_ctl);
 
+    // --- Right-drag marks an area to redact; a right-click cancels ---
+    let redact_drag = gtk4::GestureDrag::new();
+    redact_drag.set_button(3);
+    let state_redact = state.clone();
+    let da_redact = drawing_area.clone();
+    redact_drag.connect_drag_begin(move |_gesture, x, y| {
+        let mut st = state_redact.borrow_mut();
+        st.redact_start = Some((x, y));
+        st.redact_current = Some((x, y));
+        da_redact.queue_draw();
+    });
+    let state_redact = state.clone();
+    let da_redact = drawing_area.clone();
+    redact_drag.connect_drag_update(move |_gesture, offset_x, offset_y| {
+        let mut st = state_redact.borrow_mut();
+        if let Some(start) = st.redact_start {
+            st.redact_current = Some((start.0 + offset_x, start.1 + offset_y));
+            da_redact.queue_draw();
+        }
+    });
+    let state_redact = state.clone();
+    let da_redact = drawing_area.clone();
+    let win_redact = window.clone();
+    redact_drag.connect_drag_end(move |_gesture, offset_x, offset_y| {
+        let mut st = state_redact.borrow_mut();
+        if capture::is_click((offset_x, offset_y)) {
+            st.redact_start = None;
+            st.redact_current = None;
+            drop(st);
+            win_redact.close();
+            return;
+        }
+        if let Some(start) = st.redact_start.take() {
+            st.redact_current = None;
+            let area = rect_between(start, (start.0 + offset_x, start.1 + offset_y));
+            if area.2 > 1.0 && area.3 > 1.0 {
+                st.redactions.push(area);
+            }
+            da_redact.queue_draw();
+        }
+    });
+    drawing_area.add_controller(redact_drag);
+
     // --- 5. Mouse drag for area selection ---
     let drag = gtk4::GestureDrag::new();
     drag.set_button(1); // left mouse button
 
     let state_begin = state.clone();
     let da_begin = drawing_area.clone();
+    let snap_begin = snap.clone();
     drag.connect_drag_begin(move |_gesture, x, y| {
         let mut st = state_begin.borrow_mut();
-        st.start = Some((x, y));
-        st.current = Some((x, y));
+        let point = snap_begin((x, y));
+        st.start = Some(point);
+        st.current = Some(point);
         da_begin.queue_draw();
+        drop(st);
+        fade_hint(&state_begin, &da_begin);
     });
 
     let state_update = state.clone();
     let da_update = drawing_area.clone();
-    drag.connect_drag_update(move |_gesture, offset_x, offset_y| {
+    let snap_update = snap.clone();
+    drag.connect_drag_update(move |gesture, offset_x, offset_y| {
         let mut st = state_update.borrow_mut();
-        if let Some(start) = st.start {
-            st.current = Some((start.0 + offset_x, start.1 + offset_y));
+        // Offsets are from where the button went down, before any snapping
+        let Some(origin) = gesture.start_point().or(st.start) else {
+            return;
+        };
+        // High-rate mice report sub-pixel moves; only redraw when the
+        // rectangle actually changes on screen
+        let previous = st.current.map(|(x, y)| (x.round(), y.round()));
+        let current = snap_update((origin.0 + offset_x, origin.1 + offset_y));
+        st.current = Some(current);
+        if previous != Some((current.0.round(), current.1.round())) {
+            da_update.queue_draw();
         }
-        da_update.queue_draw();
     });
 
-    // drag-end → capture selection
-    let state_end = state.clone();
-    let win_end = window.clone();
-    drag.connect_drag_end(move |_gesture, offset_x, offset_y| {
-        let st = state_end.borrow();
+    // Capture the selection between two corners in overlay coordinates,
+    // whether dragged, clicked or typed
+    let state_finish = state.clone();
+    let win_finish = window.clone();
+    let app_finish = app.clone();
+    let finish = Rc::new(move |start: (f64, f64), (end_x, end_y): (f64, f64)| {
+        let session = session.clone();
+        let st = state_finish.borrow();
+        let end = (end_x, end_y);
+        let local_x = start.0.min(end_x);
+        let local_y = start.1.min(end_y);
 
-        if let Some(start) = st.start {
-            let end_x = start.0 + offset_x;
-            let end_y = start.1 + offset_y;
+        // Close overlay immediately
+        win_finish.close();
 
-            let sel_w = ((start.0 - end_x).abs() * scale_factor) as u32;
-            let sel_h = ((start.1 - end_y).abs() * scale_factor) as u32;
-
-            // Improved coordinate mapping with proper scaling
-            let local_x = start.0.min(end_x);
-            let local_y = start.1.min(end_y);
-            let global_x = ((local_x * scale_factor) as i32) + min_x;
-            let global_y = ((local_y * scale_factor) as i32) + min_y;
+        if let Some(base) = &base {
+            // Map the selection into base-image pixels and crop it
+            let rect = layout.image_rect(start, end, base.origin);
+            let Some((rgba, width, height)) = base.image.crop(rect) else {
+                return;
+            };
+            // The crop starts at the image edge if the selection hangs off it
+            let crop_origin = (
+                base.origin.0 + rect.x.max(0) as f64 / scale_factor,
+                base.origin.1 + rect.y.max(0) as f64 / scale_factor,
+            );
+            let mut output = output.clone();
+            output.redactions = capture::redaction_areas(&st.redactions, crop_origin, scale_factor);
+            match capture::check_selection(width, height, &config.capture) {
+                capture::SelectionCheck::Capture => {
+                    if let Err(e) = capture::store_capture(&rgba, width, height, &output, &db, &clipboard, &config) {
+                        log::error!("Crop capture failed: {:#}", e);
+                        notifications::notify_screenshot_error(&config, &format!("{:#}", e));
+                    }
+                }
+                capture::SelectionCheck::TooSmall => notifications::notify_screenshot_error(
+                    &config,
+                    &format!(
+                        "Selection {}×{} is smaller than {} px",
+                        width, height, config.capture.min_selection_px
+                    ),
+                ),
+                capture::SelectionCheck::Discard => {}
+            }
+            return;
+        }
 
-            // Close overlay immediately
-            win_end.close();
+        let rect = layout.screen_rect(start, end);
+        let (sel_w, sel_h) = (rect.width, rect.height);
 
-            // Minimum selection size guard (account for scaling)
-            if sel_w < 10 || sel_h < 10 {
+        // Minimum selection size guard (sizes are already in device pixels)
+        match capture::check_selection(sel_w, sel_h, &config.capture) {
+            capture::SelectionCheck::Capture => {}
+            capture::SelectionCheck::TooSmall => {
+                notifications::notify_screenshot_error(
+                    &config,
+                    &format!(
+                        "Selection {}×{} is smaller than {} px",
+                        sel_w, sel_h, config.capture.min_selection_px
+                    ),
+                );
                 return;
             }
+            capture::SelectionCheck::Discard => return,
+        }
+
+        capture::save_last_region(rect);
+
+        // --- Capture the region with proper delay ---
+        let db = db.clone();
+        let clipboard = clipboard.clone();
+        let config = config.clone();
+        let mut output = output.clone();
+        output.redactions = capture::redaction_areas(&st.redactions, (local_x, local_y), scale_factor);
 
-            // --- Capture the region with proper delay ---
-            let db = db.clone();
-            let clipboard = clipboard.clone();
-            
+        show_countdown(&app_finish, config.capture.countdown_seconds, move || {
             // Increased delay to ensure overlay is completely gone (300ms for safety)
             // This prevents any overlay artifacts from appearing in screenshots
             glib::timeout_add_local_once(std::time::Duration::from_millis(300), move || {
-                match screenshot::capture_region(global_x, global_y, sel_w, sel_h) {
-                    Ok((raw_bgra, width, height)) => {
-                        let rgba = screenshot::bgra_to_rgba(&raw_bgra);
-                        
-                        // Encode to PNG
-                        match screenshot::encode_png(&rgba, width, height) {
-                            Ok(png_bytes) => {
-                                let thumb = screenshot::create_thumbnail(&png_bytes, 150).unwrap_or_default();
-
-                                // Copy to shared clipboard
-                                if let Ok(mut cb) = clipboard.lock() {
-                                    if let Err(e) = clipboard::set_clipboard_image(&mut cb, &rgba, width as usize, height as usize) {
-                                        log::error!("Failed to copy to clipboard: {}", e);
-                                        notifications::notify_screenshot_error("Clipboard copy failed");
-                                    }
-                                }
-
-                                // Store in database
-                                if let Ok(db) = db.lock() {
-                                    if let Err(e) = db.insert_image(&png_bytes, &thumb) {
-                                        log::error!("Failed to save screenshot: {}", e);
-                                    }
-                                }
-
-                                let tmp_path = std::env::temp_dir().join("clipsnap_last.png");
-                                let _ = std::fs::write(&tmp_path, &png_bytes);
-                                notifications::notify_screenshot_success(&tmp_path);
-                            }
-                            Err(e) => {
-                                log::error!("PNG encoding failed: {}", e);
-                                notifications::notify_screenshot_error("PNG encoding failed");
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("Region capture failed: {}", e);
-                        notifications::notify_screenshot_error(&format!("Capture failed: {}", e));
+                capture::capture_to_history(rect, &output, &db, &clipboard, &config, session);
+            });
+        });
+    });
+
+    match selection_mode {
+        SelectionMode::Drag => {
+            // drag-end → capture selection; a click without a drag cancels
+            let state_end = state.clone();
+            let finish_drag = finish.clone();
+            let win_end = window.clone();
+            drag.connect_drag_end(move |gesture, offset_x, offset_y| {
+                if capture::is_click((offset_x, offset_y)) {
+                    win_end.close();
+                    return;
+                }
+                let Some(start) = state_end.borrow().start else {
+                    return;
+                };
+                let origin = gesture.start_point().unwrap_or(start);
+                finish_drag(start, snap((origin.0 + offset_x, origin.1 + offset_y)));
+            });
+            drawing_area.add_controller(drag);
+        }
+        SelectionMode::ClickClick => {
+            // First click sets a corner, the selection follows the pointer,
+            // second click captures
+            let click = gtk4::GestureClick::new();
+            click.set_button(1);
+            let state_click = state.clone();
+            let da_click = drawing_area.clone();
+            let finish_click = finish.clone();
+            let snap_click = snap.clone();
+            click.connect_pressed(move |_gesture, _n_press, x, y| {
+                let point = snap_click((x, y));
+                let corners = state_click.borrow_mut().clicks.click(point);
+                match corners {
+                    Some((start, end)) => finish_click(start, end),
+                    None => {
+                        let mut st = state_click.borrow_mut();
+                        st.start = Some(point);
+                        st.current = Some(point);
+                        drop(st);
+                        da_click.queue_draw();
+                        fade_hint(&state_click, &da_click);
                     }
                 }
             });
+            drawing_area.add_controller(click);
+
+            let motion = gtk4::EventControllerMotion::new();
+            let state_motion = state.clone();
+            let da_motion = drawing_area.clone();
+            motion.connect_motion(move |_, x, y| {
+                let mut st = state_motion.borrow_mut();
+                if st.clicks.corner().is_none() {
+                    return;
+                }
+                let previous = st.current.map(|(x, y)| (x.round(), y.round()));
+                let current = snap((x, y));
+                st.current = Some(current);
+                if previous != Some((current.0.round(), current.1.round())) {
+                    da_motion.queue_draw();
+                }
+            });
+            drawing_area.add_controller(motion);
+        }
+    }
+
+    // --- 6. Typed coordinates: Enter captures the region, bad input is
+    // reported under the entry ---
+    let screen_bounds = layout.screen_bounds();
+    let error_entry = region_error.clone();
+    region_entry.connect_changed(move |_| error_entry.set_visible(false));
+    let error_entry = region_error.clone();
+    region_entry.connect_activate(move |entry| match capture::parse_typed_region(&entry.text(), screen_bounds) {
+        Ok(rect) => {
+            let (x, y, w, h) = layout.overlay_rect(rect);
+            finish((x, y), (x + w, y + h));
+        }
+        Err(message) => {
+            error_entry.set_text(&message);
+            error_entry.set_visible(true);
+        }
+    });
+
+    let layers = gtk4::Overlay::new();
+    layers.set_child(Some(&drawing_area));
+    layers.add_overlay(&region_box);
+    window.set_child(Some(&layers));
+    window.present();
+    window
+}
+
+/// Let the user click a window and capture just that window.
+///
+/// A lightly dimmed overlay catches the click; once it has closed, whatever
+/// window is under the pointer is looked up and captured. Clicking the bare
+/// desktop captures the whole screen. `session` is held as in [`show_overlay`].
+/// Returns the picker window.
+pub fn show_window_picker(
+    app: &gtk4::Application,
+    db: Arc<Mutex<Database>>,
+    clipboard: Arc<Mutex<Clipboard>>,
+    config: Arc<Config>,
+    output: CaptureOutput,
+    session: CaptureSession,
+) -> gtk4::Window {
+    let session = Rc::new(session);
+    let app_click = app.clone();
+    show_click_picker(app, "ClipSnap Window Picker", "Click a window to capture", move || {
+        let (db, clipboard, config, output) = (db.clone(), clipboard.clone(), config.clone(), output.clone());
+        let session = session.clone();
+        // The window is looked up when the countdown ends, so it can be one
+        // (a dialog, a notification) that only appeared during the countdown
+        show_countdown(&app_click, config.capture.countdown_seconds, move || {
+            // Same settle delay as region capture, so the picker is gone from
+            // both the pointer lookup and the captured pixels
+            glib::timeout_add_local_once(std::time::Duration::from_millis(300), move || {
+                capture::capture_window_under_pointer(&output, &db, &clipboard, &config, session);
+            });
+        });
+    })
+}
+
+/// Let the user click a point and copy the color of the pixel there
+/// (`shortcuts.pick_color`). `session` is held as in [`show_overlay`].
+/// Returns the picker window.
+pub fn show_color_picker(
+    app: &gtk4::Application,
+    db: Arc<Mutex<Database>>,
+    clipboard: Arc<Mutex<Clipboard>>,
+    config: Arc<Config>,
+    hashes: SharedHashes,
+    session: CaptureSession,
+) -> gtk4::Window {
+    let session = Rc::new(session);
+    show_click_picker(app, "ClipSnap Color Picker", "Click to copy a color", move || {
+        let (db, clipboard, config, hashes) = (db.clone(), clipboard.clone(), config.clone(), hashes.clone());
+        let session = session.clone();
+        // The dimmed picker must be gone before the pixel is read
+        glib::timeout_add_local_once(std::time::Duration::from_millis(300), move || {
+            color::pick_color(&db, &clipboard, &hashes, &config);
+            drop(session);
+        });
+    })
+}
+
+/// A lightly dimmed window over the screen (see [`cover_screen`]) showing
+/// `hint`. A left click closes it and calls `on_click`; Escape or a right
+/// click just closes it.
+fn show_click_picker(
+    app: &gtk4::Application,
+    title: &str,
+    hint: &'static str,
+    on_click: impl Fn() + 'static,
+) -> gtk4::Window {
+    let window = gtk4::Window::builder()
+        .application(app)
+        .decorated(false)
+        .title(title)
+        .build();
+    window.add_css_class("clipsnap-overlay");
+    cover_screen(&window);
+
+    let cursor = gdk4::Cursor::from_name("crosshair", None)
+        .or_else(|| gdk4::Cursor::from_name("cross", None));
+    window.set_cursor(cursor.as_ref());
+
+    let drawing_area = gtk4::DrawingArea::new();
+    drawing_area.set_hexpand(true);
+    drawing_area.set_vexpand(true);
+    let hint = format!("{}  •  Esc or right-click to cancel", hint);
+    drawing_area.set_draw_func(move |_da, cr, w, h| {
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.25);
+        cr.set_operator(cairo::Operator::Source);
+        let _ = cr.paint();
+        draw_hint(cr, &hint, (w as f64 / 2.0, h as f64 / 2.0), 1.0);
+    });
+
+    let win_key = window.clone();
+    let key_ctl = gtk4::EventControllerKey::new();
+    key_ctl.connect_key_pressed(move |_, key, _code, _mods| {
+        if key == gdk4::Key::Escape {
+            win_key.close();
+            return glib::Propagation::Stop;
         }
+        glib::Propagation::Proceed
+    });
+    window.add_controller(key_ctl);
+
+    let click = gtk4::GestureClick::new();
+    click.set_button(1);
+    let win_click = window.clone();
+    click.connect_released(move |_gesture, _n, _x, _y| {
+        win_click.close();
+        on_click();
     });
+    drawing_area.add_controller(click);
+
+    let cancel = gtk4::GestureClick::new();
+    cancel.set_button(3);
+    let win_cancel = window.clone();
+    cancel.connect_released(move |_gesture, _n, _x, _y| win_cancel.close());
+    drawing_area.add_controller(cancel);
 
-    drawing_area.add_controller(drag);
     window.set_child(Some(&drawing_area));
     window.present();
+    window
+}
+
+/// Count down `seconds` in large digits on the primary monitor, then call
+/// `on_done` once the countdown has closed (`capture.countdown_seconds`).
+/// With 0 seconds `on_done` runs at once.
+///
+/// The countdown covers the primary monitor and keeps the keyboard focus, so
+/// it stays on top and Escape cancels it; `on_done` is then dropped uncalled.
+pub fn show_countdown(app: &gtk4::Application, seconds: u32, on_done: impl FnOnce() + 'static) {
+    if seconds == 0 {
+        on_done();
+        return;
+    }
+
+    let window = gtk4::Window::builder()
+        .application(app)
+        .decorated(false)
+        .title("ClipSnap Countdown")
+        .build();
+    window.add_css_class("clipsnap-countdown");
+    add_countdown_style();
+    match primary_monitor() {
+        Some(monitor) => window.fullscreen_on_monitor(&monitor),
+        None => window.fullscreen(),
+    }
+
+    let remaining = Rc::new(Cell::new(seconds));
+    let drawing_area = gtk4::DrawingArea::new();
+    drawing_area.set_hexpand(true);
+    drawing_area.set_vexpand(true);
+    let remaining_draw = remaining.clone();
+    drawing_area.set_draw_func(move |_da, cr, w, h| {
+        cr.set_operator(cairo::Operator::Source);
+        cr.set_source_rgba(0.0, 0.0, 0.0, 0.0);
+        let _ = cr.paint();
+        draw_countdown(cr, remaining_draw.get(), (w as f64 / 2.0, h as f64 / 2.0));
+    });
+
+    // Taken when the countdown finishes or is cancelled
+    let on_done = Rc::new(RefCell::new(Some(on_done)));
+
+    let (win_key, on_cancel) = (window.clone(), on_done.clone());
+    let key_ctl = gtk4::EventControllerKey::new();
+    key_ctl.connect_key_pressed(move |_, key, _code, _mods| {
+        if key == gdk4::Key::Escape {
+            log::info!("Capture countdown cancelled");
+            on_cancel.borrow_mut().take();
+            win_key.close();
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    });
+    window.add_controller(key_ctl);
+
+    let (win_tick, da_tick) = (window.clone(), drawing_area.clone());
+    glib::timeout_add_seconds_local(1, move || {
+        if on_done.borrow().is_none() {
+            return glib::ControlFlow::Break;
+        }
+        let left = remaining.get().saturating_sub(1);
+        if left > 0 {
+            remaining.set(left);
+            da_tick.queue_draw();
+            return glib::ControlFlow::Continue;
+        }
+        win_tick.close();
+        let done = on_done.borrow_mut().take();
+        if let Some(done) = done {
+            done();
+        }
+        glib::ControlFlow::Break
+    });
+
+    window.set_child(Some(&drawing_area));
+    window.present();
+}
+
+/// Register the countdown's transparent background, once per process.
+fn add_countdown_style() {
+    static ADDED: std::sync::Once = std::sync::Once::new();
+    ADDED.call_once(|| {
+        let Some(display) = gdk4::Display::default() else {
+            return;
+        };
+        let provider = gtk4::CssProvider::new();
+        provider.load_from_data(".clipsnap-countdown { background-color: transparent; }");
+        gtk4::style_context_add_provider_for_display(&display, &provider, gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    });
+}
+
+/// A countdown number in a dark disc centred on `center`.
+fn draw_countdown(cr: &cairo::Context, number: u32, center: (f64, f64)) {
+    cr.set_operator(cairo::Operator::Over);
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.6);
+    cr.arc(center.0, center.1, 56.0, 0.0, std::f64::consts::TAU);
+    let _ = cr.fill();
+
+    let text = number.to_string();
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Bold);
+    cr.set_font_size(64.0);
+    let Ok(extents) = cr.text_extents(&text) else {
+        return;
+    };
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.95);
+    cr.move_to(
+        center.0 - extents.width() / 2.0 - extents.x_bearing(),
+        center.1 - extents.height() / 2.0 - extents.y_bearing(),
+    );
+    let _ = cr.show_text(&text);
+}
+
+/// Fade the instruction hint out over ~200ms.
+fn fade_hint(state: &Rc<RefCell<OverlayState>>, da: &gtk4::DrawingArea) {
+    if state.borrow().hint_alpha <= 0.0 {
+        return;
+    }
+    let state = state.clone();
+    let da = da.clone();
+    glib::timeout_add_local(std::time::Duration::from_millis(16), move || {
+        let mut st = state.borrow_mut();
+        st.hint_alpha = (st.hint_alpha - 0.08).max(0.0);
+        da.queue_draw();
+        if st.hint_alpha > 0.0 {
+            glib::ControlFlow::Continue
+        } else {
+            glib::ControlFlow::Break
+        }
+    });
+}
+
+/// The selection as `(x, y, width, height)` once it is big enough to draw.
+fn selection_rect(st: &OverlayState) -> Option<(f64, f64, f64, f64)> {
+    let (start, cur) = (st.start?, st.current?);
+    let (w, h) = ((start.0 - cur.0).abs(), (start.1 - cur.1).abs());
+    (w > 1.0 && h > 1.0).then(|| (start.0.min(cur.0), start.1.min(cur.1), w, h))
+}
+
+/// The base image on black, dimmed, as a `width`×`height` (logical) surface at
+/// device resolution, ready to be painted in one step.
+fn dimmed_base_surface(
+    base: &PlacedBase,
+    width: i32,
+    height: i32,
+    scale_factor: f64,
+) -> Result<cairo::ImageSurface, cairo::Error> {
+    let surface = cairo::ImageSurface::create(
+        cairo::Format::ARgb32,
+        (width as f64 * scale_factor).ceil() as i32,
+        (height as f64 * scale_factor).ceil() as i32,
+    )?;
+    surface.set_device_scale(scale_factor, scale_factor);
+    let cr = cairo::Context::new(&surface)?;
+    cr.set_source_rgb(0.0, 0.0, 0.0);
+    cr.paint()?;
+    paint_base(&cr, base, scale_factor);
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.5);
+    cr.set_operator(cairo::Operator::Over);
+    cr.paint()?;
+    drop(cr);
+    Ok(surface)
+}
+
+/// The rectangle spanned by two corners, as (x, y, width, height).
+fn rect_between(a: (f64, f64), b: (f64, f64)) -> (f64, f64, f64, f64) {
+    (a.0.min(b.0), a.1.min(b.1), (a.0 - b.0).abs(), (a.1 - b.1).abs())
+}
+
+/// Mark an area to be redacted: darkened, with a dashed red outline.
+fn draw_redaction(cr: &cairo::Context, (x, y, w, h): (f64, f64, f64, f64)) {
+    cr.set_operator(cairo::Operator::Over);
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.6);
+    cr.rectangle(x, y, w, h);
+    let _ = cr.fill();
+    cr.set_source_rgba(1.0, 0.3, 0.3, 0.9);
+    cr.set_line_width(1.5);
+    cr.set_dash(&[6.0, 4.0], 0.0);
+    cr.rectangle(x, y, w, h);
+    let _ = cr.stroke();
+    cr.set_dash(&[], 0.0);
+}
+
+/// Draw an instruction pill such as "Drag to select • Esc to cancel" centred
+/// on `center`.
+fn draw_hint(cr: &cairo::Context, text: &str, center: (f64, f64), alpha: f64) {
+    cr.set_operator(cairo::Operator::Over);
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Bold);
+    cr.set_font_size(18.0);
+    let Ok(extents) = cr.text_extents(text) else {
+        return;
+    };
+
+    let pad_x = 20.0;
+    let pad_y = 12.0;
+    let box_w = extents.width() + pad_x * 2.0;
+    let box_h = extents.height() + pad_y * 2.0;
+    let box_x = center.0 - box_w / 2.0;
+    let box_y = center.1 - box_h / 2.0;
+
+    cr.set_source_rgba(0.0, 0.0, 0.0, 0.7 * alpha);
+    cr.rectangle(box_x, box_y, box_w, box_h);
+    let _ = cr.fill();
+
+    cr.set_source_rgba(1.0, 1.0, 1.0, 0.95 * alpha);
+    cr.move_to(
+        center.0 - extents.width() / 2.0 - extents.x_bearing(),
+        center.1 - extents.height() / 2.0 - extents.y_bearing(),
+    );
+    let _ = cr.show_text(text);
+}
+
+/// Convert RGBA pixels into a cairo surface (premultiplied, native-endian ARGB).
+fn image_surface(image: &BaseImage) -> Result<cairo::ImageSurface, cairo::Error> {
+    let stride = cairo::Format::ARgb32.stride_for_width(image.width)?;
+    let mut data = vec![0u8; stride as usize * image.height as usize];
+    for (y, row) in image.rgba.chunks_exact(image.width as usize * 4).enumerate() {
+        let out = &mut data[y * stride as usize..];
+        for (x, px) in row.chunks_exact(4).enumerate() {
+            let a = px[3] as u32;
+            let premul = |c: u8| ((c as u32 * a + 127) / 255) as u8;
+            let argb = u32::from_be_bytes([px[3], premul(px[0]), premul(px[1]), premul(px[2])]);
+            out[x * 4..x * 4 + 4].copy_from_slice(&argb.to_ne_bytes());
+        }
+    }
+    cairo::ImageSurface::create_for_data(data, cairo::Format::ARgb32, image.width as i32, image.height as i32, stride)
+}
+
+/// Paint the base image at its placed position.
+fn paint_base(cr: &cairo::Context, base: &PlacedBase, scale_factor: f64) {
+    let _ = cr.save();
+    cr.translate(base.origin.0, base.origin.1);
+    cr.scale(1.0 / scale_factor, 1.0 / scale_factor);
+    cr.set_operator(cairo::Operator::Over);
+    if cr.set_source_surface(&base.surface, 0.0, 0.0).is_ok() {
+        let _ = cr.paint();
+    }
+    let _ = cr.restore();
 }
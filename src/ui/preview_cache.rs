@@ -0,0 +1,119 @@
+//! Card previews kept for the whole session, so reopening the history
+//! dialog doesn't truncate every text or decode every thumbnail again.
+//!
+//! Entries are keyed by the content hash stored with them, so a preview
+//! stays valid however often the entry is restored or re-sorted. Entries
+//! without one (snippets, rows stored before hashes were kept) are previewed
+//! afresh each time.
+
+use crate::models::ContentType;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Characters of text shown on a card.
+const PREVIEW_CHARS: usize = 150;
+
+/// Previews kept per kind before the cache starts over, bounding its size as
+/// the history rotates.
+const MAX_CACHED: usize = 2000;
+
+/// Shared between every history dialog opened on the GTK main thread.
+pub type SharedPreviewCache = Rc<RefCell<PreviewCache>>;
+
+#[derive(Default)]
+pub struct PreviewCache {
+    texts: HashMap<String, Rc<str>>,
+    textures: HashMap<String, Option<gdk4::Texture>>,
+}
+
+impl PreviewCache {
+    pub fn shared() -> SharedPreviewCache {
+        Rc::new(RefCell::new(PreviewCache::default()))
+    }
+
+    /// The card text for `text`, truncated to `PREVIEW_CHARS`. `hash` is the
+    /// entry's stored content hash.
+    pub fn text_preview(&mut self, hash: Option<&str>, text: &str) -> Rc<str> {
+        let Some(key) = hash else {
+            return text_preview(text).into();
+        };
+        if self.texts.len() >= MAX_CACHED && !self.texts.contains_key(key) {
+            self.texts.clear();
+        }
+        self.texts.entry(key.to_string()).or_insert_with(|| text_preview(text).into()).clone()
+    }
+
+    /// The texture for PNG thumbnail bytes, decoded with `decode` on first
+    /// use. `hash` is the entry's stored content hash. Thumbnails that fail
+    /// to decode are remembered as `None` until forgotten.
+    pub fn texture(
+        &mut self,
+        hash: Option<&str>,
+        png_bytes: &[u8],
+        decode: impl FnOnce(&[u8]) -> Option<gdk4::Texture>,
+    ) -> Option<gdk4::Texture> {
+        let Some(key) = hash else {
+            return decode(png_bytes);
+        };
+        if self.textures.len() >= MAX_CACHED && !self.textures.contains_key(key) {
+            self.textures.clear();
+        }
+        self.textures.entry(key.to_string()).or_insert_with(|| decode(png_bytes)).clone()
+    }
+
+    /// Drop the previews of an entry that was deleted or given a new
+    /// thumbnail.
+    pub fn forget(&mut self, hash: Option<&str>) {
+        if let Some(key) = hash {
+            self.texts.remove(key);
+            self.textures.remove(key);
+        }
+    }
+
+    /// Drop every preview of one type, after its tab is cleared.
+    pub fn clear(&mut self, content_type: ContentType) {
+        match content_type {
+            ContentType::Text => self.texts.clear(),
+            ContentType::Image => self.textures.clear(),
+        }
+    }
+}
+
+/// `text` cut to `PREVIEW_CHARS` characters, with an ellipsis if cut.
+fn text_preview(text: &str) -> String {
+    match text.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_preview() {
+        assert_eq!(text_preview("short"), "short");
+        let long = "é".repeat(PREVIEW_CHARS + 1);
+        assert_eq!(text_preview(&long), format!("{}…", "é".repeat(PREVIEW_CHARS)));
+        assert_eq!(text_preview(&"x".repeat(PREVIEW_CHARS)), "x".repeat(PREVIEW_CHARS));
+    }
+
+    #[test]
+    fn test_text_previews_are_reused_until_forgotten() {
+        let mut cache = PreviewCache::default();
+        let first = cache.text_preview(Some("h1"), "hello");
+        assert!(Rc::ptr_eq(&first, &cache.text_preview(Some("h1"), "hello")));
+        cache.forget(Some("h1"));
+        assert!(!Rc::ptr_eq(&first, &cache.text_preview(Some("h1"), "hello")));
+    }
+
+    #[test]
+    fn test_text_previews_without_a_hash_are_not_kept() {
+        let mut cache = PreviewCache::default();
+        let first = cache.text_preview(None, "hello");
+        assert!(!Rc::ptr_eq(&first, &cache.text_preview(None, "hello")));
+        assert!(cache.texts.is_empty());
+    }
+}
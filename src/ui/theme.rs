@@ -0,0 +1,38 @@
+//! Light or dark windows per `ui.theme`. The history window's CSS uses the
+//! theme colors, so it follows the variant GTK picks.
+
+use crate::{config, portal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the last applied theme is "auto", checked by the color-scheme
+/// watcher so a reload to "dark" or "light" isn't overridden.
+static FOLLOW_DESKTOP: AtomicBool = AtomicBool::new(false);
+/// Whether the color-scheme watcher is subscribed; it only ever is once.
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Apply `ui.theme`: "dark" and "light" force that variant, "auto" follows
+/// the desktop's color scheme through the settings portal as it changes.
+/// Without a portal, "auto" leaves GTK's own choice alone. Called again
+/// when the config is reloaded.
+pub fn apply(theme: &str) {
+    let Some(settings) = gtk4::Settings::default() else {
+        return;
+    };
+    let forced = config::theme_prefers_dark(theme);
+    FOLLOW_DESKTOP.store(forced.is_none(), Ordering::Relaxed);
+    if let Some(dark) = forced {
+        settings.set_gtk_application_prefer_dark_theme(dark);
+        return;
+    }
+    if let Some(dark) = portal::prefers_dark_scheme() {
+        settings.set_gtk_application_prefer_dark_theme(dark);
+    }
+    if WATCHING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    portal::watch_color_scheme(move |dark| {
+        if FOLLOW_DESKTOP.load(Ordering::Relaxed) {
+            settings.set_gtk_application_prefer_dark_theme(dark.unwrap_or(false));
+        }
+    });
+}